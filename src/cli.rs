@@ -0,0 +1,101 @@
+use std::sync::Arc;
+use crate::model::audit_log::AuditContext;
+use crate::model::tenant::Tenant;
+use crate::service::ship_track_service::ShipTrackService;
+
+const PACK_USAGE: &str = "用法: drone_al pack-points [--dry-run]";
+const IMPORT_USAGE: &str = "用法: drone_al import --org <orgId> --wind-farm <windFarmId> [--drone-id <droneId>] [--owner <owner>] <文件>...";
+
+// 命令行导入历史航迹文件：drone_al import --org ... --wind-farm ... a.gpx b.kml
+// 与 POST /track/import 使用同一套解析与去重逻辑，返回进程退出码
+pub async fn run_import(service: Arc<ShipTrackService>, args: &[String]) -> i32 {
+    let (mut org_id, mut wind_farm_id, mut drone_id, mut owner) = (None, None, None, None);
+    let mut files = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let slot = match arg.as_str() {
+            "--org" => &mut org_id,
+            "--wind-farm" => &mut wind_farm_id,
+            "--drone-id" => &mut drone_id,
+            "--owner" => &mut owner,
+            _ => {
+                files.push(arg.clone());
+                continue;
+            }
+        };
+        *slot = args.next().cloned();
+    }
+    let (Some(org_id), Some(wind_farm_id)) = (org_id, wind_farm_id) else {
+        eprintln!("{}", IMPORT_USAGE);
+        return 2;
+    };
+    if files.is_empty() {
+        eprintln!("{}", IMPORT_USAGE);
+        return 2;
+    }
+
+    let ctx = AuditContext {
+        actor: std::env::var("USER").unwrap_or_else(|_| "cli".to_string()),
+        route: "cli import".to_string(),
+        client_ip: None,
+        tenant: Tenant { org_id, wind_farm_id },
+    };
+    let mut failed = 0;
+    for path in files {
+        let result = match std::fs::read_to_string(&path) {
+            Ok(content) => service.import_file(&path, &content, drone_id.clone(), owner.clone(), &ctx).await,
+            Err(e) => {
+                eprintln!("{}: 读取失败: {}", path, e);
+                failed += 1;
+                continue;
+            }
+        };
+        for track in &result.tracks {
+            let status = if track.duplicate { "已存在" } else { "已导入" };
+            println!("{}: {} {} ({} 个点)", path, status, track.id.to_hex(), track.total_points);
+        }
+        if let Some(error) = result.error {
+            eprintln!("{}: {}", path, error);
+            failed += 1;
+        }
+    }
+    if failed > 0 { 1 } else { 0 }
+}
+
+// 把已写满的航迹点块改写为压缩编码，并输出压缩前后的大小：drone_al pack-points [--dry-run]
+// --dry-run 只统计不写入。之后新写满的块是否压缩由 TRACK_POINT_ENCODING 决定。
+// 编码按经纬度 1e-7 度、高度 mm、航向 0.01 度、速度 cm/s 量化，超出这个精度的块保持原样，不会改变存储的值
+pub async fn run_pack_points(service: Arc<ShipTrackService>, args: &[String]) -> i32 {
+    let dry_run = match args {
+        [] => false,
+        [flag] if flag == "--dry-run" => true,
+        _ => {
+            eprintln!("{}", PACK_USAGE);
+            return 2;
+        }
+    };
+    let stats = match service.pack_points(dry_run).await {
+        Ok(stats) => stats,
+        Err(e) => {
+            eprintln!("压缩航迹点失败: {}", e);
+            return 1;
+        }
+    };
+    let action = if dry_run { "可压缩" } else { "已压缩" };
+    println!("{} {} 个航迹文档、{} 个分段，共 {} 个点；跳过已压缩的 {} 个", action, stats.tracks, stats.segments, stats.points, stats.skipped);
+    if stats.inexact > 0 {
+        println!(
+            "{} 个块含有超出编码精度（经纬度 1e-7 度、高度 mm、航向 0.01 度、速度 cm/s）的值，压缩会改变存储的值，保持数组格式",
+            stats.inexact,
+        );
+    }
+    println!(
+        "coordinates 大小: {} → {} 字节（{:.1}%，每点 {:.1} → {:.1} 字节）",
+        stats.array_bytes,
+        stats.packed_bytes,
+        stats.ratio() * 100.0,
+        stats.array_bytes as f64 / stats.points.max(1) as f64,
+        stats.packed_bytes as f64 / stats.points.max(1) as f64,
+    );
+    0
+}
@@ -0,0 +1,56 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use axum::extract::{ConnectInfo, FromRequestParts, Query, State};
+use axum::http::request::Parts;
+use axum::routing::get;
+use axum::{Json, Router};
+use crate::error::AppError;
+use crate::middleware::rate_limit::ClientIp;
+use crate::model::audit_log::{AuditContext, AuditLogQuery, AuditLogResponseDto};
+use crate::model::tenant::Tenant;
+use crate::service::audit_service::AuditService;
+
+pub fn audit_routes() -> Router<Arc<AuditService>> {
+    Router::new()
+        .route("/audit_log", get(query_audit_log))
+}
+
+async fn query_audit_log(
+    State(service): State<Arc<AuditService>>,
+    tenant: Tenant,
+    Query(query): Query<AuditLogQuery>,
+) -> Result<Json<Vec<AuditLogResponseDto>>, AppError> {
+    let logs = service.query(&tenant, query).await?;
+    Ok(Json(logs.into_iter().map(AuditLogResponseDto::from).collect()))
+}
+
+// 从请求头 X-Actor 获取操作人
+impl<S: Send + Sync> FromRequestParts<S> for AuditContext {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let tenant = Tenant::from_request_parts(parts, state).await?;
+        let actor = parts.headers
+            .get("x-actor")
+            .and_then(|v| v.to_str().ok())
+            .filter(|v| !v.is_empty())
+            .unwrap_or("anonymous")
+            .to_string();
+        Ok(AuditContext {
+            actor,
+            route: format!("{} {}", parts.method, parts.uri.path()),
+            client_ip: client_ip(parts),
+            tenant,
+        })
+    }
+}
+
+// 客户端 IP 取限流中间件按受信任代理解析出的地址，没有经过中间件时取连接地址
+pub fn client_ip(parts: &Parts) -> Option<String> {
+    let resolved = parts.extensions.get::<ClientIp>().map(|ClientIp(ip)| ip.to_string());
+    resolved.or_else(|| {
+        parts.extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string())
+    })
+}
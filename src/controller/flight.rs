@@ -1,20 +1,27 @@
 use std::sync::Arc;
-use axum::extract::State;
+use axum::extract::{Path, State};
 use axum::{Json, Router};
 use axum::routing::{delete, get, post, put};
 use mongodb::bson::oid::ObjectId;
 use tracing::log::error;
 use crate::error::AppError;
+use crate::model::audit_log::AuditContext;
 use crate::service::flight_service::FlightService;
-use crate::model::flight::{Flight, FlightDto};
-use crate::service::ship_track_service::ShipTrackService;
+use crate::model::flight::{Flight, FlightBulkDeleteFilter, FlightResponseDto};
+use crate::model::tenant::Tenant;
+use crate::model::trash::{BulkDeleteRequestDto, TrashEntryDto};
 
 pub fn flight_routes() -> Router<Arc<FlightService>> {
     Router::new()
         .route("/flight", post(create_empty_flight))
+        .route("/flight/{id}", put(update_flight).delete(delete_flight))
+        .route("/flight/trash", get(get_flight_trash))
+        .route("/flight/delete_all", delete(bulk_delete_flights))
+        .route("/flight/{id}/restore", put(restore_flight))
 }
 async fn create_empty_flight(
     State(service): State<Arc<FlightService>>,
+    ctx: AuditContext,
     Json(track_id_raw): Json<String>,
 ) ->Result<Json<String>,AppError>{
     let new_id = ObjectId::new(); // 服务器生成 _id
@@ -24,14 +31,62 @@ async fn create_empty_flight(
     // 从 payload 和服务器生成的值构建 Flight 实例
     let flight = Flight {
         id: new_id,
+        org_id: String::new(),
+        wind_farm_id: String::new(),
         track_id,
+        mission_id: None,
         battery_capacity: vec![],
         estimated_remaining_usage_time: vec![],
         cabin_temperature: vec![],
         aircraft_altitude: vec![],
         distance_to_fan: vec![],
         air_pressure: vec![],
+        deleted_at: None,
+        deleted_by: None,
     };
-    service.create(flight).await?;
+    service.create(flight, &ctx).await?;
     Ok(Json(new_id.to_hex()))
-}
\ No newline at end of file
+}
+async fn update_flight(
+    State(service): State<Arc<FlightService>>,
+    Path(id): Path<String>,
+    ctx: AuditContext,
+    Json(flight): Json<Flight>,
+) -> Result<Json<&'static str>, AppError> {
+    service.update(&id, flight, &ctx).await?;
+    Ok(Json("ok"))
+}
+async fn delete_flight(
+    State(service): State<Arc<FlightService>>,
+    Path(id): Path<String>,
+    ctx: AuditContext,
+) -> Result<Json<&'static str>, AppError> {
+    service.delete(&id, &ctx).await?;
+    Ok(Json("ok"))
+}
+// 批量删除：必须提供筛选条件，并用第一次请求返回的确认令牌再次提交
+async fn bulk_delete_flights(
+    State(service): State<Arc<FlightService>>,
+    ctx: AuditContext,
+    Json(request): Json<BulkDeleteRequestDto<FlightBulkDeleteFilter>>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    Ok(Json(service.bulk_delete(request, &ctx).await?))
+}
+async fn get_flight_trash(
+    State(service): State<Arc<FlightService>>,
+    tenant: Tenant,
+) -> Result<Json<Vec<TrashEntryDto<FlightResponseDto>>>, AppError> {
+    let flights = service.get_trash(&tenant).await?;
+    Ok(Json(flights.into_iter().map(|flight| {
+        let (deleted_at, deleted_by) = (flight.deleted_at, flight.deleted_by.clone());
+        TrashEntryDto::new(FlightResponseDto::from(flight), deleted_at, deleted_by)
+    }).collect()))
+}
+async fn restore_flight(
+    State(service): State<Arc<FlightService>>,
+    Path(id): Path<String>,
+    ctx: AuditContext,
+) -> Result<Json<Option<FlightResponseDto>>, AppError> {
+    let res = service.restore(&id, &ctx).await?;
+    Ok(Json(res.map(FlightResponseDto::from)))
+}
@@ -0,0 +1,100 @@
+use std::sync::Arc;
+use axum::extract::{Path, Query, State};
+use axum::routing::{get, put};
+use axum::{Json, Router};
+use crate::error::AppError;
+use crate::model::audit_log::AuditContext;
+use crate::model::geofence::{AlertQuery, Geofence, GeofenceAlertDto, GeofenceRequestDto, GeofenceResponseDto};
+use crate::model::tenant::Tenant;
+use crate::model::trash::TrashEntryDto;
+use crate::service::geofence_service::GeofenceService;
+
+pub fn geofence_routes() -> Router<Arc<GeofenceService>> {
+    Router::new()
+        .route("/geofence", get(list_geofences).post(create_geofence))
+        .route("/geofence/alerts", get(list_alerts))
+        .route("/geofence/trash", get(get_geofence_trash))
+        .route("/geofence/{id}", get(get_geofence).put(update_geofence).delete(delete_geofence))
+        .route("/geofence/{id}/restore", put(restore_geofence))
+}
+
+async fn create_geofence(
+    State(service): State<Arc<GeofenceService>>,
+    ctx: AuditContext,
+    Json(dto): Json<GeofenceRequestDto>,
+) -> Result<Json<String>, AppError> {
+    dto.validate().map_err(AppError::BadRequest)?;
+    let geofence = Geofence::from_request(dto);
+    let new_id = geofence.id;
+    service.create(geofence, &ctx).await?;
+    Ok(Json(new_id.to_hex()))
+}
+
+async fn list_geofences(
+    State(service): State<Arc<GeofenceService>>,
+    tenant: Tenant,
+) -> Result<Json<Vec<GeofenceResponseDto>>, AppError> {
+    let geofences = service.list(&tenant).await?;
+    Ok(Json(geofences.into_iter().map(GeofenceResponseDto::from).collect()))
+}
+
+async fn get_geofence(
+    State(service): State<Arc<GeofenceService>>,
+    tenant: Tenant,
+    Path(id): Path<String>,
+) -> Result<Json<GeofenceResponseDto>, AppError> {
+    let geofence = service.get(&tenant, &id).await?
+        .ok_or_else(|| AppError::NotFound("Geofence not found".to_string()))?;
+    Ok(Json(GeofenceResponseDto::from(geofence)))
+}
+
+async fn update_geofence(
+    State(service): State<Arc<GeofenceService>>,
+    Path(id): Path<String>,
+    ctx: AuditContext,
+    Json(dto): Json<GeofenceRequestDto>,
+) -> Result<Json<GeofenceResponseDto>, AppError> {
+    dto.validate().map_err(AppError::BadRequest)?;
+    let geofence = service.update(&id, dto, &ctx).await?
+        .ok_or_else(|| AppError::NotFound("Geofence not found".to_string()))?;
+    Ok(Json(GeofenceResponseDto::from(geofence)))
+}
+
+async fn delete_geofence(
+    State(service): State<Arc<GeofenceService>>,
+    Path(id): Path<String>,
+    ctx: AuditContext,
+) -> Result<Json<&'static str>, AppError> {
+    service.delete(&id, &ctx).await?;
+    Ok(Json("ok"))
+}
+
+async fn get_geofence_trash(
+    State(service): State<Arc<GeofenceService>>,
+    tenant: Tenant,
+) -> Result<Json<Vec<TrashEntryDto<GeofenceResponseDto>>>, AppError> {
+    let geofences = service.get_trash(&tenant).await?;
+    Ok(Json(geofences.into_iter().map(|geofence| {
+        let (deleted_at, deleted_by) = (geofence.deleted_at, geofence.deleted_by.clone());
+        TrashEntryDto::new(GeofenceResponseDto::from(geofence), deleted_at, deleted_by)
+    }).collect()))
+}
+
+async fn restore_geofence(
+    State(service): State<Arc<GeofenceService>>,
+    Path(id): Path<String>,
+    ctx: AuditContext,
+) -> Result<Json<Option<GeofenceResponseDto>>, AppError> {
+    let res = service.restore(&id, &ctx).await?;
+    Ok(Json(res.map(GeofenceResponseDto::from)))
+}
+
+// 违规告警记录，按进入时间倒序；?open=true 只看进行中的告警
+async fn list_alerts(
+    State(service): State<Arc<GeofenceService>>,
+    tenant: Tenant,
+    Query(query): Query<AlertQuery>,
+) -> Result<Json<Vec<GeofenceAlertDto>>, AppError> {
+    let alerts = service.alerts(&tenant, query).await?;
+    Ok(Json(alerts.into_iter().map(GeofenceAlertDto::from).collect()))
+}
@@ -0,0 +1,19 @@
+use std::sync::Arc;
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use crate::middleware::rate_limit::RateLimiter;
+
+pub fn metrics_routes() -> Router<Arc<RateLimiter>> {
+    Router::new()
+        .route("/metrics", get(get_metrics))
+}
+
+async fn get_metrics(State(limiter): State<Arc<RateLimiter>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        limiter.render_metrics(),
+    )
+}
@@ -0,0 +1,141 @@
+use std::sync::Arc;
+use axum::extract::{Path, Query, State};
+use axum::routing::{get, put};
+use axum::{Json, Router};
+use crate::error::AppError;
+use crate::model::audit_log::AuditContext;
+use crate::model::flight::FlightResponseDto;
+use crate::model::mission::{AnalysisQuery, Mission, MissionAnalysisDto, MissionRequestDto, MissionResponseDto};
+use crate::model::tenant::Tenant;
+use crate::model::trash::TrashEntryDto;
+use crate::service::mission_service::MissionService;
+
+pub fn mission_routes() -> Router<Arc<MissionService>> {
+    Router::new()
+        .route("/mission", get(list_missions).post(create_mission))
+        .route("/mission/trash", get(get_mission_trash))
+        .route("/mission/{id}", get(get_mission).put(update_mission).delete(delete_mission))
+        .route("/mission/{id}/restore", put(restore_mission))
+        .route("/mission/{id}/tracks/{track_id}", put(link_track).delete(unlink_track))
+        .route("/mission/{id}/flights/{flight_id}", put(link_flight).delete(unlink_flight))
+        .route("/mission/{id}/analysis", get(analyze_mission))
+}
+
+async fn create_mission(
+    State(service): State<Arc<MissionService>>,
+    ctx: AuditContext,
+    Json(dto): Json<MissionRequestDto>,
+) -> Result<Json<String>, AppError> {
+    dto.validate().map_err(AppError::BadRequest)?;
+    let mission = Mission::from_request(dto).map_err(AppError::BadRequest)?;
+    let new_id = mission.id;
+    service.create(mission, &ctx).await?;
+    Ok(Json(new_id.to_hex()))
+}
+
+async fn list_missions(
+    State(service): State<Arc<MissionService>>,
+    tenant: Tenant,
+) -> Result<Json<Vec<MissionResponseDto>>, AppError> {
+    let missions = service.list(&tenant).await?;
+    Ok(Json(missions.into_iter().map(MissionResponseDto::from).collect()))
+}
+
+async fn get_mission(
+    State(service): State<Arc<MissionService>>,
+    tenant: Tenant,
+    Path(id): Path<String>,
+) -> Result<Json<MissionResponseDto>, AppError> {
+    let mission = service.get(&tenant, &id).await?
+        .ok_or_else(|| AppError::NotFound("Mission not found".to_string()))?;
+    Ok(Json(MissionResponseDto::from(mission)))
+}
+
+async fn update_mission(
+    State(service): State<Arc<MissionService>>,
+    Path(id): Path<String>,
+    ctx: AuditContext,
+    Json(dto): Json<MissionRequestDto>,
+) -> Result<Json<MissionResponseDto>, AppError> {
+    dto.validate().map_err(AppError::BadRequest)?;
+    let mission = service.update(&id, dto, &ctx).await?
+        .ok_or_else(|| AppError::NotFound("Mission not found".to_string()))?;
+    Ok(Json(MissionResponseDto::from(mission)))
+}
+
+async fn delete_mission(
+    State(service): State<Arc<MissionService>>,
+    Path(id): Path<String>,
+    ctx: AuditContext,
+) -> Result<Json<&'static str>, AppError> {
+    service.delete(&id, &ctx).await?;
+    Ok(Json("ok"))
+}
+
+async fn get_mission_trash(
+    State(service): State<Arc<MissionService>>,
+    tenant: Tenant,
+) -> Result<Json<Vec<TrashEntryDto<MissionResponseDto>>>, AppError> {
+    let missions = service.get_trash(&tenant).await?;
+    Ok(Json(missions.into_iter().map(|mission| {
+        let (deleted_at, deleted_by) = (mission.deleted_at, mission.deleted_by.clone());
+        TrashEntryDto::new(MissionResponseDto::from(mission), deleted_at, deleted_by)
+    }).collect()))
+}
+
+async fn restore_mission(
+    State(service): State<Arc<MissionService>>,
+    Path(id): Path<String>,
+    ctx: AuditContext,
+) -> Result<Json<Option<MissionResponseDto>>, AppError> {
+    let res = service.restore(&id, &ctx).await?;
+    Ok(Json(res.map(MissionResponseDto::from)))
+}
+
+async fn link_track(
+    State(service): State<Arc<MissionService>>,
+    Path((id, track_id)): Path<(String, String)>,
+    ctx: AuditContext,
+) -> Result<Json<&'static str>, AppError> {
+    service.link_track(&id, &track_id, &ctx).await?;
+    Ok(Json("ok"))
+}
+
+async fn unlink_track(
+    State(service): State<Arc<MissionService>>,
+    Path((id, track_id)): Path<(String, String)>,
+    ctx: AuditContext,
+) -> Result<Json<&'static str>, AppError> {
+    service.unlink_track(&id, &track_id, &ctx).await?;
+    Ok(Json("ok"))
+}
+
+// 飞行记录的航迹会一并关联到任务
+async fn link_flight(
+    State(service): State<Arc<MissionService>>,
+    Path((id, flight_id)): Path<(String, String)>,
+    ctx: AuditContext,
+) -> Result<Json<FlightResponseDto>, AppError> {
+    let flight = service.link_flight(&id, &flight_id, &ctx).await?;
+    Ok(Json(FlightResponseDto::from(flight)))
+}
+
+async fn unlink_flight(
+    State(service): State<Arc<MissionService>>,
+    Path((id, flight_id)): Path<(String, String)>,
+    ctx: AuditContext,
+) -> Result<Json<&'static str>, AppError> {
+    service.unlink_flight(&id, &flight_id, &ctx).await?;
+    Ok(Json("ok"))
+}
+
+// 实际航迹与计划的对比：偏航距离、跳过的航点和各航点的耗时。
+// ?trackId= 分析指定航迹，否则分析最近关联到该任务的航迹
+async fn analyze_mission(
+    State(service): State<Arc<MissionService>>,
+    tenant: Tenant,
+    Path(id): Path<String>,
+    Query(query): Query<AnalysisQuery>,
+) -> Result<Json<Vec<MissionAnalysisDto>>, AppError> {
+    Ok(Json(service.analyze(&tenant, &id, query).await?))
+}
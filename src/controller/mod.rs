@@ -1,3 +1,9 @@
 pub(crate) mod track;
 pub mod report;
 pub mod flight;
+pub mod audit;
+pub mod tenant;
+pub mod share;
+pub mod metrics;
+pub mod geofence;
+pub mod mission;
@@ -1,8 +1,11 @@
 use crate::error::AppError;
-use crate::model::report_raw::{ReportRawRequestDto, ReportRawResponseDto};
+use crate::model::audit_log::AuditContext;
+use crate::model::tenant::Tenant;
+use crate::model::report_raw::{ReportBulkDeleteFilter, ReportRawRequestDto, ReportRawResponseDto};
+use crate::model::trash::{BulkDeleteRequestDto, TrashEntryDto};
 use crate::service::report_raw_service::ReportRawService;
-use axum::extract::{State, Multipart, DefaultBodyLimit};
-use axum::routing::{delete, get, post};
+use axum::extract::{State, Multipart, DefaultBodyLimit, Path};
+use axum::routing::{delete, get, post, put};
 use axum::{Json, Router};
 use std::sync::Arc;
 
@@ -10,24 +13,36 @@ use std::sync::Arc;
 pub fn report_routes() -> Router<Arc<ReportRawService>> {
     Router::new()
         .route("/report_raw", post(create_report_raw).get(get_report_raw_all).delete(delete_report_by_id))
+        .route("/report_raw/{id}", get(get_report_raw_by_id))
+        .route("/report_raw/trash", get(get_report_raw_trash))
+        .route("/report_raw/{id}/restore", put(restore_report_raw))
         .route("/report_latest", get(get_latest_report_raw))
         .route("/report_with_image", post(create_report_with_image))
         .route("/report_raw/delete_all", delete(delete_all_report_raw))
         .layer(DefaultBodyLimit::max(1024*1024*10*5)) // 50MB limit
 }
-async fn get_report_raw_all(State(service): State<Arc<ReportRawService>>) -> Result<Json<Vec<ReportRawResponseDto>>, AppError> {
-    let reports = service.get_all().await?;
+async fn get_report_raw_all(State(service): State<Arc<ReportRawService>>, tenant: Tenant) -> Result<Json<Vec<ReportRawResponseDto>>, AppError> {
+    let reports = service.get_all(&tenant).await?;
     let response: Vec<ReportRawResponseDto> = reports.into_iter().map(ReportRawResponseDto::from).collect();
     Ok(Json(response))
 }
+async fn get_report_raw_by_id(
+    State(service): State<Arc<ReportRawService>>,
+    tenant: Tenant,
+    Path(id): Path<String>,
+) -> Result<Json<Option<ReportRawResponseDto>>, AppError> {
+    let res = service.get_by_id(&tenant, &id).await?;
+    Ok(Json(res.map(ReportRawResponseDto::from)))
+}
 async fn delete_report_by_id(
     State(service): State<Arc<ReportRawService>>,
+    ctx: AuditContext,
     Json(id): Json<String>
 ) -> Result<(), AppError> {
-    service.delete_by_id(&id).await
+    service.delete_by_id(&id, &ctx).await
 }
-async fn get_latest_report_raw(State(service): State<Arc<ReportRawService>>) -> Result<Json<Option<ReportRawResponseDto>>, AppError> {
-    let res = service.get_latest().await?;
+async fn get_latest_report_raw(State(service): State<Arc<ReportRawService>>, tenant: Tenant) -> Result<Json<Option<ReportRawResponseDto>>, AppError> {
+    let res = service.get_latest(&tenant).await?;
     match res {
         Some(report) => Ok(Json(Some(ReportRawResponseDto::from(report)))),
         None => Ok(Json(None)),
@@ -36,14 +51,16 @@ async fn get_latest_report_raw(State(service): State<Arc<ReportRawService>>) ->
 
 async fn create_report_raw(
     State(service): State<Arc<ReportRawService>>,
+    ctx: AuditContext,
     Json(report): Json<ReportRawRequestDto>
 ) -> Result<Json<&'static str>, AppError> {
-    service.insert_one(report).await?;
+    service.insert_one(report, &ctx).await?;
     Ok(Json("ok"))
 }
 
 async fn create_report_with_image(
     State(service): State<Arc<ReportRawService>>,
+    ctx: AuditContext,
     mut multipart: Multipart,
 ) -> Result<Json<serde_json::Value>, AppError> {
     let mut image_files = Vec::new();
@@ -84,10 +101,12 @@ async fn create_report_with_image(
     })?;
 
     // 委托给 service 层处理业务逻辑
-    let (result, report_id) = service.create_report_with_images(report.clone(), image_files).await?;
+    let (result, report_id) = service.create_report_with_images(report.clone(), image_files, &ctx).await?;
+    let tenant = ctx.tenant.clone();
     tokio::spawn(async move {
         service.clone().generate_ai_report_background(
-            report_id.clone(),
+            tenant,
+            report_id,
             report.rust,
             report.covering,
             report.damage,
@@ -96,10 +115,31 @@ async fn create_report_with_image(
     Ok(Json(result))
 }
 
-async fn delete_all_report_raw(State(service): State<Arc<ReportRawService>>) -> Result<Json<serde_json::Value>, AppError> {
-    let deleted_count = service.delete_all().await?;
-    Ok(Json(serde_json::json!({
-        "status": "success",
-        "deleted_count": deleted_count
-    })))
+// 批量删除：必须提供筛选条件，并用第一次请求返回的确认令牌再次提交
+async fn delete_all_report_raw(
+    State(service): State<Arc<ReportRawService>>,
+    ctx: AuditContext,
+    Json(request): Json<BulkDeleteRequestDto<ReportBulkDeleteFilter>>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    Ok(Json(service.bulk_delete(request, &ctx).await?))
+}
+
+async fn get_report_raw_trash(
+    State(service): State<Arc<ReportRawService>>,
+    tenant: Tenant,
+) -> Result<Json<Vec<TrashEntryDto<ReportRawResponseDto>>>, AppError> {
+    let reports = service.get_trash(&tenant).await?;
+    Ok(Json(reports.into_iter().map(|report| {
+        let (deleted_at, deleted_by) = (report.deleted_at, report.deleted_by.clone());
+        TrashEntryDto::new(ReportRawResponseDto::from(report), deleted_at, deleted_by)
+    }).collect()))
+}
+
+async fn restore_report_raw(
+    State(service): State<Arc<ReportRawService>>,
+    Path(id): Path<String>,
+    ctx: AuditContext,
+) -> Result<Json<Option<ReportRawResponseDto>>, AppError> {
+    let res = service.restore(&id, &ctx).await?;
+    Ok(Json(res.map(ReportRawResponseDto::from)))
 }
@@ -0,0 +1,120 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+use axum::body::Body;
+use axum::extract::{FromRequestParts, Path, State};
+use axum::http::header;
+use axum::http::request::Parts;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use tokio_util::io::ReaderStream;
+use crate::controller::audit::client_ip;
+use crate::error::AppError;
+use crate::model::audit_log::AuditContext;
+use crate::model::report_raw::ReportRawResponseDto;
+use crate::model::share_link::{ShareAccessLogResponseDto, ShareLinkCreatedDto, ShareLinkRequestDto, ShareLinkResponseDto};
+use crate::model::tenant::Tenant;
+use crate::service::share_service::{ShareService, ShareViewer};
+
+pub fn share_routes() -> Router<Arc<ShareService>> {
+    Router::new()
+        // 内部管理接口，按租户隔离
+        .route("/share", post(create_share_link).get(get_share_links))
+        .route("/share/{id}", delete(revoke_share_link))
+        .route("/share/{id}/access_log", get(get_share_access_log))
+        // 对外只读接口，凭令牌访问
+        .route("/shared/{token}", get(get_shared_reports))
+        .route("/shared/{token}/report/{report_id}", get(get_shared_report))
+        .route("/shared/{token}/report/{report_id}/image/{index}", get(get_shared_image))
+}
+
+async fn create_share_link(
+    State(service): State<Arc<ShareService>>,
+    ctx: AuditContext,
+    Json(dto): Json<ShareLinkRequestDto>,
+) -> Result<Json<ShareLinkCreatedDto>, AppError> {
+    Ok(Json(service.create(dto, &ctx).await?))
+}
+
+async fn get_share_links(
+    State(service): State<Arc<ShareService>>,
+    tenant: Tenant,
+) -> Result<Json<Vec<ShareLinkResponseDto>>, AppError> {
+    let links = service.list(&tenant).await?;
+    Ok(Json(links.into_iter().map(ShareLinkResponseDto::from).collect()))
+}
+
+async fn revoke_share_link(
+    State(service): State<Arc<ShareService>>,
+    Path(id): Path<String>,
+    ctx: AuditContext,
+) -> Result<Json<&'static str>, AppError> {
+    service.revoke(&id, &ctx).await?;
+    Ok(Json("ok"))
+}
+
+async fn get_share_access_log(
+    State(service): State<Arc<ShareService>>,
+    tenant: Tenant,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<ShareAccessLogResponseDto>>, AppError> {
+    let logs = service.get_access_log(&tenant, &id).await?;
+    Ok(Json(logs.into_iter().map(ShareAccessLogResponseDto::from).collect()))
+}
+
+async fn get_shared_reports(
+    State(service): State<Arc<ShareService>>,
+    viewer: ShareViewer,
+    Path(token): Path<String>,
+) -> Result<Json<Vec<ReportRawResponseDto>>, AppError> {
+    let link = service.resolve(&token).await?;
+    service.log_access(&link, "reports", None, &viewer).await;
+    let reports = service.shared_reports(&link).await?;
+    Ok(Json(reports.into_iter().map(ReportRawResponseDto::from).collect()))
+}
+
+async fn get_shared_report(
+    State(service): State<Arc<ShareService>>,
+    viewer: ShareViewer,
+    Path((token, report_id)): Path<(String, String)>,
+) -> Result<Json<ReportRawResponseDto>, AppError> {
+    let link = service.resolve(&token).await?;
+    let report = service.shared_report(&link, &report_id).await?;
+    service.log_access(&link, "report", Some(report.id), &viewer).await;
+    Ok(Json(ReportRawResponseDto::from(report)))
+}
+
+async fn get_shared_image(
+    State(service): State<Arc<ShareService>>,
+    viewer: ShareViewer,
+    Path((token, report_id, index)): Path<(String, String, usize)>,
+) -> Result<Response, AppError> {
+    let link = service.resolve(&token).await?;
+    let file_path = service.shared_image_path(&link, &report_id, index).await?;
+    let file = tokio::fs::File::open(&file_path).await.map_err(|e| {
+        tracing::error!("打开分享图片失败 {}: {:?}", file_path, e);
+        AppError::NotFound("Image not found".to_string())
+    })?;
+    service.log_access(&link, "image", bson::oid::ObjectId::parse_str(&report_id).ok(), &viewer).await;
+    let content_type = match file_path.rsplit('.').next().map(|ext| ext.to_ascii_lowercase()).as_deref() {
+        Some("png") => "image/png",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        _ => "image/jpeg",
+    };
+    Ok(([(header::CONTENT_TYPE, content_type)], Body::from_stream(ReaderStream::new(file))).into_response())
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ShareViewer {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(ShareViewer {
+            client_ip: client_ip(parts),
+            user_agent: parts.headers
+                .get(header::USER_AGENT)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string),
+        })
+    }
+}
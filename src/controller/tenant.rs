@@ -0,0 +1,52 @@
+use std::sync::Arc;
+use axum::extract::{FromRequestParts, State};
+use axum::http::request::Parts;
+use axum::routing::get;
+use axum::{Json, Router};
+use crate::error::AppError;
+use crate::model::tenant::{EffectiveTenantConfig, Tenant, TenantConfigRequestDto};
+use crate::service::tenant_service::TenantService;
+
+pub fn tenant_routes() -> Router<Arc<TenantService>> {
+    Router::new()
+        .route("/tenant/config", get(get_tenant_config).put(update_tenant_config))
+}
+
+async fn get_tenant_config(
+    State(service): State<Arc<TenantService>>,
+    tenant: Tenant,
+) -> Result<Json<EffectiveTenantConfig>, AppError> {
+    Ok(Json(service.effective_config(&tenant).await?))
+}
+
+async fn update_tenant_config(
+    State(service): State<Arc<TenantService>>,
+    tenant: Tenant,
+    Json(dto): Json<TenantConfigRequestDto>,
+) -> Result<Json<&'static str>, AppError> {
+    if let Some(filter) = &dto.track_filter {
+        filter.validate().map_err(AppError::BadRequest)?;
+    }
+    service.upsert_config(&tenant, dto).await?;
+    Ok(Json("ok"))
+}
+
+// 租户由网关注入的 X-Org-Id / X-Wind-Farm-Id 请求头确定，缺失时拒绝请求，避免读到其他租户的数据
+impl<S: Send + Sync> FromRequestParts<S> for Tenant {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let header = |name: &str| {
+            parts.headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        let org_id = header("x-org-id")
+            .ok_or_else(|| AppError::BadRequest("Missing X-Org-Id header".to_string()))?;
+        let wind_farm_id = header("x-wind-farm-id")
+            .ok_or_else(|| AppError::BadRequest("Missing X-Wind-Farm-Id header".to_string()))?;
+        Ok(Tenant { org_id, wind_farm_id })
+    }
+}
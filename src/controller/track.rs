@@ -1,68 +1,511 @@
-use chrono::Utc;
-use axum::{extract::{State, Path}, Json, Router, routing::{get, post, put, delete}};
-use crate::model::ship_track::{ShipTrack, UpdateShipTrackPayload};
-use crate::service::ship_track_service::ShipTrackService;
+use axum::{extract::{State, Path, Query}, Json, Router, routing::{get, post, put, delete}};
+use axum::http::{header, HeaderMap};
+use axum::response::{IntoResponse, Response};
+use serde::Serialize;
+use crate::model::ship_track::{ShipTrack, ShipTrackUpdateDto, UpdateShipTrackPayload};
+use crate::service::ship_track_service::{bbox_polygon, AppendRequest, ShipTrackService};
 use std::sync::Arc;
 use crate::model::ship_track::ShipTrackRequestDto;
 use crate::model::ship_track::ShipTrackResponseDto;
+use crate::model::ship_track::{ShipTrackSummaryDto, TrackBulkDeleteFilter, TrackFeatureProperties, TrackListQuery, TrackPageDto};
+use crate::model::geojson::{Feature, FeatureCollection};
 use bson::oid::ObjectId;
+use crate::model::audit_log::AuditContext;
+use crate::model::tenant::Tenant;
+use crate::model::trash::{BulkDeleteRequestDto, TrashEntryDto};
+use crate::error::AppError;
+use crate::export::{gpx, kml};
+use crate::export::map::{self, MapFormat};
+use axum::body::Body;
+use axum::extract::{DefaultBodyLimit, Multipart};
+use crate::model::ship_track::{BboxQuery, MonthlyDistanceDto, MonthlyDistanceQuery, NearQuery, TrackImportResultDto, TrackResponseQuery, TrackStatsDto};
+use crate::model::track_point::TrackPoint;
+use crate::model::track_segment::{IndexedPointDto, PointRange};
+use crate::model::live::LiveQuery;
+use crate::model::heatmap::HeatmapQuery;
+use crate::model::track_edit::{MergeRequestDto, SortieDto, SortieQuery, SplitRequestDto};
+use crate::model::replay::{ReplayDto, ReplayQuery};
+use crate::model::ship_track::OutputQuery;
+use crate::model::track_filter::Smoothing;
+use crate::geo::crs::Crs;
+use crate::geo::filter::kalman_smooth;
+use crate::service::live_service::LiveSubscription;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use std::convert::Infallible;
+use std::time::Duration;
+
+// 单条消息写不出去超过这个时间就认为客户端已失联，断开连接
+const LIVE_SEND_TIMEOUT: Duration = Duration::from_secs(10);
+// 历史航迹文件可能较大，导入接口单独放宽请求体大小限制
+const IMPORT_BODY_LIMIT: usize = 64 * 1024 * 1024;
 pub fn track_routes() -> Router<Arc<ShipTrackService>> {
     Router::new()
-        .route("/track", post(create_track))
+        .route("/track", post(create_track).get(list_tracks))
+        .route("/track/import", post(import_tracks).layer(DefaultBodyLimit::max(IMPORT_BODY_LIMIT)))
         .route("/track/{id}", get(get_track))
         .route("/track/{id}", put(update_track))
         .route("/track/{id}", delete(delete_track))
+        .route("/track/trash", get(get_track_trash))
+        .route("/track/delete_all", delete(bulk_delete_tracks))
+        .route("/track/{id}/restore", put(restore_track))
+        .route("/track/{id}/export", get(export_track))
+        .route("/track/{id}/stats", get(get_track_stats))
+        .route("/track/{id}/points", get(get_track_points))
+        .route("/track/{id}/replay", get(replay_track))
+        .route("/track/{id}/map", get(render_track_map))
+        .route("/track/{id}/split", post(split_track))
+        .route("/track/{id}/merge", post(merge_track))
+        .route("/track/{id}/trim", post(trim_track))
+        .route("/track/{id}/sorties", get(get_track_sorties))
+        .route("/track/stats/monthly", get(get_monthly_distance))
+        .route("/track/heatmap", get(get_heatmap))
+        .route("/track/near", get(get_tracks_near))
+        .route("/track/within", get(get_tracks_within_bbox).post(get_tracks_within_polygon))
+        .route("/track/intersects", post(get_tracks_intersecting))
+        .route("/track/live/ws", get(live_ws))
+        .route("/track/live/sse", get(live_sse))
         .route("/track_latest", get(get_latest_track))
         .route("/append_track/{id}", put(append_track))
 }
 
-async fn create_track(State(service): State<Arc<ShipTrackService>>, Json(track_dto): Json<ShipTrackRequestDto>) -> Json<String> {
-    let new_id = ObjectId::new(); // 服务器生成 _id
-    let current_time = Utc::now(); // 服务器生成时间戳
-
+async fn create_track(State(service): State<Arc<ShipTrackService>>, ctx: AuditContext, Json(track_dto): Json<ShipTrackRequestDto>) -> Result<Json<String>, AppError> {
     // 从 payload 和服务器生成的值构建 ShipTrack 实例
-    let track = ShipTrack {
-        id: new_id,
-        start_time: current_time.into(),
-        last_update: current_time.into(),
-        coordinates: track_dto.coordinates,
-        total_points: track_dto.total_points,
+    let crs = track_dto.crs;
+    let mut track = ShipTrack::from_request(track_dto);
+    track.points_to_wgs84(crs).map_err(AppError::BadRequest)?;
+    let new_id = track.id;
+    service.create(track, &ctx).await?;
+    Ok(Json(new_id.to_hex()))
+}
+
+// multipart 表单：一个或多个 file 字段，可选 droneId、owner 字段作用于本次导入的全部航迹
+async fn import_tracks(State(service): State<Arc<ShipTrackService>>, ctx: AuditContext, mut multipart: Multipart) -> Result<Json<Vec<TrackImportResultDto>>, AppError> {
+    let mut files = Vec::new();
+    let (mut drone_id, mut owner) = (None, None);
+    while let Some(field) = multipart.next_field().await.map_err(|e| AppError::BadRequest(format!("Invalid multipart body: {}", e)))? {
+        let name = field.name().unwrap_or("").to_string();
+        let file_name = field.file_name().map(str::to_string);
+        let bytes = field.bytes().await.map_err(|e| AppError::BadRequest(format!("Failed to read field {}: {}", name, e)))?;
+        match name.as_str() {
+            "droneId" => drone_id = Some(String::from_utf8_lossy(&bytes).to_string()),
+            "owner" => owner = Some(String::from_utf8_lossy(&bytes).to_string()),
+            _ => files.push((file_name.unwrap_or(name), bytes)),
+        }
+    }
+    if files.is_empty() {
+        return Err(AppError::BadRequest("No files uploaded".to_string()));
+    }
+    let mut results = Vec::with_capacity(files.len());
+    for (file_name, bytes) in files {
+        let result = match std::str::from_utf8(&bytes) {
+            Ok(content) => service.import_file(&file_name, content, drone_id.clone(), owner.clone(), &ctx).await,
+            Err(_) => TrackImportResultDto { file: file_name, tracks: Vec::new(), error: Some("File is not valid UTF-8 text".to_string()) },
+        };
+        results.push(result);
+    }
+    Ok(Json(results))
+}
+
+const GEOJSON_CONTENT_TYPE: &str = "application/geo+json";
+
+// 以 application/geo+json 返回的 GeoJSON 响应
+struct GeoJson<T: Serialize>(T);
+
+impl<T: Serialize> IntoResponse for GeoJson<T> {
+    fn into_response(self) -> Response {
+        let mut response = Json(self.0).into_response();
+        response.headers_mut().insert(header::CONTENT_TYPE, header::HeaderValue::from_static(GEOJSON_CONTENT_TYPE));
+        response
+    }
+}
+
+fn wants_geojson(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|accept| accept.contains(GEOJSON_CONTENT_TYPE))
+}
+
+async fn list_tracks(State(service): State<Arc<ShipTrackService>>, tenant: Tenant, headers: HeaderMap, Query(query): Query<TrackListQuery>) -> Result<Response, AppError> {
+    let geojson = query.format.as_deref() == Some("geojson") || wants_geojson(&headers);
+    let (crs, raw) = (query.crs, query.raw);
+    let page = service.list(&tenant, query).await?;
+    if geojson {
+        // 摘要不含坐标，需要再取完整航迹来生成几何
+        let ids: Vec<ObjectId> = page.items.iter().map(|s| s.id).collect();
+        let mut tracks = service.get_many(&tenant, &ids).await?;
+        if !raw {
+            service.denoise(&tenant, &mut tracks).await?;
+        }
+        let features: Vec<Feature<TrackFeatureProperties>> = tracks.into_iter().map(|mut track| {
+            let crs = track.project_to(crs);
+            let mut feature = Feature::from(track);
+            feature.properties.crs = crs;
+            feature
+        }).collect();
+        return Ok(GeoJson(FeatureCollection::new(features, page.next_cursor)).into_response());
+    }
+    Ok(Json(TrackPageDto {
+        items: page.items.into_iter().map(ShipTrackSummaryDto::from).collect(),
+        next_cursor: page.next_cursor,
+    }).into_response())
+}
+
+// 支持 /track/{id}.geojson 或 Accept: application/geo+json 返回 GeoJSON Feature
+// ?compact=true 时坐标只返回 [经度, 纬度]，?crs= 指定输出坐标系
+async fn get_track(State(service): State<Arc<ShipTrackService>>, tenant: Tenant, headers: HeaderMap, Path(id): Path<String>, Query(query): Query<TrackResponseQuery>) -> Result<Response, AppError> {
+    let (id, geojson) = match id.strip_suffix(".geojson") {
+        Some(id) => (id.to_string(), true),
+        None => (id, wants_geojson(&headers)),
+    };
+    let mut res = service.get(&tenant, &id).await?;
+    // 默认去掉被过滤的点并按租户配置平滑，?raw=true 返回原始坐标
+    if !query.raw {
+        service.denoise(&tenant, res.as_mut_slice()).await?;
+    }
+    // ?simplify= 只抽稀本次返回的几何，存储的航迹保持无损
+    let simplification = match (&mut res, query.simplify.as_deref()) {
+        (Some(track), Some(param)) => Some(track.simplify(param).map_err(AppError::BadRequest)?),
+        _ => None,
+    };
+    if geojson {
+        let mut track = res.ok_or_else(|| AppError::NotFound("Track not found".to_string()))?;
+        let crs = track.project_to(query.crs);
+        let mut feature = Feature::<TrackFeatureProperties>::from(track);
+        feature.properties.simplification = simplification;
+        feature.properties.crs = crs;
+        return Ok(GeoJson(feature).into_response());
+    }
+    Ok(Json(res.map(|track| {
+        let mut dto = ShipTrackResponseDto::new(track, &query);
+        dto.simplification = simplification;
+        dto
+    })).into_response())
+}
+
+async fn update_track(State(service): State<Arc<ShipTrackService>>, Path(id): Path<String>, ctx: AuditContext, Json(track_dto): Json<ShipTrackUpdateDto>) -> Result<Json<&'static str>, AppError> {
+    // 与创建、追加一样先转换为 WGS-84 并校验经纬度范围
+    let crs = track_dto.crs;
+    let mut track = ShipTrack::from_update(track_dto).map_err(AppError::BadRequest)?;
+    track.points_to_wgs84(crs).map_err(AppError::BadRequest)?;
+    service.update(&id, track, &ctx).await?;
+    Ok(Json("ok"))
+}
+async fn delete_track (State(service): State<Arc<ShipTrackService>>, Path(id): Path<String>, ctx: AuditContext) -> Result<Json<&'static str>, AppError> {
+    service.delete(&id, &ctx).await?;
+    Ok(Json("ok"))
+}
+#[derive(serde::Deserialize)]
+struct ExportQuery {
+    // gpx 或 kml
+    format: String,
+    // GPX / KML 的坐标必须是经纬度，因此不支持 utm
+    #[serde(default)]
+    crs: Crs,
+    #[serde(default)]
+    raw: bool,
+}
+
+// 以流的方式导出 GPX / KML 文件
+async fn export_track(State(service): State<Arc<ShipTrackService>>, tenant: Tenant, Path(id): Path<String>, Query(query): Query<ExportQuery>) -> Result<Response, AppError> {
+    if query.crs == Crs::Utm {
+        return Err(AppError::BadRequest("UTM output is not supported for GPX/KML exports".to_string()));
+    }
+    let mut track = service.get(&tenant, &id).await?
+        .ok_or_else(|| AppError::NotFound("Track not found".to_string()))?;
+    if !query.raw {
+        service.denoise(&tenant, std::slice::from_mut(&mut track)).await?;
+    }
+    track.project_to(query.crs);
+    let file_name = format!("track-{}.{}", track.id.to_hex(), query.format);
+    let (content_type, body) = match query.format.as_str() {
+        "gpx" => (gpx::CONTENT_TYPE, Body::from_stream(gpx::stream(track))),
+        "kml" => (kml::CONTENT_TYPE, Body::from_stream(kml::stream(track))),
+        other => return Err(AppError::BadRequest(format!("Unsupported export format: {}", other))),
     };
-    service.create(track).await.unwrap();
-    Json(new_id.to_hex())
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file_name)),
+        ],
+        body,
+    ).into_response())
 }
 
-async fn get_track(State(service): State<Arc<ShipTrackService>>, Path(id): Path<String>) -> Json<Option<ShipTrackResponseDto>> {
-    let res = service.get(&id).await.unwrap();
-    Json(res.map(ShipTrackResponseDto::from))
+#[derive(serde::Deserialize)]
+struct MapQuery {
+    // png（默认）或 svg
+    format: Option<String>,
+    // 图片尺寸（像素），默认 800×600
+    width: Option<u32>,
+    height: Option<u32>,
+    // 叠加启用的地理围栏 / 风机位置
+    #[serde(default)]
+    geofences: bool,
+    #[serde(default)]
+    turbines: bool,
+    #[serde(default)]
+    raw: bool,
 }
 
-async fn update_track(State(service): State<Arc<ShipTrackService>>, Path(id): Path<String>, Json(track): Json<ShipTrack>) -> Json<&'static str> {
-    service.update(&id, track).await.unwrap();
-    Json("ok")
+// 渲染航迹静态地图，供 PDF 报告和邮件通知使用
+async fn render_track_map(State(service): State<Arc<ShipTrackService>>, tenant: Tenant, Path(id): Path<String>, Query(query): Query<MapQuery>) -> Result<Response, AppError> {
+    let format = MapFormat::parse(query.format.as_deref().unwrap_or("png")).map_err(AppError::BadRequest)?;
+    let (width, height) = (query.width.unwrap_or(800), query.height.unwrap_or(600));
+    if [width, height].iter().any(|size| !(map::MIN_SIZE..=map::MAX_SIZE).contains(size)) {
+        return Err(AppError::BadRequest(format!("width and height must be between {} and {}", map::MIN_SIZE, map::MAX_SIZE)));
+    }
+    let scene = service.map_scene(&tenant, &id, query.raw, query.geofences, query.turbines).await?;
+    if scene.track.is_empty() {
+        return Err(AppError::BadRequest("Track has no points to render".to_string()));
+    }
+    // 绘制和 PNG 编码是 CPU 密集操作，放到阻塞线程池执行
+    let image = tokio::task::spawn_blocking(move || map::render(&scene, width, height, format))
+        .await
+        .map_err(|e| AppError::InternalServerError(format!("地图渲染任务失败: {}", e)))?
+        .map_err(|e| AppError::InternalServerError(format!("地图渲染失败: {}", e)))?;
+    Ok(([(header::CONTENT_TYPE, format.content_type())], image).into_response())
 }
-async fn delete_track (State(service): State<Arc<ShipTrackService>>, Path(id): Path<String>) -> Json<&'static str> {
-    service.delete(&id).await.unwrap();
-    Json("ok")
+
+// 时间范围内所有航迹的覆盖热力图：?format=geojson（默认）返回 geohash 单元格多边形，
+// png / svg 返回按 ?metric=count|dwell 着色的栅格图
+async fn get_heatmap(State(service): State<Arc<ShipTrackService>>, tenant: Tenant, Query(query): Query<HeatmapQuery>) -> Result<Response, AppError> {
+    let metric = query.metric().map_err(AppError::BadRequest)?;
+    let format = match query.format.as_deref().unwrap_or("geojson") {
+        "geojson" => None,
+        other => Some(MapFormat::parse(other).map_err(AppError::BadRequest)?),
+    };
+    let (width, height) = (query.width.unwrap_or(800), query.height.unwrap_or(600));
+    if format.is_some() && [width, height].iter().any(|size| !(map::MIN_SIZE..=map::MAX_SIZE).contains(size)) {
+        return Err(AppError::BadRequest(format!("width and height must be between {} and {}", map::MIN_SIZE, map::MAX_SIZE)));
+    }
+    let grid = service.heatmap(&tenant, &query).await?;
+    let Some(format) = format else {
+        return Ok(Json(grid.into_feature_collection()).into_response());
+    };
+    if grid.is_empty() {
+        return Err(AppError::BadRequest("No points in the requested range".to_string()));
+    }
+    let cells = grid.values(metric);
+    let image = tokio::task::spawn_blocking(move || map::render_heatmap(&cells, width, height, format))
+        .await
+        .map_err(|e| AppError::InternalServerError(format!("热力图渲染任务失败: {}", e)))?
+        .map_err(|e| AppError::InternalServerError(format!("热力图渲染失败: {}", e)))?;
+    Ok(([(header::CONTENT_TYPE, format.content_type())], image).into_response())
 }
-async fn get_latest_track (State(service): State<Arc<ShipTrackService>>) -> Json<Option<ShipTrackResponseDto>> {
-    let res = service.get_latest().await.unwrap();
-    Json(res.map(ShipTrackResponseDto::from))
+
+async fn get_track_stats(State(service): State<Arc<ShipTrackService>>, tenant: Tenant, Path(id): Path<String>) -> Result<Json<TrackStatsDto>, AppError> {
+    let track = service.get(&tenant, &id).await?
+        .ok_or_else(|| AppError::NotFound("Track not found".to_string()))?;
+    Ok(Json(TrackStatsDto::from(&track)))
 }
 
+// 按下标 ?start=&end= 或时间 ?from=&to= 读取部分航迹点，?crs= 指定输出坐标系。
+// 被过滤的点不返回但保留原下标；平滑只在本次返回的范围内进行
+async fn get_track_points(State(service): State<Arc<ShipTrackService>>, tenant: Tenant, Path(id): Path<String>, Query(range): Query<PointRange>, Query(output): Query<OutputQuery>) -> Result<Json<Vec<IndexedPointDto>>, AppError> {
+    let mut points = service.get_points(&tenant, &id, range).await?;
+    if !output.raw {
+        points.retain(|p| p.point.rejected.is_none());
+        let config = service.track_filter(&tenant).await?;
+        if config.enabled && config.smoothing == Smoothing::Kalman {
+            let mut smoothed: Vec<TrackPoint> = points.iter().map(|p| TrackPoint::from(p.point.clone())).collect();
+            kalman_smooth(&config, &mut smoothed);
+            for (p, s) in points.iter_mut().zip(smoothed) {
+                [p.point.lon, p.point.lat] = s.position();
+            }
+        }
+    }
+    if !output.crs.is_wgs84() {
+        let projection = output.crs.projection(points.first().map(|p| [p.point.lon, p.point.lat]));
+        points.iter_mut().for_each(|p| p.point.project(&projection));
+    }
+    Ok(Json(points))
+}
+
+const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+// NDJSON 回放每次写出的帧数
+const REPLAY_CHUNK_FRAMES: usize = 256;
+
+// 按 ?step= 秒的固定间隔插值回放航迹，?telemetry=true 合并关联飞行记录的遥测。
+// ?format=ndjson 或 Accept: application/x-ndjson 时每行一帧，以流的方式返回
+async fn replay_track(State(service): State<Arc<ShipTrackService>>, tenant: Tenant, headers: HeaderMap, Path(id): Path<String>, Query(query): Query<ReplayQuery>) -> Result<Response, AppError> {
+    let ndjson = match query.format.as_deref() {
+        Some("ndjson") => true,
+        Some("json") => false,
+        Some(other) => return Err(AppError::BadRequest(format!("Unsupported replay format: {}", other))),
+        None => headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()).is_some_and(|accept| accept.contains(NDJSON_CONTENT_TYPE)),
+    };
+    let step_seconds = query.step_ms().map_err(AppError::BadRequest)? as f64 / 1000.0;
+    let (track_id, flight_id, replay) = service.replay(&tenant, &id, &query).await?;
+    if ndjson {
+        let mut frames = replay;
+        let chunks = std::iter::from_fn(move || {
+            let mut chunk = String::new();
+            for frame in frames.by_ref().take(REPLAY_CHUNK_FRAMES) {
+                if let Ok(line) = serde_json::to_string(&frame) {
+                    chunk.push_str(&line);
+                    chunk.push('\n');
+                }
+            }
+            (!chunk.is_empty()).then_some(Ok::<_, Infallible>(chunk))
+        });
+        let body = Body::from_stream(futures::stream::iter(chunks));
+        return Ok(([(header::CONTENT_TYPE, NDJSON_CONTENT_TYPE)], body).into_response());
+    }
+    Ok(Json(ReplayDto {
+        track_id,
+        flight_id: flight_id.map(|id| id.to_hex()),
+        step_seconds,
+        crs: replay.crs_name(),
+        frames: replay.collect(),
+    }).into_response())
+}
+
+// 空间查询，返回航迹摘要
+async fn get_tracks_near(State(service): State<Arc<ShipTrackService>>, tenant: Tenant, Query(query): Query<NearQuery>) -> Result<Json<Vec<ShipTrackSummaryDto>>, AppError> {
+    let tracks = service.near(&tenant, query).await?;
+    Ok(Json(tracks.into_iter().map(ShipTrackSummaryDto::from).collect()))
+}
+
+async fn get_tracks_within_bbox(State(service): State<Arc<ShipTrackService>>, tenant: Tenant, Query(query): Query<BboxQuery>) -> Result<Json<Vec<ShipTrackSummaryDto>>, AppError> {
+    let tracks = service.within(&tenant, bbox_polygon(&query.bbox)?).await?;
+    Ok(Json(tracks.into_iter().map(ShipTrackSummaryDto::from).collect()))
+}
+
+// 请求体为 GeoJSON Polygon / MultiPolygon
+async fn get_tracks_within_polygon(State(service): State<Arc<ShipTrackService>>, tenant: Tenant, Json(polygon): Json<bson::Document>) -> Result<Json<Vec<ShipTrackSummaryDto>>, AppError> {
+    let tracks = service.within(&tenant, polygon).await?;
+    Ok(Json(tracks.into_iter().map(ShipTrackSummaryDto::from).collect()))
+}
+
+// 请求体为任意 GeoJSON 几何
+async fn get_tracks_intersecting(State(service): State<Arc<ShipTrackService>>, tenant: Tenant, Json(geometry): Json<bson::Document>) -> Result<Json<Vec<ShipTrackSummaryDto>>, AppError> {
+    let tracks = service.intersects(&tenant, geometry).await?;
+    Ok(Json(tracks.into_iter().map(ShipTrackSummaryDto::from).collect()))
+}
+
+async fn get_monthly_distance(State(service): State<Arc<ShipTrackService>>, tenant: Tenant, Query(query): Query<MonthlyDistanceQuery>) -> Result<Json<Vec<MonthlyDistanceDto>>, AppError> {
+    Ok(Json(service.monthly_distance(&tenant, query).await?))
+}
+
+// 批量删除：必须提供筛选条件，并用第一次请求返回的确认令牌再次提交
+async fn bulk_delete_tracks(State(service): State<Arc<ShipTrackService>>, ctx: AuditContext, Json(request): Json<BulkDeleteRequestDto<TrackBulkDeleteFilter>>) -> Result<Json<serde_json::Value>, AppError> {
+    Ok(Json(service.bulk_delete(request, &ctx).await?))
+}
+async fn get_track_trash(State(service): State<Arc<ShipTrackService>>, tenant: Tenant) -> Result<Json<Vec<TrashEntryDto<ShipTrackResponseDto>>>, AppError> {
+    let tracks = service.get_trash(&tenant).await?;
+    Ok(Json(tracks.into_iter().map(|track| {
+        let (deleted_at, deleted_by) = (track.deleted_at, track.deleted_by.clone());
+        TrashEntryDto::new(ShipTrackResponseDto::from(track), deleted_at, deleted_by)
+    }).collect()))
+}
+async fn restore_track(State(service): State<Arc<ShipTrackService>>, Path(id): Path<String>, ctx: AuditContext) -> Result<Json<Option<ShipTrackResponseDto>>, AppError> {
+    let res = service.restore(&id, &ctx).await?;
+    Ok(Json(res.map(ShipTrackResponseDto::from)))
+}
+// 拆分航迹：{"index": n}、{"time": "..."} 或 {"gapSeconds": s}（按识别出的架次拆分），返回原航迹和拆出的新航迹
+async fn split_track(State(service): State<Arc<ShipTrackService>>, Path(id): Path<String>, ctx: AuditContext, Json(request): Json<SplitRequestDto>) -> Result<Json<Vec<ShipTrackSummaryDto>>, AppError> {
+    let tracks = service.split(&id, request, &ctx).await?;
+    Ok(Json(tracks.iter().map(ShipTrackSummaryDto::from).collect()))
+}
+
+// 把 {"trackId": "..."} 的点按时间合并进本航迹，被合并的航迹移入回收站
+async fn merge_track(State(service): State<Arc<ShipTrackService>>, Path(id): Path<String>, ctx: AuditContext, Json(request): Json<MergeRequestDto>) -> Result<Json<ShipTrackSummaryDto>, AppError> {
+    let track = service.merge(&id, request, &ctx).await?;
+    Ok(Json(ShipTrackSummaryDto::from(&track)))
+}
+
+// 裁剪首尾：请求体与 /points 的查询参数相同，下标 start/end 和/或时间 from/to
+async fn trim_track(State(service): State<Arc<ShipTrackService>>, Path(id): Path<String>, ctx: AuditContext, Json(range): Json<PointRange>) -> Result<Json<ShipTrackSummaryDto>, AppError> {
+    let track = service.trim(&id, range, &ctx).await?;
+    Ok(Json(ShipTrackSummaryDto::from(&track)))
+}
+
+// 按 ?gapSeconds=（默认 300）的时间间隔识别航迹中的架次，用于拆分前预览
+async fn get_track_sorties(State(service): State<Arc<ShipTrackService>>, tenant: Tenant, Path(id): Path<String>, Query(query): Query<SortieQuery>) -> Result<Json<Vec<SortieDto>>, AppError> {
+    Ok(Json(service.sorties(&tenant, &id, &query).await?))
+}
+
+// 实时订阅：?trackId= 订阅单条航迹，不传时订阅本租户的全部活跃航迹。
+// 连接后先收到 snapshot，之后是每个追加批次的 append；处理太慢时收到 lagged 和新的 snapshot
+async fn live_ws(State(service): State<Arc<ShipTrackService>>, tenant: Tenant, Query(query): Query<LiveQuery>, ws: WebSocketUpgrade) -> Result<Response, AppError> {
+    let subscription = LiveSubscription::open(service, tenant, query).await?;
+    Ok(ws.on_upgrade(move |socket| stream_live(socket, subscription)))
+}
+
+async fn stream_live(mut socket: WebSocket, mut subscription: LiveSubscription) {
+    loop {
+        tokio::select! {
+            message = subscription.next() => {
+                let text = match message {
+                    Ok(Some(text)) => text,
+                    Ok(None) => break,
+                    Err(e) => {
+                        tracing::error!("实时订阅读取快照失败: {:?}", e);
+                        break;
+                    }
+                };
+                match tokio::time::timeout(LIVE_SEND_TIMEOUT, socket.send(Message::Text(text.into()))).await {
+                    Ok(Ok(())) => {}
+                    _ => break,
+                }
+            }
+            // 客户端只会发送控制帧，关闭或出错时结束订阅
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+// 不支持 WebSocket 的客户端使用 SSE，消息内容与 WebSocket 相同
+async fn live_sse(State(service): State<Arc<ShipTrackService>>, tenant: Tenant, Query(query): Query<LiveQuery>) -> Result<Sse<impl futures::Stream<Item = Result<Event, Infallible>>>, AppError> {
+    let subscription = LiveSubscription::open(service, tenant, query).await?;
+    let stream = futures::stream::unfold(subscription, |mut subscription| async move {
+        match subscription.next().await {
+            Ok(Some(text)) => Some((Ok(Event::default().data(text)), subscription)),
+            Ok(None) => None,
+            Err(e) => {
+                tracing::error!("实时订阅读取快照失败: {:?}", e);
+                None
+            }
+        }
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+async fn get_latest_track (State(service): State<Arc<ShipTrackService>>, tenant: Tenant, Query(query): Query<TrackResponseQuery>) -> Result<Json<Option<ShipTrackResponseDto>>, AppError> {
+    let mut res = service.get_latest(&tenant).await?;
+    if !query.raw {
+        service.denoise(&tenant, res.as_mut_slice()).await?;
+    }
+    Ok(Json(res.map(|track| ShipTrackResponseDto::new(track, &query))))
+}
+
+// 带 seq / batchId 的批次可以安全重试；跳号的批次返回 409
 async fn append_track(
     State(service): State<Arc<ShipTrackService>>,
     Path(id): Path<String>, // 从路径获取 ID
+    Query(query): Query<TrackResponseQuery>,
+    ctx: AuditContext,
     Json(payload): Json<UpdateShipTrackPayload> // 使用新的 Payload
-) -> Json<Option<ShipTrackResponseDto>> { // 返回更新后的轨迹或 None
-    match service.append_coordinates_and_update(&id, payload.coordinates_to_add).await {
-        Ok(Some(updated_track_model)) => Json(Some(ShipTrackResponseDto::from(updated_track_model))),
-        Ok(None) => Json(None), // 文档未找到或未修改 (但 ReturnDocument::After 应该会返回)
-        Err(_e) => {
-            // 在实际应用中应记录错误 _e
-            // 并返回更合适的 HTTP 错误状态码
-            eprintln!("Error updating track: {:?}", _e); // 简单打印错误
-            Json(None) // 简化错误处理
-        }
+) -> Result<Json<Option<ShipTrackResponseDto>>, AppError> { // 返回更新后的轨迹或 None
+    let mut points: Vec<TrackPoint> = payload.coordinates_to_add.into_iter().map(TrackPoint::from).collect();
+    points.iter_mut().try_for_each(|point| point.convert_to_wgs84(payload.crs)).map_err(AppError::BadRequest)?;
+    let batch = AppendRequest {
+        points,
+        seq: payload.seq,
+        batch_id: payload.batch_id,
+    };
+    let mut res = service.append_coordinates_and_update(&id, batch, &ctx).await?;
+    if let (Some((track, _)), false) = (&mut res, query.raw) {
+        service.denoise(&ctx.tenant, std::slice::from_mut(track)).await?;
     }
-}
\ No newline at end of file
+    Ok(Json(res.map(|(updated_track_model, ack)| {
+        let mut dto = ShipTrackResponseDto::new(updated_track_model, &query);
+        dto.append = Some(ack);
+        dto
+    })))
+}
@@ -10,6 +10,9 @@ use serde_json::json;
 pub enum AppError {
     Mongo(mongodb::error::Error),
     BadRequest(String),
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
     InternalServerError(String),
     // 在此添加其他错误变体
 }
@@ -23,6 +26,9 @@ impl IntoResponse for AppError {
                 format!("数据库错误: {}", err),
             ),
             AppError::BadRequest(err) => (StatusCode::BAD_REQUEST, err.to_string()),
+            AppError::Forbidden(err) => (StatusCode::FORBIDDEN, err.to_string()),
+            AppError::NotFound(err) => (StatusCode::NOT_FOUND, err.to_string()),
+            AppError::Conflict(err) => (StatusCode::CONFLICT, err.to_string()),
             AppError::InternalServerError(err) => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
         };
 
@@ -41,3 +47,10 @@ impl From<mongodb::error::Error> for AppError {
     }
 }
 
+
+// 模型序列化为 BSON 失败属于服务端错误
+impl From<bson::ser::Error> for AppError {
+    fn from(err: bson::ser::Error) -> Self {
+        AppError::InternalServerError(format!("序列化错误: {}", err))
+    }
+}
@@ -0,0 +1,35 @@
+use std::convert::Infallible;
+use std::fmt::Write;
+use bytes::Bytes;
+use futures::Stream;
+use crate::export::{chunked, escape_xml, track_name};
+use crate::model::ship_track::ShipTrack;
+
+pub const CONTENT_TYPE: &str = "application/gpx+xml";
+
+// GPX 1.1: trk/trkseg/trkpt
+pub fn stream(track: ShipTrack) -> impl Stream<Item = Result<Bytes, Infallible>> {
+    let name = escape_xml(&track_name(&track));
+    let start_time = track.start_time.try_to_rfc3339_string().unwrap_or_default();
+    let header = format!(
+        concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<gpx version=\"1.1\" creator=\"drone_al\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n",
+            "  <metadata>\n    <name>{name}</name>\n    <time>{time}</time>\n  </metadata>\n",
+            "  <trk>\n    <name>{name}</name>\n    <trkseg>\n",
+        ),
+        name = name,
+        time = start_time,
+    );
+    let footer = "    </trkseg>\n  </trk>\n</gpx>\n".to_string();
+    chunked(header, track.coordinates, |out, point| {
+        let _ = write!(out, "      <trkpt lat=\"{}\" lon=\"{}\">", point.lat, point.lon);
+        if let Some(altitude) = point.altitude {
+            let _ = write!(out, "<ele>{}</ele>", altitude);
+        }
+        if let Some(time) = point.time.and_then(|t| t.try_to_rfc3339_string().ok()) {
+            let _ = write!(out, "<time>{}</time>", time);
+        }
+        let _ = writeln!(out, "</trkpt>");
+    }, footer)
+}
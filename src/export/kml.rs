@@ -0,0 +1,45 @@
+use std::convert::Infallible;
+use std::fmt::Write;
+use bytes::Bytes;
+use futures::Stream;
+use crate::export::{chunked, escape_xml, track_name};
+use crate::model::ship_track::ShipTrack;
+use crate::model::track_point::TrackPoint;
+
+pub const CONTENT_TYPE: &str = "application/vnd.google-earth.kml+xml";
+
+fn placemark(name: &str, style: &str, point: Option<[f64; 2]>) -> String {
+    match point {
+        Some([lon, lat]) => format!(
+            "    <Placemark>\n      <name>{}</name>\n      <styleUrl>#{}</styleUrl>\n      <Point><coordinates>{},{},0</coordinates></Point>\n    </Placemark>\n",
+            name, style, lon, lat
+        ),
+        None => String::new(),
+    }
+}
+
+// KML 2.2：带样式的 LineString，以及起点/终点标记
+pub fn stream(track: ShipTrack) -> impl Stream<Item = Result<Bytes, Infallible>> {
+    let name = escape_xml(&track_name(&track));
+    let start = track.coordinates.first().map(TrackPoint::position);
+    let end = track.coordinates.last().map(TrackPoint::position);
+    let header = format!(
+        concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n  <Document>\n    <name>{name}</name>\n",
+            "    <Style id=\"track\"><LineStyle><color>ff0000ff</color><width>3</width></LineStyle></Style>\n",
+            "    <Style id=\"start\"><IconStyle><color>ff00ff00</color></IconStyle></Style>\n",
+            "    <Style id=\"end\"><IconStyle><color>ff0000ff</color></IconStyle></Style>\n",
+            "{start}{end}",
+            "    <Placemark>\n      <name>{name}</name>\n      <styleUrl>#track</styleUrl>\n",
+            "      <LineString>\n        <tessellate>1</tessellate>\n        <coordinates>\n",
+        ),
+        name = name,
+        start = placemark("Start", "start", start),
+        end = placemark("End", "end", end),
+    );
+    let footer = "        </coordinates>\n      </LineString>\n    </Placemark>\n  </Document>\n</kml>\n".to_string();
+    chunked(header, track.coordinates, |out, point| {
+        let _ = writeln!(out, "          {},{},{}", point.lon, point.lat, point.altitude.unwrap_or(0.0));
+    }, footer)
+}
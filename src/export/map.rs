@@ -0,0 +1,321 @@
+// 静态地图渲染（PNG / SVG），不依赖瓦片服务：航迹图绘制投影后的航迹、起终点、比例尺，以及可选的围栏和风机位置；
+// 热力图把 geohash 单元格按数值着色绘制。
+use std::f64::consts::FRAC_PI_4;
+use std::sync::OnceLock;
+use image::codecs::png::PngEncoder;
+use image::{ExtendedColorType, ImageEncoder};
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use plotters::style::{register_font, FontStyle};
+use crate::geo::EARTH_RADIUS_M;
+use crate::model::geofence::{Geofence, GeofenceKind, GeofenceShape};
+
+pub const MIN_SIZE: u32 = 64;
+pub const MAX_SIZE: u32 = 4096;
+// 地图四周留白（像素），以及只有一个点时的最小显示范围（米）
+const MARGIN: f64 = 24.0;
+const MIN_SPAN_M: f64 = 200.0;
+// 圆形围栏按多边形近似绘制的边数
+const CIRCLE_SEGMENTS: usize = 72;
+const DEFAULT_FONT_PATH: &str = "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf";
+
+const BACKGROUND: RGBColor = RGBColor(245, 245, 240);
+const TRACK: RGBColor = RGBColor(0, 102, 204);
+const START: RGBColor = RGBColor(34, 139, 34);
+const END: RGBColor = RGBColor(200, 30, 30);
+const TURBINE: RGBColor = RGBColor(60, 60, 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapFormat {
+    Png,
+    Svg,
+}
+
+impl MapFormat {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "png" => Ok(MapFormat::Png),
+            "svg" => Ok(MapFormat::Svg),
+            other => Err(format!("Unsupported map format: {}", other)),
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            MapFormat::Png => "image/png",
+            MapFormat::Svg => "image/svg+xml",
+        }
+    }
+}
+
+// 要绘制的内容，坐标均为 WGS-84 [经度, 纬度]
+pub struct MapScene {
+    pub track: Vec<[f64; 2]>,
+    pub geofences: Vec<Geofence>,
+    pub turbines: Vec<[f64; 2]>,
+}
+
+// 风机避让围栏的中心视为风机位置
+pub fn turbine_positions(geofences: &[Geofence]) -> Vec<[f64; 2]> {
+    geofences
+        .iter()
+        .filter(|geofence| geofence.kind == GeofenceKind::TurbineExclusion)
+        .filter_map(|geofence| match &geofence.shape {
+            GeofenceShape::Circle { center, .. } => Some(*center),
+            GeofenceShape::Polygon { coordinates } => {
+                let ring = coordinates.first()?;
+                // 闭合环的最后一个点与第一个点重复，不参与平均
+                let vertices = &ring[..ring.len().saturating_sub(1)];
+                let n = vertices.len().max(1) as f64;
+                Some(vertices.iter().fold([0.0, 0.0], |acc, p| [acc[0] + p[0] / n, acc[1] + p[1] / n]))
+            }
+        })
+        .collect()
+}
+
+pub fn render(scene: &MapScene, width: u32, height: u32, format: MapFormat) -> Result<Vec<u8>, String> {
+    let view = View::fit(&scene.track, width, height);
+    encode(&Layer::Track(scene), &view, format)
+}
+
+// 热力图栅格：cells 为每个单元格的范围 [最小经度, 最小纬度, 最大经度, 最大纬度] 和数值，
+// 颜色按数值的对数缩放，避免少数热点单元格让其余区域都接近底色
+pub fn render_heatmap(cells: &[([f64; 4], f64)], width: u32, height: u32, format: MapFormat) -> Result<Vec<u8>, String> {
+    let corners: Vec<[f64; 2]> = cells.iter().flat_map(|(b, _)| [[b[0], b[1]], [b[2], b[3]]]).collect();
+    let view = View::fit(&corners, width, height);
+    encode(&Layer::Heatmap(cells), &view, format)
+}
+
+enum Layer<'a> {
+    Track(&'a MapScene),
+    Heatmap(&'a [([f64; 4], f64)]),
+}
+
+fn encode(layer: &Layer, view: &View, format: MapFormat) -> Result<Vec<u8>, String> {
+    let (width, height) = (view.width as u32, view.height as u32);
+    match format {
+        MapFormat::Svg => {
+            let mut svg = String::new();
+            {
+                let root = SVGBackend::with_string(&mut svg, (width, height)).into_drawing_area();
+                paint(&root, layer, view, true).map_err(|e| e.to_string())?;
+                root.present().map_err(|e| e.to_string())?;
+            }
+            Ok(svg.into_bytes())
+        }
+        MapFormat::Png => {
+            let mut pixels = vec![0u8; width as usize * height as usize * 3];
+            {
+                let root = BitMapBackend::with_buffer(&mut pixels, (width, height)).into_drawing_area();
+                // 位图中的文字需要字体，字体不可用时省略比例尺的文字标注
+                paint(&root, layer, view, font_ready()).map_err(|e| e.to_string())?;
+                root.present().map_err(|e| e.to_string())?;
+            }
+            let mut png = Vec::new();
+            PngEncoder::new(&mut png)
+                .write_image(&pixels, width, height, ExtendedColorType::Rgb8)
+                .map_err(|e| e.to_string())?;
+            Ok(png)
+        }
+    }
+}
+
+fn paint<DB: DrawingBackend>(root: &DrawingArea<DB, Shift>, layer: &Layer, view: &View, labels: bool) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    match layer {
+        Layer::Track(scene) => draw(root, scene, view, labels),
+        Layer::Heatmap(cells) => draw_heatmap(root, cells, view, labels),
+    }
+}
+
+// 位图文字使用的字体，首次渲染时从 MAP_FONT_PATH（默认 DejaVu Sans）加载
+fn font_ready() -> bool {
+    static READY: OnceLock<bool> = OnceLock::new();
+    *READY.get_or_init(|| {
+        let path = std::env::var("MAP_FONT_PATH").unwrap_or_else(|_| DEFAULT_FONT_PATH.to_string());
+        match std::fs::read(&path) {
+            Ok(bytes) => {
+                let registered = register_font("sans-serif", FontStyle::Normal, Box::leak(bytes.into_boxed_slice())).is_ok();
+                if !registered {
+                    tracing::warn!("地图字体 {} 无法解析，PNG 地图将不显示文字", path);
+                }
+                registered
+            }
+            Err(e) => {
+                tracing::warn!("读取地图字体 {} 失败，PNG 地图将不显示文字: {}", path, e);
+                false
+            }
+        }
+    })
+}
+
+// Web 墨卡托投影下的视口：航迹居中并保持纵横比
+struct View {
+    center: [f64; 2],
+    scale: f64,
+    width: f64,
+    height: f64,
+    meters_per_pixel: f64,
+}
+
+impl View {
+    fn fit(track: &[[f64; 2]], width: u32, height: u32) -> Self {
+        let projected: Vec<[f64; 2]> = track.iter().map(|p| mercator(*p)).collect();
+        let (mut min, mut max) = ([f64::INFINITY; 2], [f64::NEG_INFINITY; 2]);
+        for p in &projected {
+            min = [min[0].min(p[0]), min[1].min(p[1])];
+            max = [max[0].max(p[0]), max[1].max(p[1])];
+        }
+        if projected.is_empty() {
+            (min, max) = ([0.0; 2], [0.0; 2]);
+        }
+        let center = [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0];
+        // 墨卡托单位在中心纬度处约等于 R·cos(φ) 米
+        let unit_meters = EARTH_RADIUS_M * inverse_mercator_lat(center[1]).cos();
+        let min_span = MIN_SPAN_M / unit_meters;
+        let span = [(max[0] - min[0]).max(min_span), (max[1] - min[1]).max(min_span)];
+        let (width, height) = (width as f64, height as f64);
+        let scale = ((width - 2.0 * MARGIN).max(1.0) / span[0]).min((height - 2.0 * MARGIN).max(1.0) / span[1]);
+        View { center, scale, width, height, meters_per_pixel: unit_meters / scale }
+    }
+
+    fn pixel(&self, position: [f64; 2]) -> (i32, i32) {
+        let [x, y] = mercator(position);
+        (
+            (self.width / 2.0 + (x - self.center[0]) * self.scale).round() as i32,
+            (self.height / 2.0 - (y - self.center[1]) * self.scale).round() as i32,
+        )
+    }
+
+    // 投影后去掉落在同一像素上的连续点，长航迹的路径不会比画面更精细
+    fn path(&self, positions: &[[f64; 2]]) -> Vec<(i32, i32)> {
+        let mut path: Vec<(i32, i32)> = Vec::with_capacity(positions.len());
+        for position in positions {
+            let pixel = self.pixel(*position);
+            if path.last() != Some(&pixel) {
+                path.push(pixel);
+            }
+        }
+        path
+    }
+}
+
+// [经度弧度, 墨卡托纵坐标]
+fn mercator(position: [f64; 2]) -> [f64; 2] {
+    let lat = position[1].clamp(-85.0, 85.0).to_radians();
+    [position[0].to_radians(), (FRAC_PI_4 + lat / 2.0).tan().ln()]
+}
+
+fn inverse_mercator_lat(y: f64) -> f64 {
+    2.0 * y.exp().atan() - std::f64::consts::FRAC_PI_2
+}
+
+// 以中心点和半径（米）近似圆形围栏
+fn circle_ring(center: [f64; 2], radius: f64) -> Vec<[f64; 2]> {
+    let d_lat = (radius / EARTH_RADIUS_M).to_degrees();
+    let d_lon = d_lat / center[1].to_radians().cos().max(1e-6);
+    (0..=CIRCLE_SEGMENTS)
+        .map(|i| {
+            let angle = i as f64 / CIRCLE_SEGMENTS as f64 * std::f64::consts::TAU;
+            [center[0] + d_lon * angle.cos(), center[1] + d_lat * angle.sin()]
+        })
+        .collect()
+}
+
+fn geofence_color(kind: GeofenceKind) -> (RGBColor, f64) {
+    match kind {
+        GeofenceKind::NoFly => (RGBColor(220, 40, 40), 0.2),
+        GeofenceKind::TurbineExclusion => (RGBColor(240, 140, 0), 0.2),
+        GeofenceKind::WorkArea => (RGBColor(40, 160, 60), 0.06),
+    }
+}
+
+fn draw<DB: DrawingBackend>(root: &DrawingArea<DB, Shift>, scene: &MapScene, view: &View, labels: bool) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    root.fill(&BACKGROUND)?;
+
+    for geofence in &scene.geofences {
+        let (color, alpha) = geofence_color(geofence.kind);
+        let rings = match &geofence.shape {
+            GeofenceShape::Polygon { coordinates } => coordinates.clone(),
+            GeofenceShape::Circle { center, radius_meters } => vec![circle_ring(*center, *radius_meters)],
+        };
+        for (i, ring) in rings.iter().enumerate() {
+            let path = view.path(ring);
+            // 洞用背景色覆盖
+            if i == 0 {
+                root.draw(&Polygon::new(path.clone(), color.mix(alpha).filled()))?;
+            } else {
+                root.draw(&Polygon::new(path.clone(), BACKGROUND.filled()))?;
+            }
+            root.draw(&PathElement::new(path, color.stroke_width(1)))?;
+        }
+    }
+
+    let path = view.path(&scene.track);
+    if path.len() > 1 {
+        root.draw(&PathElement::new(path, TRACK.stroke_width(3)))?;
+    }
+
+    for turbine in &scene.turbines {
+        let (x, y) = view.pixel(*turbine);
+        root.draw(&Circle::new((x, y), 5, TURBINE.filled()))?;
+        root.draw(&Circle::new((x, y), 5, WHITE.stroke_width(1)))?;
+    }
+
+    if let (Some(first), Some(last)) = (scene.track.first(), scene.track.last()) {
+        for (position, color) in [(last, END), (first, START)] {
+            let pixel = view.pixel(*position);
+            root.draw(&Circle::new(pixel, 7, color.filled()))?;
+            root.draw(&Circle::new(pixel, 7, WHITE.stroke_width(2)))?;
+        }
+    }
+
+    draw_scale_bar(root, view, labels)
+}
+
+fn draw_heatmap<DB: DrawingBackend>(root: &DrawingArea<DB, Shift>, cells: &[([f64; 4], f64)], view: &View, labels: bool) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    root.fill(&BACKGROUND)?;
+    let max = cells.iter().map(|(_, value)| *value).fold(0.0, f64::max);
+    for (bounds, value) in cells {
+        if *value <= 0.0 {
+            continue;
+        }
+        let (x0, y0) = view.pixel([bounds[0], bounds[3]]);
+        let (x1, y1) = view.pixel([bounds[2], bounds[1]]);
+        // 单元格小于一个像素时至少占一个像素
+        let corner = (x1.max(x0 + 1), y1.max(y0 + 1));
+        root.draw(&Rectangle::new([(x0, y0), corner], heat_color(value.ln_1p() / max.ln_1p()).filled()))?;
+    }
+    draw_scale_bar(root, view, labels)
+}
+
+// 0..=1 映射到浅黄、橙、深红的渐变
+fn heat_color(t: f64) -> RGBColor {
+    const STOPS: [(f64, [f64; 3]); 3] = [(0.0, [255.0, 237.0, 160.0]), (0.5, [253.0, 141.0, 60.0]), (1.0, [189.0, 0.0, 38.0])];
+    let t = if t.is_finite() { t.clamp(0.0, 1.0) } else { 1.0 };
+    let i = if t <= STOPS[1].0 { 0 } else { 1 };
+    let ((a, from), (b, to)) = (STOPS[i], STOPS[i + 1]);
+    let k = (t - a) / (b - a);
+    let channel = |c: usize| (from[c] + (to[c] - from[c]) * k).round() as u8;
+    RGBColor(channel(0), channel(1), channel(2))
+}
+
+// 左下角的比例尺，长度取约四分之一画面宽度内最大的 1、2、5 × 10ⁿ 米
+fn draw_scale_bar<DB: DrawingBackend>(root: &DrawingArea<DB, Shift>, view: &View, labels: bool) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    let target = view.meters_per_pixel * view.width / 4.0;
+    if !target.is_finite() || target <= 0.0 {
+        return Ok(());
+    }
+    let magnitude = 10f64.powf(target.log10().floor());
+    let meters = [5.0, 2.0, 1.0].into_iter().map(|m| m * magnitude).find(|m| *m <= target).unwrap_or(magnitude);
+    let length = (meters / view.meters_per_pixel).round() as i32;
+    let (x, y) = (MARGIN as i32, view.height as i32 - MARGIN as i32);
+    let style = BLACK.stroke_width(2);
+    root.draw(&PathElement::new(vec![(x, y), (x + length, y)], style))?;
+    root.draw(&PathElement::new(vec![(x, y - 6), (x, y)], style))?;
+    root.draw(&PathElement::new(vec![(x + length, y - 6), (x + length, y)], style))?;
+    if labels {
+        let label = if meters >= 1000.0 { format!("{} km", meters / 1000.0) } else { format!("{} m", meters) };
+        root.draw(&Text::new(label, (x, y - 22), ("sans-serif", 14).into_font().color(&BLACK)))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,59 @@
+// 航迹文件导出。输出按块生成，避免超长航迹一次性拼接整个文档。
+pub mod gpx;
+pub mod kml;
+pub mod map;
+
+use std::convert::Infallible;
+use std::sync::Arc;
+use bytes::Bytes;
+use futures::stream::{self, Stream, StreamExt};
+use crate::model::ship_track::ShipTrack;
+use crate::model::track_point::TrackPoint;
+
+// 每个输出块包含的点数
+const POINTS_PER_CHUNK: usize = 1000;
+
+pub fn escape_xml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // XML 1.0 不允许的控制字符直接丢弃
+            c if (c as u32) < 0x20 && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// 导出文件中使用的航迹名称
+pub fn track_name(track: &ShipTrack) -> String {
+    match &track.drone_id {
+        Some(drone_id) => format!("{} {}", drone_id, track.id.to_hex()),
+        None => track.id.to_hex(),
+    }
+}
+
+// 文档头 + 按块渲染的坐标 + 文档尾
+fn chunked<F>(header: String, coordinates: Vec<TrackPoint>, render_point: F, footer: String) -> impl Stream<Item = Result<Bytes, Infallible>>
+where
+    F: Fn(&mut String, &TrackPoint) + Send + 'static,
+{
+    let coordinates = Arc::new(coordinates);
+    let starts = (0..coordinates.len()).step_by(POINTS_PER_CHUNK);
+    let body = stream::iter(starts).map(move |start| {
+        let end = (start + POINTS_PER_CHUNK).min(coordinates.len());
+        let mut chunk = String::new();
+        for point in &coordinates[start..end] {
+            render_point(&mut chunk, point);
+        }
+        Ok(Bytes::from(chunk))
+    });
+    stream::once(async move { Ok(Bytes::from(header)) })
+        .chain(body)
+        .chain(stream::once(async move { Ok(Bytes::from(footer)) }))
+}
@@ -0,0 +1,244 @@
+// 坐标系转换。存储统一使用 WGS-84，输入时转换为 WGS-84，输出时按需转换。
+// GCJ-02 为国内地图使用的加密坐标，BD-09 在 GCJ-02 基础上再次偏移；UTM 输出为 [东向, 北向]（米）。
+use std::f64::consts::PI;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Crs {
+    #[default]
+    Wgs84,
+    Gcj02,
+    Bd09,
+    Utm,
+}
+
+impl Crs {
+    pub fn is_wgs84(self) -> bool {
+        self == Crs::Wgs84
+    }
+
+    // 输入坐标转换为 WGS-84。UTM 坐标缺少带号，不支持作为输入
+    pub fn to_wgs84(self, position: [f64; 2]) -> Result<[f64; 2], String> {
+        match self {
+            Crs::Wgs84 => Ok(position),
+            Crs::Gcj02 => Ok(gcj02_to_wgs84(position)),
+            Crs::Bd09 => Ok(gcj02_to_wgs84(bd09_to_gcj02(position))),
+            Crs::Utm => Err("UTM is only supported as an output coordinate system".to_string()),
+        }
+    }
+
+    // 输出用的投影。UTM 的带号由 reference 决定，同一条航迹的所有点使用同一个带号，便于直接量算距离
+    pub fn projection(self, reference: Option<[f64; 2]>) -> Projection {
+        let zone = match self {
+            Crs::Utm => reference.map(UtmZone::of),
+            _ => None,
+        };
+        Projection { crs: self, zone }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UtmZone {
+    pub number: u8,
+    pub north: bool,
+}
+
+impl UtmZone {
+    // 标准 6° 分带，不处理挪威和斯瓦尔巴的特殊带
+    pub fn of(position: [f64; 2]) -> Self {
+        let number = (((position[0] + 180.0) / 6.0).floor() as i32).clamp(0, 59) as u8 + 1;
+        UtmZone { number, north: position[1] >= 0.0 }
+    }
+
+    pub fn epsg(&self) -> u32 {
+        if self.north { 32600 + self.number as u32 } else { 32700 + self.number as u32 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Projection {
+    crs: Crs,
+    zone: Option<UtmZone>,
+}
+
+impl Projection {
+    pub fn is_identity(&self) -> bool {
+        self.crs.is_wgs84()
+    }
+
+    pub fn apply(&self, position: [f64; 2]) -> [f64; 2] {
+        match self.crs {
+            Crs::Wgs84 => position,
+            Crs::Gcj02 => wgs84_to_gcj02(position),
+            Crs::Bd09 => gcj02_to_bd09(wgs84_to_gcj02(position)),
+            Crs::Utm => wgs84_to_utm(position, self.zone.unwrap_or_else(|| UtmZone::of(position))),
+        }
+    }
+
+    // 响应中标注的坐标系名称，UTM 使用对应的 EPSG 代码
+    pub fn name(&self) -> String {
+        match (self.crs, self.zone) {
+            (Crs::Wgs84, _) => "WGS84".to_string(),
+            (Crs::Gcj02, _) => "GCJ02".to_string(),
+            (Crs::Bd09, _) => "BD09".to_string(),
+            (Crs::Utm, Some(zone)) => format!("EPSG:{}", zone.epsg()),
+            (Crs::Utm, None) => "UTM".to_string(),
+        }
+    }
+}
+
+// GCJ-02 使用的克拉索夫斯基椭球参数
+const KRASOVSKY_A: f64 = 6_378_245.0;
+const KRASOVSKY_EE: f64 = 0.006_693_421_622_965_943;
+const BD_X_PI: f64 = PI * 3000.0 / 180.0;
+
+// 中国境外不做偏移
+fn out_of_china(position: [f64; 2]) -> bool {
+    !(72.004..=137.8347).contains(&position[0]) || !(0.8293..=55.8271).contains(&position[1])
+}
+
+fn transform_lat(x: f64, y: f64) -> f64 {
+    let mut ret = -100.0 + 2.0 * x + 3.0 * y + 0.2 * y * y + 0.1 * x * y + 0.2 * x.abs().sqrt();
+    ret += (20.0 * (6.0 * x * PI).sin() + 20.0 * (2.0 * x * PI).sin()) * 2.0 / 3.0;
+    ret += (20.0 * (y * PI).sin() + 40.0 * (y / 3.0 * PI).sin()) * 2.0 / 3.0;
+    ret += (160.0 * (y / 12.0 * PI).sin() + 320.0 * (y * PI / 30.0).sin()) * 2.0 / 3.0;
+    ret
+}
+
+fn transform_lon(x: f64, y: f64) -> f64 {
+    let mut ret = 300.0 + x + 2.0 * y + 0.1 * x * x + 0.1 * x * y + 0.1 * x.abs().sqrt();
+    ret += (20.0 * (6.0 * x * PI).sin() + 20.0 * (2.0 * x * PI).sin()) * 2.0 / 3.0;
+    ret += (20.0 * (x * PI).sin() + 40.0 * (x / 3.0 * PI).sin()) * 2.0 / 3.0;
+    ret += (150.0 * (x / 12.0 * PI).sin() + 300.0 * (x / 30.0 * PI).sin()) * 2.0 / 3.0;
+    ret
+}
+
+pub fn wgs84_to_gcj02(position: [f64; 2]) -> [f64; 2] {
+    if out_of_china(position) {
+        return position;
+    }
+    let [lon, lat] = position;
+    let rad_lat = lat.to_radians();
+    let magic = 1.0 - KRASOVSKY_EE * rad_lat.sin().powi(2);
+    let sqrt_magic = magic.sqrt();
+    let d_lat = transform_lat(lon - 105.0, lat - 35.0) * 180.0 / ((KRASOVSKY_A * (1.0 - KRASOVSKY_EE)) / (magic * sqrt_magic) * PI);
+    let d_lon = transform_lon(lon - 105.0, lat - 35.0) * 180.0 / (KRASOVSKY_A / sqrt_magic * rad_lat.cos() * PI);
+    [lon + d_lon, lat + d_lat]
+}
+
+// GCJ-02 没有解析逆变换，迭代求解，精度约 1e-9 度
+pub fn gcj02_to_wgs84(position: [f64; 2]) -> [f64; 2] {
+    if out_of_china(position) {
+        return position;
+    }
+    let mut wgs = position;
+    for _ in 0..20 {
+        let shifted = wgs84_to_gcj02(wgs);
+        let (d_lon, d_lat) = (shifted[0] - position[0], shifted[1] - position[1]);
+        wgs = [wgs[0] - d_lon, wgs[1] - d_lat];
+        if d_lon.abs() < 1e-9 && d_lat.abs() < 1e-9 {
+            break;
+        }
+    }
+    wgs
+}
+
+pub fn gcj02_to_bd09(position: [f64; 2]) -> [f64; 2] {
+    let [x, y] = position;
+    let z = (x * x + y * y).sqrt() + 0.00002 * (y * BD_X_PI).sin();
+    let theta = y.atan2(x) + 0.000003 * (x * BD_X_PI).cos();
+    [z * theta.cos() + 0.0065, z * theta.sin() + 0.006]
+}
+
+pub fn bd09_to_gcj02(position: [f64; 2]) -> [f64; 2] {
+    let (x, y) = (position[0] - 0.0065, position[1] - 0.006);
+    let z = (x * x + y * y).sqrt() - 0.00002 * (y * BD_X_PI).sin();
+    let theta = y.atan2(x) - 0.000003 * (x * BD_X_PI).cos();
+    [z * theta.cos(), z * theta.sin()]
+}
+
+// WGS-84 椭球上的横轴墨卡托投影，返回 [东向, 北向]（米），南半球北向加 10000 km 假北
+pub fn wgs84_to_utm(position: [f64; 2], zone: UtmZone) -> [f64; 2] {
+    const A: f64 = 6_378_137.0;
+    const F: f64 = 1.0 / 298.257_223_563;
+    const K0: f64 = 0.9996;
+    let e2 = F * (2.0 - F);
+    let ep2 = e2 / (1.0 - e2);
+    let phi = position[1].to_radians();
+    let lambda0 = ((zone.number as f64 - 1.0) * 6.0 - 180.0 + 3.0).to_radians();
+    let n = A / (1.0 - e2 * phi.sin().powi(2)).sqrt();
+    let t = phi.tan().powi(2);
+    let c = ep2 * phi.cos().powi(2);
+    let a = phi.cos() * (position[0].to_radians() - lambda0);
+    let m = A * ((1.0 - e2 / 4.0 - 3.0 * e2.powi(2) / 64.0 - 5.0 * e2.powi(3) / 256.0) * phi
+        - (3.0 * e2 / 8.0 + 3.0 * e2.powi(2) / 32.0 + 45.0 * e2.powi(3) / 1024.0) * (2.0 * phi).sin()
+        + (15.0 * e2.powi(2) / 256.0 + 45.0 * e2.powi(3) / 1024.0) * (4.0 * phi).sin()
+        - (35.0 * e2.powi(3) / 3072.0) * (6.0 * phi).sin());
+    let easting = K0 * n * (a + (1.0 - t + c) * a.powi(3) / 6.0 + (5.0 - 18.0 * t + t * t + 72.0 * c - 58.0 * ep2) * a.powi(5) / 120.0) + 500_000.0;
+    let mut northing = K0 * (m + n * phi.tan() * (a * a / 2.0
+        + (5.0 - t + 9.0 * c + 4.0 * c * c) * a.powi(4) / 24.0
+        + (61.0 - 58.0 * t + t * t + 600.0 * c - 330.0 * ep2) * a.powi(6) / 720.0));
+    if !zone.north {
+        northing += 10_000_000.0;
+    }
+    [easting, northing]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BEIJING: [f64; 2] = [116.397_128, 39.916_527];
+
+    fn assert_close(actual: [f64; 2], expected: [f64; 2], tolerance: f64) {
+        assert!(
+            (actual[0] - expected[0]).abs() <= tolerance && (actual[1] - expected[1]).abs() <= tolerance,
+            "{:?} is not within {} of {:?}",
+            actual,
+            tolerance,
+            expected,
+        );
+    }
+
+    #[test]
+    fn gcj02_inverse_recovers_wgs84() {
+        let shifted = wgs84_to_gcj02(BEIJING);
+        // 国内偏移量在几百米量级
+        assert!((shifted[0] - BEIJING[0]).abs() > 1e-3);
+        assert_close(gcj02_to_wgs84(shifted), BEIJING, 1e-8);
+    }
+
+    #[test]
+    fn bd09_inverse_recovers_gcj02() {
+        let gcj = wgs84_to_gcj02(BEIJING);
+        assert_close(bd09_to_gcj02(gcj02_to_bd09(gcj)), gcj, 1e-5);
+        assert_close(Crs::Bd09.to_wgs84(Projection { crs: Crs::Bd09, zone: None }.apply(BEIJING)).unwrap(), BEIJING, 1e-5);
+    }
+
+    #[test]
+    fn no_offset_outside_china() {
+        let paris = [2.3522, 48.8566];
+        assert_eq!(wgs84_to_gcj02(paris), paris);
+        assert_eq!(gcj02_to_wgs84(paris), paris);
+    }
+
+    #[test]
+    fn utm_zone_and_central_meridian() {
+        let zone = UtmZone::of(BEIJING);
+        assert_eq!(zone, UtmZone { number: 50, north: true });
+        assert_eq!(zone.epsg(), 32650);
+        assert_eq!(UtmZone::of([180.0, -10.0]).number, 60);
+        // 中央经线上东向为 500 km，赤道北向为 0
+        assert_close(wgs84_to_utm([3.0, 0.0], UtmZone::of([3.0, 0.0])), [500_000.0, 0.0], 1e-6);
+        // 南纬 10 度：假北减去 k0 × 子午线弧长 1105854.8 m
+        let south = wgs84_to_utm([3.0, -10.0], UtmZone::of([3.0, -10.0]));
+        assert_close(south, [500_000.0, 10_000_000.0 - 0.9996 * 1_105_854.8], 1.0);
+    }
+
+    #[test]
+    fn utm_is_output_only() {
+        assert!(Crs::Utm.to_wgs84(BEIJING).is_err());
+        assert_eq!(Crs::Utm.projection(Some(BEIJING)).name(), "EPSG:32650");
+    }
+}
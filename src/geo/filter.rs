@@ -0,0 +1,189 @@
+// GPS 噪声过滤：基于速度的离群点剔除、重复点抑制和卡尔曼平滑。
+use crate::geo::{haversine, EARTH_RADIUS_M};
+use crate::model::track_filter::{FilterState, TrackFilterConfig};
+use crate::model::track_point::{RejectReason, TrackPoint};
+
+// 逐点判定新追加的点，被拒绝的点只打标记，不从列表中移除。
+// state 为上一批结束时的状态，判定结束后更新为本批之后的状态
+pub fn reject_outliers(config: &TrackFilterConfig, state: &mut FilterState, points: &mut [TrackPoint]) {
+    for point in points.iter_mut() {
+        point.rejected = None;
+        let Some(last) = state.last_accepted else {
+            state.last_accepted = Some(*point);
+            continue;
+        };
+        let distance = haversine(last.position(), point.position());
+        let reason = match (last.time, point.time) {
+            (Some(from), Some(to)) => {
+                let seconds = (to.timestamp_millis() - from.timestamp_millis()) as f64 / 1000.0;
+                if seconds == 0.0 && distance <= config.duplicate_meters {
+                    Some(RejectReason::Duplicate)
+                } else if seconds <= 0.0 || distance / seconds > config.max_speed {
+                    // 时间倒退或停滞却发生了位移，同样按速度异常处理
+                    Some(RejectReason::Speed)
+                } else {
+                    None
+                }
+            }
+            (None, None) if distance <= config.duplicate_meters => Some(RejectReason::Duplicate),
+            // 缺少时间无法计算速度，直接接受
+            _ => None,
+        };
+        match reason {
+            Some(RejectReason::Speed) if state.consecutive_rejected + 1 >= config.max_consecutive_rejections => {
+                state.last_accepted = Some(*point);
+                state.consecutive_rejected = 0;
+            }
+            Some(reason) => {
+                point.rejected = Some(reason);
+                if reason == RejectReason::Speed {
+                    state.consecutive_rejected += 1;
+                }
+            }
+            None => {
+                state.last_accepted = Some(*point);
+                state.consecutive_rejected = 0;
+            }
+        }
+    }
+}
+
+// 匀速模型的卡尔曼滤波，东、北两个方向独立滤波。
+// 在以第一个点为原点的局部平面坐标（米）中计算，没有时间的点按 1 秒间隔处理
+pub fn kalman_smooth(config: &TrackFilterConfig, points: &mut [TrackPoint]) {
+    let Some(origin) = points.first().map(TrackPoint::position) else {
+        return;
+    };
+    let scale = origin[1].to_radians().cos();
+    let to_local = |p: [f64; 2]| [
+        (p[0] - origin[0]).to_radians() * scale * EARTH_RADIUS_M,
+        (p[1] - origin[1]).to_radians() * EARTH_RADIUS_M,
+    ];
+    let to_geo = |x: [f64; 2]| [
+        origin[0] + (x[0] / (scale * EARTH_RADIUS_M)).to_degrees(),
+        origin[1] + (x[1] / EARTH_RADIUS_M).to_degrees(),
+    ];
+    let q = config.process_noise.powi(2);
+    let r = config.measurement_noise.powi(2);
+    let mut axes = [Axis::new(0.0, r), Axis::new(0.0, r)];
+    let mut last_time = points[0].time;
+    for point in points.iter_mut() {
+        let dt = match (last_time, point.time) {
+            (Some(from), Some(to)) => ((to.timestamp_millis() - from.timestamp_millis()) as f64 / 1000.0).max(0.0),
+            _ => 1.0,
+        };
+        last_time = point.time.or(last_time);
+        let measured = to_local(point.position());
+        let smoothed = [axes[0].step(measured[0], dt, q, r), axes[1].step(measured[1], dt, q, r)];
+        [point.lon, point.lat] = to_geo(smoothed);
+    }
+}
+
+// 单个方向的状态 [位置, 速度] 及其协方差
+struct Axis {
+    x: [f64; 2],
+    p: [[f64; 2]; 2],
+}
+
+impl Axis {
+    fn new(position: f64, r: f64) -> Self {
+        Axis { x: [position, 0.0], p: [[r, 0.0], [0.0, r]] }
+    }
+
+    fn step(&mut self, measured: f64, dt: f64, q: f64, r: f64) -> f64 {
+        // 预测
+        let [pos, vel] = self.x;
+        self.x = [pos + vel * dt, vel];
+        let [[p00, p01], [p10, p11]] = self.p;
+        let (dt2, dt3, dt4) = (dt * dt, dt * dt * dt, dt * dt * dt * dt);
+        self.p = [
+            [p00 + dt * (p10 + p01) + dt2 * p11 + q * dt4 / 4.0, p01 + dt * p11 + q * dt3 / 2.0],
+            [p10 + dt * p11 + q * dt3 / 2.0, p11 + q * dt2],
+        ];
+        // 更新，观测矩阵为 [1, 0]
+        let [[p00, p01], [p10, p11]] = self.p;
+        let s = p00 + r;
+        let (k0, k1) = (p00 / s, p10 / s);
+        let innovation = measured - self.x[0];
+        self.x = [self.x[0] + k0 * innovation, self.x[1] + k1 * innovation];
+        self.p = [
+            [(1.0 - k0) * p00, (1.0 - k0) * p01],
+            [p10 - k1 * p00, p11 - k1 * p01],
+        ];
+        self.x[0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::DateTime;
+
+    // 沿赤道向东 east 米、在 seconds 秒时的点
+    fn point(east: f64, seconds: i64) -> TrackPoint {
+        let mut point = TrackPoint::from([(east / EARTH_RADIUS_M).to_degrees(), 0.0]);
+        point.time = Some(DateTime::from_millis(seconds * 1000));
+        point
+    }
+
+    fn rejected(points: &[TrackPoint]) -> Vec<Option<RejectReason>> {
+        points.iter().map(|p| p.rejected).collect()
+    }
+
+    #[test]
+    fn rejects_speed_outliers_and_duplicates() {
+        let config = TrackFilterConfig::default();
+        let mut state = FilterState::default();
+        let mut points = [point(0.0, 0), point(10.0, 1), point(5_000.0, 2), point(20.0, 3), point(20.0, 3)];
+        reject_outliers(&config, &mut state, &mut points);
+        assert_eq!(rejected(&points), [None, None, Some(RejectReason::Speed), None, Some(RejectReason::Duplicate)]);
+        assert_eq!(state.last_accepted, Some(points[3]));
+        assert_eq!(state.consecutive_rejected, 0);
+    }
+
+    #[test]
+    fn continues_from_previous_batch() {
+        let config = TrackFilterConfig::default();
+        let mut state = FilterState { last_accepted: Some(point(0.0, 0)), consecutive_rejected: 0 };
+        let mut points = [point(1_000.0, 1)];
+        reject_outliers(&config, &mut state, &mut points);
+        assert_eq!(points[0].rejected, Some(RejectReason::Speed));
+        assert_eq!(state.consecutive_rejected, 1);
+    }
+
+    #[test]
+    fn accepts_new_base_after_consecutive_rejections() {
+        let config = TrackFilterConfig { max_consecutive_rejections: 3, ..TrackFilterConfig::default() };
+        let mut state = FilterState::default();
+        // 第一个点本身是跳点，后面的点都离它很远
+        let mut points = [point(0.0, 0), point(5_000.0, 1), point(5_010.0, 2), point(5_020.0, 3), point(5_030.0, 4)];
+        reject_outliers(&config, &mut state, &mut points);
+        assert_eq!(rejected(&points), [None, Some(RejectReason::Speed), Some(RejectReason::Speed), None, None]);
+    }
+
+    #[test]
+    fn accepts_points_without_time() {
+        let config = TrackFilterConfig::default();
+        let mut state = FilterState::default();
+        let mut far = point(50_000.0, 0);
+        far.time = None;
+        let mut points = [point(0.0, 0), far];
+        reject_outliers(&config, &mut state, &mut points);
+        assert_eq!(rejected(&points), [None, None]);
+    }
+
+    #[test]
+    fn kalman_smooths_jitter() {
+        let config = TrackFilterConfig::default();
+        let mut points: Vec<TrackPoint> = (0..20).map(|i| {
+            let mut p = point(i as f64 * 5.0, i);
+            p.lat = if i % 2 == 0 { 0.00003 } else { -0.00003 };
+            p
+        }).collect();
+        kalman_smooth(&config, &mut points);
+        let last = points.last().unwrap();
+        assert!(last.lat.abs() < 0.00003, "{}", last.lat);
+        // 空列表直接返回
+        kalman_smooth(&config, &mut []);
+    }
+}
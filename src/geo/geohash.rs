@@ -0,0 +1,76 @@
+// Geohash 编码。精度为字符数，7 位约 153m × 153m，8 位约 38m × 19m
+const BASE32: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+pub const MAX_PRECISION: usize = 9;
+
+pub fn encode(position: [f64; 2], precision: usize) -> String {
+    let (mut lon, mut lat) = ([-180.0, 180.0], [-90.0, 90.0]);
+    let mut hash = String::with_capacity(precision);
+    let (mut bits, mut value, mut even) = (0, 0usize, true);
+    while hash.len() < precision {
+        // 偶数位细分经度，奇数位细分纬度
+        let (range, coordinate) = if even { (&mut lon, position[0]) } else { (&mut lat, position[1]) };
+        let mid = (range[0] + range[1]) / 2.0;
+        value <<= 1;
+        if coordinate >= mid {
+            value |= 1;
+            range[0] = mid;
+        } else {
+            range[1] = mid;
+        }
+        even = !even;
+        bits += 1;
+        if bits == 5 {
+            hash.push(BASE32[value] as char);
+            (bits, value) = (0, 0);
+        }
+    }
+    hash
+}
+
+// 单元格范围 [最小经度, 最小纬度, 最大经度, 最大纬度]，含非法字符时返回 None
+pub fn bounds(hash: &str) -> Option<[f64; 4]> {
+    let (mut lon, mut lat) = ([-180.0, 180.0], [-90.0, 90.0]);
+    let mut even = true;
+    for c in hash.bytes() {
+        let value = BASE32.iter().position(|b| *b == c)?;
+        for bit in (0..5).rev() {
+            let range = if even { &mut lon } else { &mut lat };
+            let mid = (range[0] + range[1]) / 2.0;
+            if value >> bit & 1 == 1 {
+                range[0] = mid;
+            } else {
+                range[1] = mid;
+            }
+            even = !even;
+        }
+    }
+    Some([lon[0], lat[0], lon[1], lat[1]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_known_cell() {
+        assert_eq!(encode([-5.6, 42.6], 5), "ezs42");
+        assert_eq!(encode([116.397, 39.916], 7).len(), 7);
+    }
+
+    #[test]
+    fn bounds_contain_encoded_point() {
+        let position = [116.397_128, 39.916_527];
+        for precision in 1..=MAX_PRECISION {
+            let [min_lon, min_lat, max_lon, max_lat] = bounds(&encode(position, precision)).unwrap();
+            assert!((min_lon..=max_lon).contains(&position[0]) && (min_lat..=max_lat).contains(&position[1]));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_characters() {
+        // a、i、l、o 不在 geohash 字母表中
+        assert!(bounds("ezs4a").is_none());
+        assert_eq!(bounds(""), Some([-180.0, -90.0, 180.0, 90.0]));
+    }
+}
@@ -0,0 +1,46 @@
+// 航迹几何计算工具。坐标统一为 WGS-84 的 [经度, 纬度]，与 GeoJSON 的轴顺序一致。
+pub mod crs;
+pub mod filter;
+pub mod geohash;
+pub mod simplify;
+
+// WGS-84 平均地球半径（米）
+pub const EARTH_RADIUS_M: f64 = 6_371_008.8;
+
+// 两点之间的大圆距离（米）
+pub fn haversine(a: [f64; 2], b: [f64; 2]) -> f64 {
+    let (lon1, lat1) = (a[0].to_radians(), a[1].to_radians());
+    let (lon2, lat2) = (b[0].to_radians(), b[1].to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = lon2 - lon1;
+    let h = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * h.sqrt().asin()
+}
+
+// 外包矩形 [最小经度, 最小纬度, 最大经度, 最大纬度]
+pub fn bounding_box(coordinates: &[[f64; 2]]) -> Option<[f64; 4]> {
+    let first = coordinates.first()?;
+    Some(coordinates.iter().fold([first[0], first[1], first[0], first[1]], |b, c| {
+        [b[0].min(c[0]), b[1].min(c[1]), b[2].max(c[0]), b[3].max(c[1])]
+    }))
+}
+
+// 射线法判断点是否在多边形内，第一个环为外边界，其余环为洞。
+// 按经纬度平面计算，适用于风场范围内的小区域，不处理跨越 180° 经线的多边形
+pub fn point_in_polygon(position: [f64; 2], rings: &[Vec<[f64; 2]>]) -> bool {
+    let in_ring = |ring: &[[f64; 2]]| {
+        let mut inside = false;
+        for (a, b) in ring.iter().zip(ring.iter().cycle().skip(1)) {
+            if (a[1] > position[1]) != (b[1] > position[1])
+                && position[0] < a[0] + (position[1] - a[1]) * (b[0] - a[0]) / (b[1] - a[1])
+            {
+                inside = !inside;
+            }
+        }
+        inside
+    };
+    match rings.split_first() {
+        Some((outer, holes)) => in_ring(outer) && !holes.iter().any(|hole| in_ring(hole)),
+        None => false,
+    }
+}
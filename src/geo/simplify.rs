@@ -0,0 +1,176 @@
+// 航迹抽稀。两种算法都返回保留点的下标（升序，首末点总是保留），调用方据此挑出完整的点数据。
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use crate::geo::{haversine, EARTH_RADIUS_M};
+
+// 从 a 指向 b 的初始方位角（弧度）
+fn bearing(a: [f64; 2], b: [f64; 2]) -> f64 {
+    let (lon1, lat1) = (a[0].to_radians(), a[1].to_radians());
+    let (lon2, lat2) = (b[0].to_radians(), b[1].to_radians());
+    let y = (lon2 - lon1).sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * (lon2 - lon1).cos();
+    y.atan2(x)
+}
+
+// 点 p 到大圆线段 a-b 的距离（米）；垂足落在线段外时取到较近端点的距离
+pub fn segment_distance(p: [f64; 2], a: [f64; 2], b: [f64; 2]) -> f64 {
+    let length = haversine(a, b);
+    let to_a = haversine(a, p);
+    if length == 0.0 {
+        return to_a;
+    }
+    let angular = to_a / EARTH_RADIUS_M;
+    let delta = bearing(a, p) - bearing(a, b);
+    if delta.cos() < 0.0 {
+        return to_a;
+    }
+    let cross = (angular.sin() * delta.sin()).asin();
+    let along = (angular.cos() / cross.cos()).clamp(-1.0, 1.0).acos() * EARTH_RADIUS_M;
+    if along > length {
+        return haversine(b, p);
+    }
+    cross.abs() * EARTH_RADIUS_M
+}
+
+// Douglas–Peucker：偏离简化线段超过 tolerance 米的点会被保留
+pub fn douglas_peucker(points: &[[f64; 2]], tolerance: f64) -> Vec<usize> {
+    if points.len() < 3 {
+        return (0..points.len()).collect();
+    }
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+    // 用显式栈代替递归，避免长航迹栈溢出
+    let mut stack = vec![(0, points.len() - 1)];
+    while let Some((start, end)) = stack.pop() {
+        let mut farthest = (0, 0.0);
+        for i in start + 1..end {
+            let distance = segment_distance(points[i], points[start], points[end]);
+            if distance > farthest.1 {
+                farthest = (i, distance);
+            }
+        }
+        if farthest.1 > tolerance {
+            keep[farthest.0] = true;
+            stack.push((start, farthest.0));
+            stack.push((farthest.0, end));
+        }
+    }
+    (0..points.len()).filter(|&i| keep[i]).collect()
+}
+
+// 三角形面积（平方米），在局部等距圆柱投影下计算
+fn triangle_area(a: [f64; 2], b: [f64; 2], c: [f64; 2]) -> f64 {
+    let scale = b[1].to_radians().cos();
+    let project = |p: [f64; 2]| [p[0].to_radians() * scale * EARTH_RADIUS_M, p[1].to_radians() * EARTH_RADIUS_M];
+    let (a, b, c) = (project(a), project(b), project(c));
+    ((b[0] - a[0]) * (c[1] - a[1]) - (c[0] - a[0]) * (b[1] - a[1])).abs() / 2.0
+}
+
+#[derive(PartialEq)]
+struct Candidate {
+    area: f64,
+    index: usize,
+}
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    // BinaryHeap 是大顶堆，反转比较使面积最小的点先出堆
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.area.total_cmp(&self.area).then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Visvalingam–Whyatt：不断移除有效面积最小的点，直到剩下 target 个点
+pub fn visvalingam(points: &[[f64; 2]], target: usize) -> Vec<usize> {
+    let target = target.max(2);
+    if points.len() <= target {
+        return (0..points.len()).collect();
+    }
+    let mut prev: Vec<usize> = (0..points.len()).map(|i| i.saturating_sub(1)).collect();
+    let mut next: Vec<usize> = (0..points.len()).map(|i| i + 1).collect();
+    let mut area = vec![f64::INFINITY; points.len()];
+    let mut heap = BinaryHeap::new();
+    for i in 1..points.len() - 1 {
+        area[i] = triangle_area(points[i - 1], points[i], points[i + 1]);
+        heap.push(Candidate { area: area[i], index: i });
+    }
+
+    let mut removed = vec![false; points.len()];
+    let mut remaining = points.len();
+    let mut last_area = 0.0_f64;
+    while remaining > target {
+        let Some(Candidate { area: candidate_area, index }) = heap.pop() else {
+            break;
+        };
+        // 堆中过期的条目（点已删除或面积已更新）直接跳过
+        if removed[index] || candidate_area != area[index] {
+            continue;
+        }
+        removed[index] = true;
+        remaining -= 1;
+        last_area = last_area.max(candidate_area);
+        let (p, n) = (prev[index], next[index]);
+        next[p] = n;
+        prev[n] = p;
+        // 相邻点的有效面积不小于刚删除的点，保证删除顺序单调
+        for neighbour in [p, n] {
+            if neighbour == 0 || neighbour == points.len() - 1 {
+                continue;
+            }
+            let updated = triangle_area(points[prev[neighbour]], points[neighbour], points[next[neighbour]]).max(last_area);
+            area[neighbour] = updated;
+            heap.push(Candidate { area: updated, index: neighbour });
+        }
+    }
+    (0..points.len()).filter(|&i| !removed[i]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 经度每 0.001 度一个点，纬度先直线升到 0.01 度（约 1.1 km）再直线回落，第 5 个点为折点
+    fn spiked_line() -> Vec<[f64; 2]> {
+        (0..11).map(|i: i32| [i as f64 * 0.001, 0.002 * (5 - (i - 5).abs()) as f64]).collect()
+    }
+
+    #[test]
+    fn segment_distance_measures_perpendicular_offset() {
+        let distance = segment_distance([0.005, 0.001], [0.0, 0.0], [0.01, 0.0]);
+        assert!((distance - 111.2).abs() < 0.5, "{}", distance);
+        // 垂足在线段外时取到端点的距离
+        let beyond = segment_distance([0.02, 0.0], [0.0, 0.0], [0.01, 0.0]);
+        assert!((beyond - haversine([0.02, 0.0], [0.01, 0.0])).abs() < 1e-6);
+    }
+
+    #[test]
+    fn douglas_peucker_drops_collinear_points() {
+        let line: Vec<[f64; 2]> = (0..20).map(|i| [i as f64 * 0.001, 0.0]).collect();
+        assert_eq!(douglas_peucker(&line, 1.0), vec![0, 19]);
+    }
+
+    #[test]
+    fn douglas_peucker_keeps_spike() {
+        assert_eq!(douglas_peucker(&spiked_line(), 10.0), vec![0, 5, 10]);
+        // 容差大于偏离距离时尖点也被去掉
+        assert_eq!(douglas_peucker(&spiked_line(), 2_000.0), vec![0, 10]);
+        assert_eq!(douglas_peucker(&spiked_line()[..2], 10.0), vec![0, 1]);
+    }
+
+    #[test]
+    fn visvalingam_reduces_to_target() {
+        let line = spiked_line();
+        let kept = visvalingam(&line, 3);
+        assert_eq!(kept, vec![0, 5, 10]);
+        assert_eq!(visvalingam(&line, 0), vec![0, 10]);
+        assert_eq!(visvalingam(&line, 20), (0..line.len()).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,85 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use crate::import::{valid_position, ImportedPoint, ParsedTrack};
+
+// 第一行为表头，按列名识别经纬度、高度和时间列，支持逗号或分号分隔
+pub fn parse(content: &str) -> Result<Vec<ParsedTrack>, String> {
+    let mut lines = content.lines().filter(|l| !l.trim().is_empty());
+    let header = lines.next().ok_or("Empty CSV file")?;
+    let separator = if header.contains(';') && !header.contains(',') { ';' } else { ',' };
+    let columns: Vec<String> = header.split(separator).map(|c| c.trim().trim_matches('"').to_ascii_lowercase()).collect();
+    let find = |names: &[&str]| columns.iter().position(|c| names.contains(&c.as_str()));
+
+    let lon_col = find(&["lon", "lng", "long", "longitude", "x"]).ok_or("Missing longitude column")?;
+    let lat_col = find(&["lat", "latitude", "y"]).ok_or("Missing latitude column")?;
+    let alt_col = find(&["alt", "altitude", "ele", "elevation", "z"]);
+    let time_col = find(&["time", "timestamp", "datetime", "date_time"]);
+
+    let mut points = Vec::new();
+    for (i, line) in lines.enumerate() {
+        let row: Vec<&str> = line.split(separator).map(|v| v.trim().trim_matches('"')).collect();
+        let field = |col: usize| row.get(col).copied().filter(|v| !v.is_empty());
+        let lon = field(lon_col).and_then(|v| v.parse::<f64>().ok());
+        let lat = field(lat_col).and_then(|v| v.parse::<f64>().ok());
+        let (Some(lon), Some(lat)) = (lon, lat) else {
+            return Err(format!("Invalid coordinates on row {}", i + 2));
+        };
+        if !valid_position(lon, lat) {
+            return Err(format!("Point out of range on row {}: {}, {}", i + 2, lon, lat));
+        }
+        points.push(ImportedPoint {
+            lon,
+            lat,
+            altitude: alt_col.and_then(field).and_then(|v| v.parse().ok()),
+            time: time_col.and_then(field).and_then(parse_time),
+        });
+    }
+    Ok(vec![ParsedTrack { name: None, points }])
+}
+
+// RFC 3339、"YYYY-MM-DD HH:MM:SS" 或 Unix 时间戳（秒/毫秒）
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(value) {
+        return Some(t.with_timezone(&Utc));
+    }
+    if let Ok(t) = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f") {
+        return Some(t.and_utc());
+    }
+    let number: i64 = value.parse().ok()?;
+    if number > 100_000_000_000 {
+        DateTime::from_timestamp_millis(number)
+    } else {
+        DateTime::from_timestamp(number, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_aliases_and_semicolons() {
+        let content = "\"Latitude\";\"Longitude\";ele;timestamp\n39.9;116.4;50.5;1700000000000\n\n39.91;116.41;;2023-11-14 22:13:21\n";
+        let tracks = parse(content).unwrap();
+        let points = &tracks[0].points;
+        assert_eq!(points.len(), 2);
+        assert_eq!((points[0].lon, points[0].lat, points[0].altitude), (116.4, 39.9, Some(50.5)));
+        assert_eq!(points[0].time.unwrap().timestamp(), 1_700_000_000);
+        assert_eq!(points[1].altitude, None);
+        assert_eq!(points[1].time.unwrap().timestamp(), 1_700_000_001);
+    }
+
+    #[test]
+    fn parses_time_formats() {
+        assert_eq!(parse_time("2023-11-14T22:13:20+08:00").unwrap().timestamp(), 1_699_971_200);
+        assert_eq!(parse_time("1700000000").unwrap().timestamp(), 1_700_000_000);
+        assert!(parse_time("yesterday").is_none());
+    }
+
+    #[test]
+    fn reports_bad_rows() {
+        assert_eq!(parse("time,alt\n1,2").unwrap_err(), "Missing longitude column");
+        assert_eq!(parse("lon,lat\n116.4,abc").unwrap_err(), "Invalid coordinates on row 2");
+        assert!(parse("lon,lat\n200,39.9").unwrap_err().starts_with("Point out of range on row 2"));
+        assert!(parse("").is_err());
+    }
+}
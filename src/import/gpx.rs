@@ -0,0 +1,73 @@
+use chrono::{DateTime, Utc};
+use crate::import::{valid_position, ImportedPoint, ParsedTrack};
+
+// 每个 trk 生成一条航迹（多个 trkseg 合并），没有 trk 时使用 rte
+pub fn parse(content: &str) -> Result<Vec<ParsedTrack>, String> {
+    let document = roxmltree::Document::parse(content).map_err(|e| format!("Invalid GPX: {}", e))?;
+    let root = document.root_element();
+    let mut tracks = Vec::new();
+    for container in root.children().filter(|n| n.has_tag_name("trk") || n.has_tag_name("rte")) {
+        let mut track = ParsedTrack {
+            name: child_text(container, "name"),
+            points: Vec::new(),
+        };
+        for point in container.descendants().filter(|n| n.has_tag_name("trkpt") || n.has_tag_name("rtept")) {
+            let lat = point.attribute("lat").and_then(|v| v.parse::<f64>().ok());
+            let lon = point.attribute("lon").and_then(|v| v.parse::<f64>().ok());
+            let (Some(lat), Some(lon)) = (lat, lon) else {
+                return Err(format!("Point without valid lat/lon at byte {}", point.range().start));
+            };
+            if !valid_position(lon, lat) {
+                return Err(format!("Point out of range: {}, {}", lon, lat));
+            }
+            track.points.push(ImportedPoint {
+                lon,
+                lat,
+                altitude: child_text(point, "ele").and_then(|v| v.parse().ok()),
+                time: child_text(point, "time")
+                    .and_then(|v| DateTime::parse_from_rfc3339(&v).ok())
+                    .map(|t| t.with_timezone(&Utc)),
+            });
+        }
+        tracks.push(track);
+    }
+    Ok(tracks)
+}
+
+fn child_text(node: roxmltree::Node, tag: &str) -> Option<String> {
+    node.children()
+        .find(|n| n.has_tag_name(tag))
+        .and_then(|n| n.text())
+        .map(|t| t.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_segments_and_reads_routes() {
+        let content = r#"<?xml version="1.0"?>
+<gpx version="1.1" xmlns="http://www.topografix.com/GPX/1/1">
+  <trk><name> Blade A </name>
+    <trkseg><trkpt lat="39.9" lon="116.4"><ele>50</ele><time>2023-11-14T22:13:20Z</time></trkpt></trkseg>
+    <trkseg><trkpt lat="39.91" lon="116.41"/></trkseg>
+  </trk>
+  <rte><rtept lat="-33.86" lon="151.21"/></rte>
+</gpx>"#;
+        let tracks = parse(content).unwrap();
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[0].name.as_deref(), Some("Blade A"));
+        assert_eq!(tracks[0].points.len(), 2);
+        assert_eq!(tracks[0].points[0].altitude, Some(50.0));
+        assert_eq!(tracks[0].points[0].time.unwrap().timestamp(), 1_700_000_000);
+        assert_eq!((tracks[1].points[0].lon, tracks[1].points[0].lat), (151.21, -33.86));
+    }
+
+    #[test]
+    fn rejects_invalid_points() {
+        assert!(parse(r#"<gpx><trk><trkseg><trkpt lat="39.9"/></trkseg></trk></gpx>"#).unwrap_err().starts_with("Point without valid lat/lon"));
+        assert!(parse(r#"<gpx><trk><trkseg><trkpt lat="95" lon="0"/></trkseg></trk></gpx>"#).unwrap_err().starts_with("Point out of range"));
+        assert!(parse("<gpx>").unwrap_err().starts_with("Invalid GPX"));
+    }
+}
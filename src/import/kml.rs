@@ -0,0 +1,84 @@
+use chrono::{DateTime, Utc};
+use crate::import::{valid_position, ImportedPoint, ParsedTrack};
+
+// 支持 LineString 和 gx:Track；每个包含线几何的 Placemark 生成一条航迹
+pub fn parse(content: &str) -> Result<Vec<ParsedTrack>, String> {
+    let document = roxmltree::Document::parse(content).map_err(|e| format!("Invalid KML: {}", e))?;
+    let mut tracks = Vec::new();
+    for placemark in document.descendants().filter(|n| n.has_tag_name("Placemark")) {
+        let name = placemark.children()
+            .find(|n| n.has_tag_name("name"))
+            .and_then(|n| n.text())
+            .map(|t| t.trim().to_string());
+        let mut points = Vec::new();
+        for line in placemark.descendants().filter(|n| n.has_tag_name("LineString")) {
+            let coordinates = line.children()
+                .find(|n| n.has_tag_name("coordinates"))
+                .and_then(|n| n.text())
+                .unwrap_or("");
+            for tuple in coordinates.split_whitespace() {
+                points.push(parse_tuple(tuple, ',', None)?);
+            }
+        }
+        // gx:Track 的 when 与 gx:coord 一一对应，坐标以空格分隔
+        for gx_track in placemark.descendants().filter(|n| n.tag_name().name() == "Track") {
+            let times: Vec<Option<DateTime<Utc>>> = gx_track.children()
+                .filter(|n| n.tag_name().name() == "when")
+                .map(|n| n.text().and_then(|t| DateTime::parse_from_rfc3339(t.trim()).ok()).map(|t| t.with_timezone(&Utc)))
+                .collect();
+            let coords = gx_track.children().filter(|n| n.tag_name().name() == "coord");
+            for (i, coord) in coords.enumerate() {
+                points.push(parse_tuple(coord.text().unwrap_or("").trim(), ' ', times.get(i).copied().flatten())?);
+            }
+        }
+        if !points.is_empty() {
+            tracks.push(ParsedTrack { name, points });
+        }
+    }
+    Ok(tracks)
+}
+
+fn parse_tuple(tuple: &str, separator: char, time: Option<DateTime<Utc>>) -> Result<ImportedPoint, String> {
+    let mut parts = tuple.split(separator).filter(|p| !p.is_empty()).map(str::parse::<f64>);
+    let (Some(Ok(lon)), Some(Ok(lat))) = (parts.next(), parts.next()) else {
+        return Err(format!("Invalid coordinate tuple: {}", tuple));
+    };
+    if !valid_position(lon, lat) {
+        return Err(format!("Point out of range: {}, {}", lon, lat));
+    }
+    let altitude = parts.next().and_then(Result::ok);
+    Ok(ImportedPoint { lon, lat, altitude, time })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_line_strings_and_gx_tracks() {
+        let content = r#"<kml xmlns="http://www.opengis.net/kml/2.2" xmlns:gx="http://www.google.com/kml/ext/2.2"><Document>
+  <Placemark><name>Line</name><LineString><coordinates>
+    116.4,39.9,50 116.41,39.91
+  </coordinates></LineString></Placemark>
+  <Placemark><name>Point only</name><Point><coordinates>116.4,39.9</coordinates></Point></Placemark>
+  <Placemark><gx:Track>
+    <when>2023-11-14T22:13:20Z</when><when>bad</when>
+    <gx:coord>116.4 39.9 10</gx:coord><gx:coord>116.41 39.91 11</gx:coord>
+  </gx:Track></Placemark>
+</Document></kml>"#;
+        let tracks = parse(content).unwrap();
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[0].name.as_deref(), Some("Line"));
+        assert_eq!(tracks[0].points[0].altitude, Some(50.0));
+        assert_eq!(tracks[0].points[1].altitude, None);
+        assert_eq!(tracks[1].points[0].time.unwrap().timestamp(), 1_700_000_000);
+        assert!(tracks[1].points[1].time.is_none());
+        assert_eq!(tracks[1].points[1].altitude, Some(11.0));
+    }
+
+    #[test]
+    fn rejects_bad_tuples() {
+        assert_eq!(parse_tuple("116.4", ',', None).unwrap_err(), "Invalid coordinate tuple: 116.4");
+        assert!(parse_tuple("116.4,91", ',', None).unwrap_err().starts_with("Point out of range"));
+    }
+}
@@ -0,0 +1,124 @@
+// 历史航迹文件导入：GPX、KML、NMEA 0183、CSV
+pub mod csv;
+pub mod gpx;
+pub mod kml;
+pub mod nmea;
+
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+
+#[derive(Debug, Clone)]
+pub struct ImportedPoint {
+    pub lon: f64,
+    pub lat: f64,
+    pub altitude: Option<f64>,
+    pub time: Option<DateTime<Utc>>,
+}
+
+// 文件中的一条航迹，一个文件可以包含多条
+#[derive(Debug, Default)]
+pub struct ParsedTrack {
+    pub name: Option<String>,
+    pub points: Vec<ImportedPoint>,
+}
+
+impl ParsedTrack {
+    pub fn start_time(&self) -> Option<DateTime<Utc>> {
+        self.points.iter().filter_map(|p| p.time).min()
+    }
+
+    pub fn end_time(&self) -> Option<DateTime<Utc>> {
+        self.points.iter().filter_map(|p| p.time).max()
+    }
+
+    // 内容哈希，用于识别重复导入；只取决于点位、高度和时间，与文件格式无关
+    pub fn content_hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        for p in &self.points {
+            hasher.update(format!(
+                "{:.7},{:.7},{},{}\n",
+                p.lon,
+                p.lat,
+                p.altitude.map(|a| format!("{:.2}", a)).unwrap_or_default(),
+                p.time.map(|t| t.timestamp_millis()).unwrap_or(0),
+            ));
+        }
+        hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportFormat {
+    Gpx,
+    Kml,
+    Nmea,
+    Csv,
+}
+
+impl ImportFormat {
+    // 优先按扩展名判断，其次按内容特征
+    pub fn detect(file_name: &str, content: &str) -> Option<ImportFormat> {
+        let extension = file_name.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("gpx") => return Some(ImportFormat::Gpx),
+            Some("kml") => return Some(ImportFormat::Kml),
+            Some("nmea") | Some("nma") | Some("log") => return Some(ImportFormat::Nmea),
+            Some("csv") => return Some(ImportFormat::Csv),
+            _ => {}
+        }
+        let head = content.trim_start();
+        if head.starts_with('$') {
+            Some(ImportFormat::Nmea)
+        } else if head.contains("<gpx") {
+            Some(ImportFormat::Gpx)
+        } else if head.contains("<kml") {
+            Some(ImportFormat::Kml)
+        } else if head.lines().next().is_some_and(|l| l.contains(',')) {
+            Some(ImportFormat::Csv)
+        } else {
+            None
+        }
+    }
+}
+
+pub fn parse(file_name: &str, content: &str) -> Result<Vec<ParsedTrack>, String> {
+    let tracks = match ImportFormat::detect(file_name, content) {
+        Some(ImportFormat::Gpx) => gpx::parse(content)?,
+        Some(ImportFormat::Kml) => kml::parse(content)?,
+        Some(ImportFormat::Nmea) => nmea::parse(content)?,
+        Some(ImportFormat::Csv) => csv::parse(content)?,
+        None => return Err("Unrecognized file format".to_string()),
+    };
+    let tracks: Vec<ParsedTrack> = tracks.into_iter().filter(|t| !t.points.is_empty()).collect();
+    if tracks.is_empty() {
+        return Err("No track points found".to_string());
+    }
+    Ok(tracks)
+}
+
+fn valid_position(lon: f64, lat: f64) -> bool {
+    (-180.0..=180.0).contains(&lon) && (-90.0..=90.0).contains(&lat)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_format_by_extension_then_content() {
+        assert_eq!(ImportFormat::detect("flight.GPX", ""), Some(ImportFormat::Gpx));
+        assert_eq!(ImportFormat::detect("flight.log", ""), Some(ImportFormat::Nmea));
+        assert_eq!(ImportFormat::detect("upload", "  $GPGGA,"), Some(ImportFormat::Nmea));
+        assert_eq!(ImportFormat::detect("upload", "<?xml?><kml>"), Some(ImportFormat::Kml));
+        assert_eq!(ImportFormat::detect("upload", "lon,lat\n1,2"), Some(ImportFormat::Csv));
+        assert_eq!(ImportFormat::detect("upload", "hello"), None);
+    }
+
+    #[test]
+    fn content_hash_ignores_format() {
+        let csv = parse("a.csv", "lon,lat,alt,time\n116.4,39.9,50,1700000000\n").unwrap();
+        let gpx = parse("a.gpx", r#"<gpx><trk><trkseg><trkpt lat="39.9" lon="116.4"><ele>50</ele><time>2023-11-14T22:13:20Z</time></trkpt></trkseg></trk></gpx>"#).unwrap();
+        assert_eq!(csv[0].content_hash(), gpx[0].content_hash());
+        assert_eq!(parse("a.gpx", "<gpx><trk/></gpx>").unwrap_err(), "No track points found");
+    }
+}
@@ -0,0 +1,125 @@
+use chrono::{NaiveDate, NaiveTime, TimeZone, Utc};
+use crate::import::{valid_position, ImportedPoint, ParsedTrack};
+
+// 解析 $xxGGA / $xxRMC 语句。GGA 提供高度，RMC 提供日期；同一时刻的两条语句合并为一个点。
+pub fn parse(content: &str) -> Result<Vec<ParsedTrack>, String> {
+    let mut points: Vec<(String, ImportedPoint)> = Vec::new();
+    let mut date: Option<NaiveDate> = None;
+    let mut errors = 0usize;
+
+    for (line_no, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if !line.starts_with('$') {
+            continue;
+        }
+        let Some(body) = checked_body(line) else {
+            errors += 1;
+            tracing::debug!("NMEA 校验失败，第 {} 行", line_no + 1);
+            continue;
+        };
+        let fields: Vec<&str> = body.split(',').collect();
+        let kind = fields[0].get(2..).unwrap_or("");
+        let parsed = match kind {
+            "GGA" if fields.len() > 9 => {
+                // 定位质量为 0 表示无效定位
+                if fields[6] == "0" {
+                    continue;
+                }
+                position(fields[2], fields[3], fields[4], fields[5]).map(|(lon, lat)| {
+                    (fields[1].to_string(), lon, lat, fields[9].parse::<f64>().ok())
+                })
+            }
+            "RMC" if fields.len() > 9 => {
+                if fields[2] != "A" {
+                    continue;
+                }
+                if let Ok(d) = NaiveDate::parse_from_str(fields[9], "%d%m%y") {
+                    date = Some(d);
+                }
+                position(fields[3], fields[4], fields[5], fields[6]).map(|(lon, lat)| (fields[1].to_string(), lon, lat, None))
+            }
+            _ => continue,
+        };
+        let Some((time_of_day, lon, lat, altitude)) = parsed else {
+            errors += 1;
+            continue;
+        };
+        let time = date.zip(NaiveTime::parse_from_str(&time_of_day, "%H%M%S%.f").ok())
+            .map(|(d, t)| Utc.from_utc_datetime(&d.and_time(t)));
+
+        match points.last_mut() {
+            Some((last_time, last)) if *last_time == time_of_day => {
+                last.altitude = last.altitude.or(altitude);
+                last.time = last.time.or(time);
+            }
+            _ => points.push((time_of_day, ImportedPoint { lon, lat, altitude, time })),
+        }
+    }
+    if points.is_empty() && errors > 0 {
+        return Err(format!("No valid NMEA fixes ({} invalid sentences)", errors));
+    }
+    Ok(vec![ParsedTrack { name: None, points: points.into_iter().map(|(_, p)| p).collect() }])
+}
+
+// 校验 *hh 校验和并返回 $ 与 * 之间的内容；没有校验和的语句直接接受
+fn checked_body(line: &str) -> Option<&str> {
+    let line = &line[1..];
+    match line.split_once('*') {
+        Some((body, checksum)) => {
+            let expected = u8::from_str_radix(checksum.get(..2)?, 16).ok()?;
+            let actual = body.bytes().fold(0u8, |acc, b| acc ^ b);
+            (expected == actual).then_some(body)
+        }
+        None => Some(line),
+    }
+}
+
+// ddmm.mmmm / dddmm.mmmm 转换为十进制度
+fn position(lat: &str, ns: &str, lon: &str, ew: &str) -> Option<(f64, f64)> {
+    let lat = degrees(lat, 2)? * if ns == "S" { -1.0 } else { 1.0 };
+    let lon = degrees(lon, 3)? * if ew == "W" { -1.0 } else { 1.0 };
+    valid_position(lon, lat).then_some((lon, lat))
+}
+
+fn degrees(value: &str, degree_digits: usize) -> Option<f64> {
+    let degrees: f64 = value.get(..degree_digits)?.parse().ok()?;
+    let minutes: f64 = value.get(degree_digits..)?.parse().ok()?;
+    Some(degrees + minutes / 60.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 补上校验和
+    fn sentence(body: &str) -> String {
+        format!("${}*{:02X}", body, body.bytes().fold(0u8, |acc, b| acc ^ b))
+    }
+
+    #[test]
+    fn merges_gga_and_rmc_of_the_same_fix() {
+        let content = [
+            sentence("GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W"),
+            sentence("GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,"),
+            sentence("GPGGA,123520,4807.038,S,01131.000,W,1,08,0.9,546.0,M,46.9,M,,"),
+        ].join("\n");
+        let points = &parse(&content).unwrap()[0].points;
+        assert_eq!(points.len(), 2);
+        assert!((points[0].lat - 48.1173).abs() < 1e-9 && (points[0].lon - 11.516_666_666).abs() < 1e-6);
+        assert_eq!(points[0].altitude, Some(545.4));
+        assert_eq!(points[0].time.unwrap().to_rfc3339(), "1994-03-23T12:35:19+00:00");
+        assert!(points[1].lat < 0.0 && points[1].lon < 0.0);
+    }
+
+    #[test]
+    fn skips_invalid_fixes_and_checksums() {
+        let bad_checksum = "$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*00";
+        let no_fix = sentence("GPGGA,123519,4807.038,N,01131.000,E,0,00,,,M,,M,,");
+        let void = sentence("GPRMC,123519,V,4807.038,N,01131.000,E,,,230394,,");
+        let content = [bad_checksum.to_string(), no_fix, void, "not nmea".to_string()].join("\n");
+        assert!(parse(&content).unwrap_err().starts_with("No valid NMEA fixes"));
+        // 没有校验和的语句直接接受
+        let unchecked = "$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,";
+        assert_eq!(parse(unchecked).unwrap()[0].points.len(), 1);
+    }
+}
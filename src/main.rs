@@ -2,20 +2,43 @@ mod model;
 mod controller;
 mod service;
 mod error;
+mod export;
+mod geo;
+mod middleware;
+mod import;
+mod cli;
 
 use axum::{
 	routing::get,
 	Router,
 };
-use mongodb::Client;
+use bson::Document;
+use mongodb::{Client, Collection};
 use mongodb::options::ClientOptions;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tower_http::trace::TraceLayer;
 use tracing::{info, Level};
+use crate::controller::audit::audit_routes;
 use crate::controller::flight::flight_routes;
+use crate::controller::geofence::geofence_routes;
+use crate::controller::metrics::metrics_routes;
+use crate::controller::mission::mission_routes;
 use crate::controller::report::report_routes;
+use crate::controller::share::share_routes;
+use crate::controller::tenant::tenant_routes;
 use crate::controller::track::track_routes;
+use crate::model::point_codec::PointEncoding;
+use crate::middleware::rate_limit::{rate_limit, RateLimitConfig, RateLimiter};
+use crate::service::audit_service::AuditService;
+use crate::service::geofence_service::GeofenceService;
+use crate::service::live_service::LiveHub;
+use crate::service::mission_service::MissionService;
+use crate::service::share_service::ShareService;
 use crate::service::ship_track_service::ShipTrackService;
+use crate::service::soft_delete::spawn_purge_task;
+use crate::model::tenant::Tenant;
+use crate::service::tenant_service::{assign_default_tenant, count_untenanted, TenantService};
 
 #[tokio::main]
 async fn main() {
@@ -27,25 +50,162 @@ async fn main() {
     let client_options = ClientOptions::parse("mongodb://localhost:27017").await.unwrap();
     let client = Client::with_options(client_options).unwrap();
     let db = client.database("shipTracking");
+    // Initialize the AuditService shared by all services that mutate data
+    let audit_collection = db.collection::<model::audit_log::AuditLog>("audit_log");
+    let audit_service = Arc::new(AuditService::new(audit_collection));
+    // Initialize the TenantService holding per-tenant AI prompts and storage quotas
+    let tenant_collection = db.collection::<model::tenant::TenantConfig>("tenantConfigs");
+    let tenant_service = Arc::new(TenantService::new(tenant_collection));
     // Initialize the ShipTrackService with the MongoDB collection
     let ship_track_collection = db.collection::<model::ship_track::ShipTrack>("trackSegments");
-    let ship_track_service = Arc::new(ShipTrackService::new(ship_track_collection));
-    // Initialize the ReportRawService with the MongoDB collection
-    let report_collection = db.collection::<model::report_raw::ReportRaw>("reportRaw");
-    let report_raw_service = Arc::new(service::report_raw_service::ReportRawService::new(report_collection));
+    // Points beyond TRACK_SEGMENT_POINTS (default 10000) per document roll over into trackSegmentPoints
+    let segment_points = std::env::var("TRACK_SEGMENT_POINTS").ok().and_then(|v| v.parse::<usize>().ok()).unwrap_or(10_000);
+    // Full point chunks are stored as arrays or, with TRACK_POINT_ENCODING=packed, as compact binary; reads accept both
+    let point_encoding = std::env::var("TRACK_POINT_ENCODING").ok().and_then(|v| PointEncoding::parse(&v).ok()).unwrap_or(PointEncoding::Array);
+    // Live streaming buffer per subscriber (LIVE_BUFFER_EVENTS, default 1024); slower clients are resynced with a snapshot
+    let live_buffer = std::env::var("LIVE_BUFFER_EVENTS").ok().and_then(|v| v.parse::<usize>().ok()).unwrap_or(1024);
+    let live_hub = Arc::new(LiveHub::new(live_buffer));
+    // Initialize the GeofenceService; every appended point is checked against the tenant's enabled geofences
+    let geofence_service = Arc::new(GeofenceService::new(
+        db.collection::<model::geofence::Geofence>("geofences"),
+        db.collection::<model::geofence::GeofenceAlert>("alerts"),
+        audit_service.clone(),
+        live_hub.clone(),
+    ));
     // Initialize the FlightService with the MongoDB collection
     let flight_collection = db.collection::<model::flight::Flight>("flights");
-    let flight_service = Arc::new(service::flight_service::FlightService::new(flight_collection));
-    
+    let flight_service = Arc::new(service::flight_service::FlightService::new(flight_collection, audit_service.clone()));
+    let ship_track_service = Arc::new(ShipTrackService::new(
+        ship_track_collection,
+        db.collection::<model::track_segment::TrackSegment>("trackSegmentPoints"),
+        audit_service.clone(),
+        live_hub,
+        geofence_service.clone(),
+        tenant_service.clone(),
+        flight_service.clone(),
+        segment_points,
+        point_encoding,
+    ));
+    // 命令行导入历史航迹文件或压缩已有航迹点后直接退出，不启动 HTTP 服务
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("import") {
+        std::process::exit(cli::run_import(ship_track_service, &args[2..]).await);
+    }
+    if args.get(1).map(String::as_str) == Some("pack-points") {
+        std::process::exit(cli::run_pack_points(ship_track_service, &args[2..]).await);
+    }
+    // Documents written before tenant scoping are assigned to DEFAULT_ORG_ID / DEFAULT_WIND_FARM_ID on startup
+    let default_tenant = match (std::env::var("DEFAULT_ORG_ID"), std::env::var("DEFAULT_WIND_FARM_ID")) {
+        (Ok(org_id), Ok(wind_farm_id)) => Some(Tenant { org_id, wind_farm_id }),
+        _ => None,
+    };
+    let untenanted: Vec<Collection<Document>> = ["trackSegments", "trackSegmentPoints", "reportRaw", "flights", "audit_log"]
+        .into_iter()
+        .map(|name| db.collection::<Document>(name))
+        .collect();
+    // 后台把旧的二维坐标点迁移为点对象，迁移期间读取仍兼容两种格式
+    let migrating_service = ship_track_service.clone();
+    let indexing_geofences = geofence_service.clone();
+    tokio::spawn(async move {
+        // 先归属租户，之后的补算和迁移才能被各租户查到
+        match &default_tenant {
+            Some(tenant) => match assign_default_tenant(&untenanted, tenant).await {
+                Ok(0) => {}
+                Ok(count) => info!("已将 {} 个旧文档归入默认租户 {}/{}", count, tenant.org_id, tenant.wind_farm_id),
+                Err(e) => tracing::error!("归属默认租户失败: {:?}", e),
+            },
+            None => match count_untenanted(&untenanted).await {
+                Ok(0) => {}
+                Ok(count) => tracing::warn!("有 {} 个旧文档没有租户，设置 DEFAULT_ORG_ID 和 DEFAULT_WIND_FARM_ID 后重启以完成迁移", count),
+                Err(e) => tracing::error!("统计未归属租户的文档失败: {:?}", e),
+            },
+        }
+        if let Err(e) = migrating_service.ensure_indexes().await {
+            tracing::error!("创建航迹索引失败: {:?}", e);
+        }
+        if let Err(e) = indexing_geofences.ensure_indexes().await {
+            tracing::error!("创建告警索引失败: {:?}", e);
+        }
+        match migrating_service.complete_pending_segments().await {
+            Ok(0) => {}
+            Ok(count) => info!("已补完 {} 条航迹中断的分段写入", count),
+            Err(e) => tracing::error!("补完分段写入失败: {:?}", e),
+        }
+        match migrating_service.migrate_legacy_points().await {
+            Ok(0) => {}
+            Ok(count) => info!("已迁移 {} 条旧格式航迹", count),
+            Err(e) => tracing::error!("迁移旧格式航迹失败: {:?}", e),
+        }
+        match migrating_service.backfill_stats().await {
+            Ok(0) => {}
+            Ok(count) => info!("已为 {} 条航迹补算统计值", count),
+            Err(e) => tracing::error!("补算航迹统计值失败: {:?}", e),
+        }
+        match migrating_service.repair_total_points().await {
+            Ok(0) => {}
+            Ok(count) => info!("已修正 {} 条航迹的 totalPoints", count),
+            Err(e) => tracing::error!("修正 totalPoints 失败: {:?}", e),
+        }
+        match migrating_service.backfill_geometry().await {
+            Ok(0) => {}
+            Ok(count) => info!("已为 {} 个航迹文档和分段补算几何", count),
+            Err(e) => tracing::error!("补算航迹几何失败: {:?}", e),
+        }
+    });
+    // Initialize the ReportRawService with the MongoDB collection
+    let report_collection = db.collection::<model::report_raw::ReportRaw>("reportRaw");
+    let report_raw_service = Arc::new(service::report_raw_service::ReportRawService::new(report_collection, audit_service.clone(), tenant_service.clone()));
+    // Initialize the MissionService; flights and tracks are linked to planned missions for deviation analysis
+    let mission_service = Arc::new(MissionService::new(
+        db.collection::<model::mission::Mission>("missions"),
+        audit_service.clone(),
+        ship_track_service.clone(),
+        flight_service.clone(),
+    ));
+    // Initialize the ShareService for read-only report links signed with SHARE_LINK_SECRET
+    let share_secret = std::env::var("SHARE_LINK_SECRET").unwrap_or_else(|_| {
+        tracing::warn!("SHARE_LINK_SECRET 未设置，使用随机密钥，重启后已发出的分享链接将失效");
+        uuid::Uuid::new_v4().to_string()
+    });
+    let share_service = Arc::new(ShareService::new(
+        db.collection::<model::share_link::ShareLink>("shareLinks"),
+        db.collection::<model::share_link::ShareAccessLog>("shareAccessLog"),
+        report_raw_service.clone(),
+        audit_service.clone(),
+        share_secret.into_bytes(),
+    ));
+
+    // Purge soft-deleted documents once they exceed the retention period (TRASH_RETENTION_DAYS, default 30)
+    let retention_days = std::env::var("TRASH_RETENTION_DAYS").ok().and_then(|v| v.parse::<u64>().ok()).unwrap_or(30);
+    spawn_purge_task(
+        ship_track_service.clone(),
+        report_raw_service.clone(),
+        flight_service.clone(),
+        geofence_service.clone(),
+        mission_service.clone(),
+        std::time::Duration::from_secs(retention_days * 24 * 60 * 60),
+    );
+
+    // Per-client token bucket rate limiting, configured via RATE_LIMIT_* environment variables;
+    // X-Forwarded-For is only honoured from RATE_LIMIT_TRUSTED_PROXIES and X-Api-Key only for RATE_LIMIT_API_KEYS
+    let rate_limiter = Arc::new(RateLimiter::new(RateLimitConfig::from_env()));
+
     // Create the Axum application with the routes and services
     let app = Router::new()
         .route("/", get(|| async { "Hello World!" }))
         .merge(track_routes().with_state(ship_track_service))
         .merge(report_routes().with_state(report_raw_service))
         .merge(flight_routes().with_state(flight_service))
+        .merge(geofence_routes().with_state(geofence_service))
+        .merge(mission_routes().with_state(mission_service))
+        .merge(share_routes().with_state(share_service))
+        .merge(audit_routes().with_state(audit_service))
+        .merge(tenant_routes().with_state(tenant_service))
+        .merge(metrics_routes().with_state(rate_limiter.clone()))
+        .layer(axum::middleware::from_fn_with_state(rate_limiter, rate_limit))
         .layer(
             TraceLayer::new_for_http()
-                .make_span_with(|request: &axum::extract::Request| {
+                .make_span_with(|_request: &axum::extract::Request| {
                     tracing::Span::none()
                     // tracing::info_span!(
                     //     "http_request",
@@ -64,5 +224,6 @@ async fn main() {
     let listener = tokio::net::TcpListener::bind("0.0.0.0:717").await.unwrap();
     let addr = listener.local_addr().unwrap();
     info!("The service is listening http://{}", addr);
-    axum::serve(listener, app).await.unwrap();
+    // 保留客户端地址，供审计日志记录来源 IP
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
 }
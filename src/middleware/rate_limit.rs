@@ -0,0 +1,312 @@
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::request::Parts;
+use axum::http::{header, HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use chrono::Utc;
+use serde_json::json;
+
+// 空闲桶清理间隔
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+// 限流分组：不同接口使用不同的令牌桶参数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RouteGroup {
+    Default,
+    // 航迹追加，地面站高频调用
+    Append,
+    // 会触发 AI 分析的接口，产生外部费用
+    Ai,
+}
+
+impl RouteGroup {
+    pub const ALL: [RouteGroup; 3] = [RouteGroup::Default, RouteGroup::Append, RouteGroup::Ai];
+
+    pub fn classify(method: &Method, path: &str) -> Self {
+        if method == Method::POST && path == "/report_with_image" {
+            RouteGroup::Ai
+        } else if method == Method::PUT && path.starts_with("/append_track/") {
+            RouteGroup::Append
+        } else {
+            RouteGroup::Default
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RouteGroup::Default => "default",
+            RouteGroup::Append => "append",
+            RouteGroup::Ai => "ai",
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            RouteGroup::Default => 0,
+            RouteGroup::Append => 1,
+            RouteGroup::Ai => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LimitConfig {
+    // 桶容量，即允许的突发请求数
+    pub burst: f64,
+    // 每秒补充的令牌数
+    pub per_second: f64,
+    // 每个客户端每天（UTC）的请求上限
+    pub daily_quota: Option<u64>,
+}
+
+impl LimitConfig {
+    // 从环境变量读取，格式为 "突发数,每秒速率[,每日配额]"，如 RATE_LIMIT_AI=5,0.1,200
+    fn from_env(name: &str, default: LimitConfig) -> LimitConfig {
+        let Ok(value) = std::env::var(name) else {
+            return default;
+        };
+        let parts: Vec<&str> = value.split(',').map(str::trim).collect();
+        let parsed = match parts.as_slice() {
+            [burst, rate] => burst.parse().ok().zip(rate.parse().ok()).map(|(b, r)| (b, r, None)),
+            [burst, rate, quota] => burst.parse().ok().zip(rate.parse().ok()).zip(quota.parse().ok()).map(|((b, r), q)| (b, r, Some(q))),
+            _ => None,
+        };
+        match parsed {
+            Some((burst, per_second, daily_quota)) if burst >= 1.0 && per_second > 0.0 => LimitConfig { burst, per_second, daily_quota },
+            _ => {
+                tracing::warn!("{} 格式错误: {}，使用默认值", name, value);
+                default
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    pub default: LimitConfig,
+    pub append: LimitConfig,
+    pub ai: LimitConfig,
+    // 只有来自这些地址的连接才信任 X-Forwarded-For，其他连接按对端地址限流
+    pub trusted_proxies: HashSet<IpAddr>,
+    // 已登记的 API Key，未登记的 X-Api-Key 按客户端 IP 限流
+    pub api_keys: HashSet<String>,
+}
+
+impl RateLimitConfig {
+    pub fn from_env() -> Self {
+        RateLimitConfig {
+            default: LimitConfig::from_env("RATE_LIMIT_DEFAULT", LimitConfig { burst: 100.0, per_second: 50.0, daily_quota: None }),
+            append: LimitConfig::from_env("RATE_LIMIT_APPEND", LimitConfig { burst: 20.0, per_second: 10.0, daily_quota: None }),
+            ai: LimitConfig::from_env("RATE_LIMIT_AI", LimitConfig { burst: 5.0, per_second: 0.1, daily_quota: Some(200) }),
+            trusted_proxies: list_from_env("RATE_LIMIT_TRUSTED_PROXIES")
+                .into_iter()
+                .filter_map(|v| v.parse().map_err(|_| tracing::warn!("RATE_LIMIT_TRUSTED_PROXIES 中的地址无效: {}", v)).ok())
+                .collect(),
+            api_keys: list_from_env("RATE_LIMIT_API_KEYS").into_iter().collect(),
+        }
+    }
+
+    fn for_group(&self, group: RouteGroup) -> LimitConfig {
+        match group {
+            RouteGroup::Default => self.default,
+            RouteGroup::Append => self.append,
+            RouteGroup::Ai => self.ai,
+        }
+    }
+}
+
+// 逗号分隔的环境变量，忽略空项
+fn list_from_env(name: &str) -> Vec<String> {
+    let value = std::env::var(name).unwrap_or_default();
+    value.split(',').map(str::trim).filter(|v| !v.is_empty()).map(str::to_string).collect()
+}
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+    // 当前配额所属的日期及当天已用次数
+    quota_day: String,
+    quota_used: u64,
+}
+
+#[derive(Default)]
+struct GroupMetrics {
+    allowed: AtomicU64,
+    limited: AtomicU64,
+    quota_exceeded: AtomicU64,
+}
+
+pub enum Decision {
+    Allowed,
+    Limited { retry_after: u64 },
+    QuotaExceeded { retry_after: u64 },
+}
+
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Mutex<HashMap<(RouteGroup, String), Bucket>>,
+    last_prune: Mutex<Instant>,
+    metrics: [GroupMetrics; 3],
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+            last_prune: Mutex::new(Instant::now()),
+            metrics: Default::default(),
+        }
+    }
+
+    pub fn check(&self, group: RouteGroup, client: &str) -> Decision {
+        let limit = self.config.for_group(group);
+        let now = Instant::now();
+        let today = Utc::now().format("%Y-%m-%d").to_string();
+        self.prune(now);
+
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry((group, client.to_string())).or_insert_with(|| Bucket {
+            tokens: limit.burst,
+            last_refill: now,
+            quota_day: today.clone(),
+            quota_used: 0,
+        });
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * limit.per_second).min(limit.burst);
+        bucket.last_refill = now;
+        if bucket.quota_day != today {
+            bucket.quota_day = today;
+            bucket.quota_used = 0;
+        }
+
+        let metrics = &self.metrics[group.index()];
+        if limit.daily_quota.is_some_and(|quota| bucket.quota_used >= quota) {
+            metrics.quota_exceeded.fetch_add(1, Ordering::Relaxed);
+            return Decision::QuotaExceeded { retry_after: seconds_until_utc_midnight() };
+        }
+        if bucket.tokens < 1.0 {
+            metrics.limited.fetch_add(1, Ordering::Relaxed);
+            let retry_after = ((1.0 - bucket.tokens) / limit.per_second).ceil().max(1.0) as u64;
+            return Decision::Limited { retry_after };
+        }
+        bucket.tokens -= 1.0;
+        bucket.quota_used += 1;
+        metrics.allowed.fetch_add(1, Ordering::Relaxed);
+        Decision::Allowed
+    }
+
+    // 客户端地址取连接的对端地址；对端是受信任的代理时，从 X-Forwarded-For 右侧跳过受信任的代理，
+    // 第一个不受信任的地址才是客户端。客户端自己写入的 X-Forwarded-For 在最左侧，不会被采用
+    pub fn client_ip(&self, parts: &Parts) -> Option<IpAddr> {
+        let ConnectInfo(peer) = parts.extensions.get::<ConnectInfo<SocketAddr>>()?;
+        let mut client = peer.ip();
+        if !self.config.trusted_proxies.contains(&client) {
+            return Some(client);
+        }
+        let forwarded: Vec<&str> = parts.headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(str::trim)
+            .collect();
+        for entry in forwarded.into_iter().rev() {
+            let Ok(ip) = entry.parse::<IpAddr>() else {
+                break;
+            };
+            client = ip;
+            if !self.config.trusted_proxies.contains(&ip) {
+                break;
+            }
+        }
+        Some(client)
+    }
+
+    // 限流的客户端标识：已登记的 API Key，否则为客户端地址
+    fn client_key(&self, parts: &Parts, ip: Option<IpAddr>) -> String {
+        parts.headers
+            .get("x-api-key")
+            .and_then(|v| v.to_str().ok())
+            .filter(|key| self.config.api_keys.contains(*key))
+            .map(|key| format!("key:{}", key))
+            .or_else(|| ip.map(|ip| format!("ip:{}", ip)))
+            .unwrap_or_else(|| "unknown".to_string())
+    }
+
+    // 删除已补满且当天没有配额消耗的桶，避免内存随客户端数量无限增长
+    fn prune(&self, now: Instant) {
+        let mut last_prune = self.last_prune.lock().unwrap();
+        if now.duration_since(*last_prune) < PRUNE_INTERVAL {
+            return;
+        }
+        *last_prune = now;
+        let mut buckets = self.buckets.lock().unwrap();
+        buckets.retain(|(group, _), bucket| {
+            let limit = self.config.for_group(*group);
+            let refilled = bucket.tokens + now.duration_since(bucket.last_refill).as_secs_f64() * limit.per_second;
+            refilled < limit.burst || (limit.daily_quota.is_some() && bucket.quota_used > 0)
+        });
+    }
+
+    // Prometheus 文本格式的限流指标
+    pub fn render_metrics(&self) -> String {
+        let mut out = String::new();
+        out.push_str("# HELP rate_limit_requests_total Requests seen by the rate limiter.\n");
+        out.push_str("# TYPE rate_limit_requests_total counter\n");
+        for group in RouteGroup::ALL {
+            let metrics = &self.metrics[group.index()];
+            for (outcome, counter) in [
+                ("allowed", &metrics.allowed),
+                ("limited", &metrics.limited),
+                ("quota_exceeded", &metrics.quota_exceeded),
+            ] {
+                out.push_str(&format!(
+                    "rate_limit_requests_total{{group=\"{}\",outcome=\"{}\"}} {}\n",
+                    group.name(), outcome, counter.load(Ordering::Relaxed)
+                ));
+            }
+        }
+        out.push_str("# HELP rate_limit_active_buckets Token buckets currently tracked.\n");
+        out.push_str("# TYPE rate_limit_active_buckets gauge\n");
+        out.push_str(&format!("rate_limit_active_buckets {}\n", self.buckets.lock().unwrap().len()));
+        out
+    }
+}
+
+fn seconds_until_utc_midnight() -> u64 {
+    let now = Utc::now();
+    let tomorrow = (now + chrono::Duration::days(1)).date_naive().and_hms_opt(0, 0, 0).unwrap().and_utc();
+    (tomorrow - now).num_seconds().max(1) as u64
+}
+
+// 限流中间件解析出的客户端地址，审计日志和分享访问记录使用同一个地址
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
+// 限流中间件：客户端优先按已登记的 X-Api-Key 区分，没有时按客户端 IP
+pub async fn rate_limit(State(limiter): State<Arc<RateLimiter>>, request: Request, next: Next) -> Response {
+    let (mut parts, body) = request.into_parts();
+    let group = RouteGroup::classify(&parts.method, parts.uri.path());
+    let ip = limiter.client_ip(&parts);
+    if let Some(ip) = ip {
+        parts.extensions.insert(ClientIp(ip));
+    }
+    let client = limiter.client_key(&parts, ip);
+
+    let (retry_after, message) = match limiter.check(group, &client) {
+        Decision::Allowed => return next.run(Request::from_parts(parts, body)).await,
+        Decision::Limited { retry_after } => (retry_after, "Too many requests"),
+        Decision::QuotaExceeded { retry_after } => (retry_after, "Daily request quota exceeded"),
+    };
+    tracing::warn!("请求被限流, group: {}, client: {}, path: {}", group.name(), client, parts.uri.path());
+    let mut response = (StatusCode::TOO_MANY_REQUESTS, Json(json!({ "error": message }))).into_response();
+    response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+    response
+}
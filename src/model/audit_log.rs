@@ -0,0 +1,122 @@
+use bson::serde_helpers::serialize_bson_datetime_as_rfc3339_string;
+use bson::{DateTime, Document};
+use chrono::Utc;
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::serde_helpers::serialize_object_id_as_hex_string;
+use serde::{Deserialize, Serialize};
+use crate::model::tenant::Tenant;
+
+// 审计日志，只追加不修改
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditLog {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    #[serde(rename = "orgId")]
+    pub org_id: String,
+    #[serde(rename = "windFarmId")]
+    pub wind_farm_id: String,
+    pub actor: String,
+    pub route: String,
+    pub action: String,
+    // 被操作的实体类型，如 "track"、"report"、"flight"
+    pub entity: String,
+    #[serde(rename = "entityId")]
+    pub entity_id: Option<String>,
+    pub before: Option<Document>,
+    pub after: Option<Document>,
+    #[serde(rename = "clientIp")]
+    pub client_ip: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime,
+}
+
+// 发起本次变更的调用方信息，由 controller 从请求中提取后传给 service
+#[derive(Debug, Clone)]
+pub struct AuditContext {
+    pub actor: String,
+    pub route: String,
+    pub client_ip: Option<String>,
+    pub tenant: Tenant,
+}
+
+impl AuditContext {
+    // 后台任务等非 HTTP 请求触发的变更
+    pub fn system(route: &str, tenant: Tenant) -> Self {
+        AuditContext {
+            actor: "system".to_string(),
+            route: route.to_string(),
+            client_ip: None,
+            tenant,
+        }
+    }
+
+    pub fn entry(
+        &self,
+        action: &str,
+        entity: &str,
+        entity_id: Option<String>,
+        before: Option<Document>,
+        after: Option<Document>,
+    ) -> AuditLog {
+        AuditLog {
+            id: ObjectId::new(),
+            org_id: self.tenant.org_id.clone(),
+            wind_farm_id: self.tenant.wind_farm_id.clone(),
+            actor: self.actor.clone(),
+            route: self.route.clone(),
+            action: action.to_string(),
+            entity: entity.to_string(),
+            entity_id,
+            before,
+            after,
+            client_ip: self.client_ip.clone(),
+            created_at: Utc::now().into(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditLogQuery {
+    pub entity: Option<String>,
+    #[serde(rename = "entityId")]
+    pub entity_id: Option<String>,
+    pub actor: Option<String>,
+    pub from: Option<chrono::DateTime<Utc>>,
+    pub to: Option<chrono::DateTime<Utc>>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditLogResponseDto {
+    #[serde(rename = "_id", serialize_with = "serialize_object_id_as_hex_string")]
+    pub id: ObjectId,
+    pub actor: String,
+    pub route: String,
+    pub action: String,
+    pub entity: String,
+    #[serde(rename = "entityId")]
+    pub entity_id: Option<String>,
+    pub before: Option<Document>,
+    pub after: Option<Document>,
+    #[serde(rename = "clientIp")]
+    pub client_ip: Option<String>,
+    #[serde(rename = "createdAt", serialize_with = "serialize_bson_datetime_as_rfc3339_string")]
+    pub created_at: DateTime,
+}
+
+impl From<AuditLog> for AuditLogResponseDto {
+    fn from(log: AuditLog) -> Self {
+        AuditLogResponseDto {
+            id: log.id,
+            actor: log.actor,
+            route: log.route,
+            action: log.action,
+            entity: log.entity,
+            entity_id: log.entity_id,
+            before: log.before,
+            after: log.after,
+            client_ip: log.client_ip,
+            created_at: log.created_at,
+        }
+    }
+}
@@ -1,12 +1,21 @@
 use bson::oid::ObjectId;
-use serde::{Deserialize, Serialize};
 use mongodb::bson::serde_helpers::serialize_object_id_as_hex_string;
+use bson::DateTime;
+use serde::{Deserialize, Serialize};
+use crate::model::tenant::{Tenant, TenantOwned};
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Flight {
     #[serde(rename = "_id")]
     pub id: ObjectId,
+    #[serde(rename = "orgId", default)]
+    pub org_id: String,
+    #[serde(rename = "windFarmId", default)]
+    pub wind_farm_id: String,
     #[serde(rename = "trackId")]
     pub track_id: ObjectId,// 关联的航迹ID
+    // 关联的巡检任务，由 /mission/{id}/flights/{flightId} 维护
+    #[serde(rename = "missionId", default, skip_serializing_if = "Option::is_none")]
+    pub mission_id: Option<ObjectId>,
     #[serde(rename = "batteryCapacity")]
     pub battery_capacity: Vec<f64>, // 电池容量
     #[serde(rename = "estimatedRemainingUsageTime")]
@@ -19,7 +28,22 @@ pub struct Flight {
     pub distance_to_fan: Vec<f64>,
     #[serde(rename = "airPressure")]
     pub air_pressure: Vec<f64>,
+    #[serde(rename = "deletedAt", default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime>,
+    #[serde(rename = "deletedBy", default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
+}
+impl TenantOwned for Flight {
+    fn set_tenant(&mut self, tenant: &Tenant) {
+        self.org_id = tenant.org_id.clone();
+        self.wind_farm_id = tenant.wind_farm_id.clone();
+    }
+
+    fn tenant(&self) -> Tenant {
+        Tenant { org_id: self.org_id.clone(), wind_farm_id: self.wind_farm_id.clone() }
+    }
 }
+#[allow(dead_code)]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FlightDto {
     // #[serde(serialize_with = "serialize_object_id_as_hex_string")]
@@ -36,4 +60,49 @@ pub struct FlightDto {
     pub distance_to_fan: f64,
     
     pub air_pressure: f64,
-}
\ No newline at end of file
+}
+// 飞行记录批量删除的筛选条件，至少需要提供一项
+#[derive(Debug, Deserialize)]
+pub struct FlightBulkDeleteFilter {
+    pub ids: Option<Vec<String>>,
+    #[serde(rename = "trackId")]
+    pub track_id: Option<String>,
+    #[serde(rename = "missionId")]
+    pub mission_id: Option<String>,
+}
+#[derive(Debug, Serialize)]
+pub struct FlightResponseDto {
+    #[serde(rename = "_id", serialize_with = "serialize_object_id_as_hex_string")]
+    pub id: ObjectId,
+    #[serde(rename = "trackId", serialize_with = "serialize_object_id_as_hex_string")]
+    pub track_id: ObjectId,
+    #[serde(rename = "missionId", skip_serializing_if = "Option::is_none")]
+    pub mission_id: Option<String>,
+    #[serde(rename = "batteryCapacity")]
+    pub battery_capacity: Vec<f64>,
+    #[serde(rename = "estimatedRemainingUsageTime")]
+    pub estimated_remaining_usage_time: Vec<f64>,
+    #[serde(rename = "cabinTemperature")]
+    pub cabin_temperature: Vec<f64>,
+    #[serde(rename = "aircraftAltitude")]
+    pub aircraft_altitude: Vec<f64>,
+    #[serde(rename = "distanceToFan")]
+    pub distance_to_fan: Vec<f64>,
+    #[serde(rename = "airPressure")]
+    pub air_pressure: Vec<f64>,
+}
+impl From<Flight> for FlightResponseDto {
+    fn from(flight: Flight) -> Self {
+        FlightResponseDto {
+            id: flight.id,
+            track_id: flight.track_id,
+            mission_id: flight.mission_id.map(|id| id.to_hex()),
+            battery_capacity: flight.battery_capacity,
+            estimated_remaining_usage_time: flight.estimated_remaining_usage_time,
+            cabin_temperature: flight.cabin_temperature,
+            aircraft_altitude: flight.aircraft_altitude,
+            distance_to_fan: flight.distance_to_fan,
+            air_pressure: flight.air_pressure,
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use bson::serde_helpers::serialize_bson_datetime_as_rfc3339_string;
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::serde_helpers::serialize_object_id_as_hex_string;
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+use crate::geo::{haversine, point_in_polygon};
+use crate::model::tenant::{Tenant, TenantOwned};
+
+// 地理围栏类型：禁飞区和风机避让半径禁止进入，作业区禁止离开
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum GeofenceKind {
+    NoFly,
+    TurbineExclusion,
+    WorkArea,
+}
+
+impl GeofenceKind {
+    // 位于围栏内的点是否算作违规
+    pub fn breached_inside(self) -> bool {
+        !matches!(self, GeofenceKind::WorkArea)
+    }
+}
+
+// 围栏形状。多边形沿用 GeoJSON Polygon 的坐标格式，第一个环为外边界，其余为洞
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum GeofenceShape {
+    Polygon { coordinates: Vec<Vec<[f64; 2]>> },
+    Circle {
+        center: [f64; 2],
+        #[serde(rename = "radiusMeters")]
+        radius_meters: f64,
+    },
+}
+
+impl GeofenceShape {
+    pub fn contains(&self, position: [f64; 2]) -> bool {
+        match self {
+            GeofenceShape::Polygon { coordinates } => point_in_polygon(position, coordinates),
+            GeofenceShape::Circle { center, radius_meters } => haversine(*center, position) <= *radius_meters,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let valid_position = |p: &[f64; 2]| (-180.0..=180.0).contains(&p[0]) && (-90.0..=90.0).contains(&p[1]);
+        match self {
+            GeofenceShape::Polygon { coordinates } => {
+                if coordinates.is_empty() {
+                    return Err("Polygon needs at least one ring".to_string());
+                }
+                for ring in coordinates {
+                    if ring.len() < 4 || ring.first() != ring.last() {
+                        return Err("Polygon rings must be closed and have at least 4 positions".to_string());
+                    }
+                    if !ring.iter().all(valid_position) {
+                        return Err("Polygon position out of range".to_string());
+                    }
+                }
+            }
+            GeofenceShape::Circle { center, radius_meters } => {
+                if !valid_position(center) {
+                    return Err("Circle center out of range".to_string());
+                }
+                if !radius_meters.is_finite() || *radius_meters <= 0.0 {
+                    return Err("radiusMeters must be positive".to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Geofence {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    #[serde(rename = "orgId", default)]
+    pub org_id: String,
+    #[serde(rename = "windFarmId", default)]
+    pub wind_farm_id: String,
+    pub name: String,
+    pub kind: GeofenceKind,
+    pub shape: GeofenceShape,
+    // 停用的围栏不参与判定
+    pub enabled: bool,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime,
+    #[serde(rename = "deletedAt", default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime>,
+    #[serde(rename = "deletedBy", default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
+}
+
+impl Geofence {
+    pub fn from_request(dto: GeofenceRequestDto) -> Self {
+        Geofence {
+            id: ObjectId::new(),
+            org_id: String::new(),
+            wind_farm_id: String::new(),
+            name: dto.name,
+            kind: dto.kind,
+            shape: dto.shape,
+            enabled: dto.enabled,
+            created_at: DateTime::now(),
+            deleted_at: None,
+            deleted_by: None,
+        }
+    }
+
+    // 点的位置是否违反本围栏
+    pub fn breached_by(&self, position: [f64; 2]) -> bool {
+        self.shape.contains(position) == self.kind.breached_inside()
+    }
+}
+
+impl TenantOwned for Geofence {
+    fn set_tenant(&mut self, tenant: &Tenant) {
+        self.org_id = tenant.org_id.clone();
+        self.wind_farm_id = tenant.wind_farm_id.clone();
+    }
+
+    fn tenant(&self) -> Tenant {
+        Tenant { org_id: self.org_id.clone(), wind_farm_id: self.wind_farm_id.clone() }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GeofenceRequestDto {
+    pub name: String,
+    pub kind: GeofenceKind,
+    pub shape: GeofenceShape,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl GeofenceRequestDto {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".to_string());
+        }
+        self.shape.validate()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct GeofenceResponseDto {
+    #[serde(rename = "_id", serialize_with = "serialize_object_id_as_hex_string")]
+    pub id: ObjectId,
+    pub name: String,
+    pub kind: GeofenceKind,
+    pub shape: GeofenceShape,
+    pub enabled: bool,
+    #[serde(rename = "createdAt", serialize_with = "serialize_bson_datetime_as_rfc3339_string")]
+    pub created_at: DateTime,
+}
+
+impl From<Geofence> for GeofenceResponseDto {
+    fn from(geofence: Geofence) -> Self {
+        GeofenceResponseDto {
+            id: geofence.id,
+            name: geofence.name,
+            kind: geofence.kind,
+            shape: geofence.shape,
+            enabled: geofence.enabled,
+            created_at: geofence.created_at,
+        }
+    }
+}
+
+// 一次围栏违规：航迹进入禁入区域（或离开作业区）时创建，恢复合规时写入离开时间。
+// 时间取违规点自身的时间，点没有时间时取服务器收到的时间
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeofenceAlert {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    #[serde(rename = "orgId")]
+    pub org_id: String,
+    #[serde(rename = "windFarmId")]
+    pub wind_farm_id: String,
+    #[serde(rename = "geofenceId")]
+    pub geofence_id: ObjectId,
+    #[serde(rename = "geofenceName")]
+    pub geofence_name: String,
+    pub kind: GeofenceKind,
+    #[serde(rename = "trackId")]
+    pub track_id: ObjectId,
+    #[serde(rename = "droneId", default, skip_serializing_if = "Option::is_none")]
+    pub drone_id: Option<String>,
+    #[serde(rename = "enteredAt")]
+    pub entered_at: DateTime,
+    // 第一个违规点在航迹中的下标和位置
+    #[serde(rename = "entryIndex")]
+    pub entry_index: u64,
+    #[serde(rename = "entryPosition")]
+    pub entry_position: [f64; 2],
+    // 仍在违规时为 null，便于按 exitedAt: null 查询进行中的告警
+    #[serde(rename = "exitedAt")]
+    pub exited_at: Option<DateTime>,
+    // 第一个恢复合规的点
+    #[serde(rename = "exitIndex", default, skip_serializing_if = "Option::is_none")]
+    pub exit_index: Option<u64>,
+    #[serde(rename = "exitPosition", default, skip_serializing_if = "Option::is_none")]
+    pub exit_position: Option<[f64; 2]>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GeofenceAlertDto {
+    #[serde(rename = "_id", serialize_with = "serialize_object_id_as_hex_string")]
+    pub id: ObjectId,
+    #[serde(rename = "geofenceId", serialize_with = "serialize_object_id_as_hex_string")]
+    pub geofence_id: ObjectId,
+    #[serde(rename = "geofenceName")]
+    pub geofence_name: String,
+    pub kind: GeofenceKind,
+    #[serde(rename = "trackId", serialize_with = "serialize_object_id_as_hex_string")]
+    pub track_id: ObjectId,
+    #[serde(rename = "droneId", skip_serializing_if = "Option::is_none")]
+    pub drone_id: Option<String>,
+    #[serde(rename = "enteredAt", serialize_with = "serialize_bson_datetime_as_rfc3339_string")]
+    pub entered_at: DateTime,
+    #[serde(rename = "entryIndex")]
+    pub entry_index: u64,
+    #[serde(rename = "entryPosition")]
+    pub entry_position: [f64; 2],
+    #[serde(rename = "exitedAt")]
+    pub exited_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(rename = "exitIndex", skip_serializing_if = "Option::is_none")]
+    pub exit_index: Option<u64>,
+    #[serde(rename = "exitPosition", skip_serializing_if = "Option::is_none")]
+    pub exit_position: Option<[f64; 2]>,
+}
+
+impl From<GeofenceAlert> for GeofenceAlertDto {
+    fn from(alert: GeofenceAlert) -> Self {
+        GeofenceAlertDto {
+            id: alert.id,
+            geofence_id: alert.geofence_id,
+            geofence_name: alert.geofence_name,
+            kind: alert.kind,
+            track_id: alert.track_id,
+            drone_id: alert.drone_id,
+            entered_at: alert.entered_at,
+            entry_index: alert.entry_index,
+            entry_position: alert.entry_position,
+            exited_at: alert.exited_at.map(DateTime::to_chrono),
+            exit_index: alert.exit_index,
+            exit_position: alert.exit_position,
+        }
+    }
+}
+
+// GET /geofence/alerts 的过滤条件
+#[derive(Debug, Default, Deserialize)]
+pub struct AlertQuery {
+    #[serde(rename = "trackId")]
+    pub track_id: Option<String>,
+    #[serde(rename = "geofenceId")]
+    pub geofence_id: Option<String>,
+    // true 只返回仍在违规的告警，false 只返回已结束的
+    pub open: Option<bool>,
+    pub from: Option<chrono::DateTime<chrono::Utc>>,
+    pub to: Option<chrono::DateTime<chrono::Utc>>,
+    pub limit: Option<i64>,
+}
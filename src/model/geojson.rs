@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+
+// GeoJSON (RFC 7946) 结构，坐标顺序为 [经度, 纬度]；也作为航迹文档上 2dsphere 索引的字段
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Geometry {
+    Point { coordinates: [f64; 2] },
+    LineString { coordinates: Vec<[f64; 2]> },
+    // 只用于响应（如热力图单元格），航迹文档上不会出现
+    Polygon { coordinates: Vec<Vec<[f64; 2]>> },
+}
+
+impl Geometry {
+    // LineString 至少需要两个点，单点航迹退化为 Point，空航迹没有几何
+    pub fn from_track(coordinates: Vec<[f64; 2]>) -> Option<Geometry> {
+        match coordinates.len() {
+            0 => None,
+            1 => Some(Geometry::Point { coordinates: coordinates[0] }),
+            _ => Some(Geometry::LineString { coordinates }),
+        }
+    }
+
+    // 存储用的几何。2dsphere 索引不接受退化的线段，因此去掉连续重复的点；
+    // previous 为前一个文档的最后一个点，用来连接相邻分段
+    pub fn from_points(previous: Option<[f64; 2]>, positions: impl IntoIterator<Item = [f64; 2]>) -> Option<Geometry> {
+        Self::from_track(dedup_vertices(previous, positions))
+    }
+}
+
+// 以 previous 为起点去掉连续重复的点，返回结果包含 previous
+pub fn dedup_vertices(previous: Option<[f64; 2]>, positions: impl IntoIterator<Item = [f64; 2]>) -> Vec<[f64; 2]> {
+    let mut vertices: Vec<[f64; 2]> = previous.into_iter().collect();
+    for position in positions {
+        if vertices.last() != Some(&position) {
+            vertices.push(position);
+        }
+    }
+    vertices
+}
+
+#[derive(Debug, Serialize)]
+pub struct Feature<P: Serialize> {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bbox: Option<[f64; 4]>,
+    pub geometry: Option<Geometry>,
+    pub properties: P,
+}
+
+impl<P: Serialize> Feature<P> {
+    pub fn new(id: String, bbox: Option<[f64; 4]>, geometry: Option<Geometry>, properties: P) -> Self {
+        Feature { kind: "Feature", id, bbox, geometry, properties }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FeatureCollection<P: Serialize> {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub features: Vec<Feature<P>>,
+    #[serde(rename = "nextCursor", skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+impl<P: Serialize> FeatureCollection<P> {
+    pub fn new(features: Vec<Feature<P>>, next_cursor: Option<String>) -> Self {
+        FeatureCollection { kind: "FeatureCollection", features, next_cursor }
+    }
+}
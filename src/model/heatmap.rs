@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::geo::geohash;
+use crate::model::geojson::{Feature, FeatureCollection, Geometry};
+use crate::model::track_point::TrackPoint;
+
+const DEFAULT_PRECISION: usize = 7;
+// 一次聚合最多产生的单元格数，超出时应缩小时间范围或降低精度
+const MAX_HEATMAP_CELLS: usize = 200_000;
+// 相邻两点的时间间隔超过此值视为信号中断，这段时间不计入停留时长
+const MAX_DWELL_GAP_MS: i64 = 60_000;
+
+// GET /track/heatmap 的查询参数
+#[derive(Debug, Deserialize)]
+pub struct HeatmapQuery {
+    pub from: Option<chrono::DateTime<chrono::Utc>>,
+    pub to: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(rename = "droneId")]
+    pub drone_id: Option<String>,
+    // geohash 字符数，1..=9，默认 7（约 150m 见方）
+    pub precision: Option<usize>,
+    // count（默认）按点数着色，dwell 按停留时长着色；GeoJSON 中两者都会返回
+    pub metric: Option<String>,
+    // geojson（默认）、png 或 svg
+    pub format: Option<String>,
+    // 图片尺寸（像素），默认 800×600
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+impl HeatmapQuery {
+    pub fn precision(&self) -> Result<usize, String> {
+        let precision = self.precision.unwrap_or(DEFAULT_PRECISION);
+        if !(1..=geohash::MAX_PRECISION).contains(&precision) {
+            return Err(format!("precision must be between 1 and {}", geohash::MAX_PRECISION));
+        }
+        Ok(precision)
+    }
+
+    pub fn metric(&self) -> Result<HeatmapMetric, String> {
+        match self.metric.as_deref().unwrap_or("count") {
+            "count" => Ok(HeatmapMetric::Count),
+            "dwell" => Ok(HeatmapMetric::Dwell),
+            other => Err(format!("Unsupported heatmap metric: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeatmapMetric {
+    Count,
+    Dwell,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HeatmapCellProperties {
+    pub geohash: String,
+    pub count: u64,
+    #[serde(rename = "dwellSeconds")]
+    pub dwell_seconds: f64,
+}
+
+#[derive(Debug, Default)]
+struct HeatmapBin {
+    count: u64,
+    dwell_ms: i64,
+}
+
+// 按 geohash 单元格累加点数和停留时长。点逐个送入，内存占用只与单元格数有关。
+// 停留时长：相邻两个带时间的点之间的间隔计入前一个点所在的单元格；被过滤的点不计入；
+// 指定时间范围时，没有时间或不在范围内的点不计入
+pub struct HeatmapGrid {
+    precision: usize,
+    from: Option<i64>,
+    to: Option<i64>,
+    cells: HashMap<String, HeatmapBin>,
+    previous: Option<(String, i64)>,
+}
+
+impl HeatmapGrid {
+    pub fn new(precision: usize, from: Option<chrono::DateTime<chrono::Utc>>, to: Option<chrono::DateTime<chrono::Utc>>) -> Self {
+        HeatmapGrid {
+            precision,
+            from: from.map(|t| t.timestamp_millis()),
+            to: to.map(|t| t.timestamp_millis()),
+            cells: HashMap::new(),
+            previous: None,
+        }
+    }
+
+    // 开始一条新航迹，上一条航迹的最后一个点不与之相连
+    pub fn start_track(&mut self) {
+        self.previous = None;
+    }
+
+    pub fn extend(&mut self, points: &[TrackPoint]) -> Result<(), String> {
+        for point in points.iter().filter(|p| p.accepted()) {
+            self.add(point)?;
+        }
+        Ok(())
+    }
+
+    fn add(&mut self, point: &TrackPoint) -> Result<(), String> {
+        let time = point.time.map(|t| t.timestamp_millis());
+        let ranged = self.from.is_some() || self.to.is_some();
+        let in_range = time.is_some_and(|t| !(self.from.is_some_and(|from| t < from) || self.to.is_some_and(|to| t > to)));
+        if ranged && !in_range {
+            self.previous = None;
+            return Ok(());
+        }
+        if let (Some((cell, previous)), Some(time)) = (&self.previous, time) {
+            let gap = time - previous;
+            if gap > 0 && gap <= MAX_DWELL_GAP_MS
+                && let Some(bin) = self.cells.get_mut(cell)
+            {
+                bin.dwell_ms += gap;
+            }
+        }
+        let hash = geohash::encode(point.position(), self.precision);
+        if !self.cells.contains_key(&hash) && self.cells.len() >= MAX_HEATMAP_CELLS {
+            return Err(format!("Heatmap exceeds {} cells, narrow the time range or lower the precision", MAX_HEATMAP_CELLS));
+        }
+        self.cells.entry(hash.clone()).or_default().count += 1;
+        if let Some(time) = time {
+            self.previous = Some((hash, time));
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    // 栅格渲染用：每个单元格的范围和所选指标的值
+    pub fn values(&self, metric: HeatmapMetric) -> Vec<([f64; 4], f64)> {
+        self.cells
+            .iter()
+            .filter_map(|(hash, bin)| {
+                let value = match metric {
+                    HeatmapMetric::Count => bin.count as f64,
+                    HeatmapMetric::Dwell => bin.dwell_ms as f64 / 1000.0,
+                };
+                Some((geohash::bounds(hash)?, value))
+            })
+            .collect()
+    }
+
+    // 每个单元格一个矩形 Feature，按 geohash 排序
+    pub fn into_feature_collection(self) -> FeatureCollection<HeatmapCellProperties> {
+        let mut cells: Vec<(String, HeatmapBin)> = self.cells.into_iter().collect();
+        cells.sort_by(|a, b| a.0.cmp(&b.0));
+        let features = cells
+            .into_iter()
+            .filter_map(|(hash, bin)| {
+                let [west, south, east, north] = geohash::bounds(&hash)?;
+                let ring = vec![[west, south], [east, south], [east, north], [west, north], [west, south]];
+                let properties = HeatmapCellProperties { geohash: hash.clone(), count: bin.count, dwell_seconds: bin.dwell_ms as f64 / 1000.0 };
+                Some(Feature::new(hash, Some([west, south, east, north]), Some(Geometry::Polygon { coordinates: vec![ring] }), properties))
+            })
+            .collect();
+        FeatureCollection::new(features, None)
+    }
+}
@@ -0,0 +1,59 @@
+use bson::serde_helpers::serialize_bson_datetime_as_rfc3339_string;
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::serde_helpers::serialize_object_id_as_hex_string;
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+use crate::geo::crs::Crs;
+use crate::model::geofence::GeofenceAlertDto;
+use crate::model::ship_track::ShipTrackResponseDto;
+use crate::model::track_point::TrackPointDto;
+
+// GET /track/live/ws 与 /track/live/sse 的查询参数
+#[derive(Debug, Default, Deserialize)]
+pub struct LiveQuery {
+    // 只订阅一条航迹；不传时订阅本租户的全部活跃航迹
+    #[serde(rename = "trackId")]
+    pub track_id: Option<String>,
+    // 快照中的坐标只返回 [经度, 纬度]
+    #[serde(default)]
+    pub compact: bool,
+    // 订阅全部航迹时，最近多少分钟内有更新的航迹算作活跃，默认 10 分钟
+    #[serde(rename = "activeMinutes")]
+    pub active_minutes: Option<i64>,
+    // 快照和追加事件中坐标的输出坐标系
+    #[serde(default)]
+    pub crs: Crs,
+    // 保留被噪声过滤拒绝的点（带 rejected 标记），默认不发送
+    #[serde(default)]
+    pub raw: bool,
+}
+
+// 推送给订阅者的消息，按 type 字段区分
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum LiveMessage<'a> {
+    // 连接建立、掉队重新同步或第一次见到某条航迹时发送的全量航迹
+    Snapshot { tracks: Vec<ShipTrackResponseDto> },
+    // 一次追加写入的点
+    Append(&'a TrackAppendedDto),
+    // 围栏违规告警的创建或结束
+    Alert(&'a GeofenceAlertDto),
+    // 客户端处理太慢，服务端丢弃了 skipped 条消息，随后会重新发送快照
+    Lagged { skipped: u64 },
+}
+
+// 追加事件。startIndex 为第一个新点在整条航迹中的下标，客户端据此去掉快照中已包含的点
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackAppendedDto {
+    #[serde(rename = "trackId", serialize_with = "serialize_object_id_as_hex_string")]
+    pub track_id: ObjectId,
+    #[serde(rename = "droneId", skip_serializing_if = "Option::is_none")]
+    pub drone_id: Option<String>,
+    #[serde(rename = "startIndex")]
+    pub start_index: u64,
+    #[serde(rename = "totalPoints")]
+    pub total_points: u32,
+    #[serde(rename = "lastUpdate", serialize_with = "serialize_bson_datetime_as_rfc3339_string")]
+    pub last_update: DateTime,
+    pub points: Vec<TrackPointDto>,
+}
@@ -0,0 +1,485 @@
+use bson::serde_helpers::serialize_bson_datetime_as_rfc3339_string;
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::serde_helpers::serialize_object_id_as_hex_string;
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+use crate::geo::crs::Crs;
+use crate::geo::haversine;
+use crate::geo::simplify::segment_distance;
+use crate::model::ship_track::ShipTrack;
+use crate::model::tenant::{Tenant, TenantOwned};
+use crate::model::track_point::TrackPoint;
+
+// 未单独指定半径的航点，航迹进入该距离（米）以内即视为到达
+const DEFAULT_ACCEPTANCE_RADIUS: f64 = 5.0;
+
+// 航点，按数组顺序依次执行
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Waypoint {
+    // WGS-84 [经度, 纬度]
+    pub position: [f64; 2],
+    // 计划高度（米），与航迹点的 altitude 使用同一基准；有高度时按三维距离判断是否到达
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub altitude: Option<f64>,
+    // 到达后需要悬停的秒数
+    #[serde(rename = "holdSeconds", default)]
+    pub hold_seconds: f64,
+    // 在该航点执行的动作，如 "photograph blade A"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    // 覆盖任务的 acceptanceRadius
+    #[serde(rename = "radiusMeters", default, skip_serializing_if = "Option::is_none")]
+    pub radius_meters: Option<f64>,
+}
+
+// 巡检任务计划。航迹和飞行记录通过 missionId 关联到任务
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Mission {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    #[serde(rename = "orgId", default)]
+    pub org_id: String,
+    #[serde(rename = "windFarmId", default)]
+    pub wind_farm_id: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub waypoints: Vec<Waypoint>,
+    #[serde(rename = "acceptanceRadius")]
+    pub acceptance_radius: f64,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime,
+    #[serde(rename = "deletedAt", default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime>,
+    #[serde(rename = "deletedBy", default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
+}
+
+impl TenantOwned for Mission {
+    fn set_tenant(&mut self, tenant: &Tenant) {
+        self.org_id = tenant.org_id.clone();
+        self.wind_farm_id = tenant.wind_farm_id.clone();
+    }
+
+    fn tenant(&self) -> Tenant {
+        Tenant { org_id: self.org_id.clone(), wind_farm_id: self.wind_farm_id.clone() }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MissionRequestDto {
+    pub name: String,
+    pub description: Option<String>,
+    pub waypoints: Vec<Waypoint>,
+    #[serde(rename = "acceptanceRadius", default = "default_acceptance_radius")]
+    pub acceptance_radius: f64,
+    // 航点坐标所在的坐标系，保存前统一转换为 WGS-84
+    #[serde(default)]
+    pub crs: Crs,
+}
+
+fn default_acceptance_radius() -> f64 {
+    DEFAULT_ACCEPTANCE_RADIUS
+}
+
+impl MissionRequestDto {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".to_string());
+        }
+        if self.waypoints.is_empty() {
+            return Err("Mission needs at least one waypoint".to_string());
+        }
+        let positive = |v: f64| v.is_finite() && v > 0.0;
+        if !positive(self.acceptance_radius) {
+            return Err("acceptanceRadius must be positive".to_string());
+        }
+        for (i, waypoint) in self.waypoints.iter().enumerate() {
+            if self.crs.is_wgs84() && !((-180.0..=180.0).contains(&waypoint.position[0]) && (-90.0..=90.0).contains(&waypoint.position[1])) {
+                return Err(format!("Waypoint {} position out of range", i));
+            }
+            if !waypoint.hold_seconds.is_finite() || waypoint.hold_seconds < 0.0 {
+                return Err(format!("Waypoint {} holdSeconds must not be negative", i));
+            }
+            if waypoint.radius_meters.is_some_and(|r| !positive(r)) {
+                return Err(format!("Waypoint {} radiusMeters must be positive", i));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Mission {
+    pub fn from_request(dto: MissionRequestDto) -> Result<Self, String> {
+        let mut waypoints = dto.waypoints;
+        for waypoint in &mut waypoints {
+            waypoint.position = dto.crs.to_wgs84(waypoint.position)?;
+        }
+        Ok(Mission {
+            id: ObjectId::new(),
+            org_id: String::new(),
+            wind_farm_id: String::new(),
+            name: dto.name,
+            description: dto.description,
+            waypoints,
+            acceptance_radius: dto.acceptance_radius,
+            created_at: DateTime::now(),
+            deleted_at: None,
+            deleted_by: None,
+        })
+    }
+
+    fn radius(&self, waypoint: &Waypoint) -> f64 {
+        waypoint.radius_meters.unwrap_or(self.acceptance_radius)
+    }
+
+    // 点是否位于航点的到达半径内；航点和点都有高度时计入高度差
+    fn reaches(&self, waypoint: &Waypoint, point: &TrackPoint) -> bool {
+        let horizontal = haversine(waypoint.position, point.position());
+        let vertical = match (waypoint.altitude, point.altitude) {
+            (Some(planned), Some(actual)) => planned - actual,
+            _ => 0.0,
+        };
+        horizontal.hypot(vertical) <= self.radius(waypoint)
+    }
+
+    // 把实际航迹与计划对比。被噪声过滤拒绝的点不参与计算，下标仍是点在整条航迹中的下标。
+    // 航点按顺序匹配：航迹到达某个后续航点时，中间尚未到达的航点记为跳过；
+    // 在航点上悬停满 holdSeconds 且已进入下一个航点的范围后才切换，便于同一位置不同高度的连续航点。
+    // 偏航距离只统计从到达第一个航点到离开最后一个到达的航点之间的点，不含起降段
+    pub fn analyze(&self, track: &ShipTrack) -> MissionAnalysisDto {
+        let points: Vec<(u64, &TrackPoint)> = track.coordinates
+            .iter()
+            .enumerate()
+            .filter(|(_, point)| point.accepted())
+            .map(|(index, point)| (index as u64, point))
+            .collect();
+        let count = self.waypoints.len();
+        let mut visits: Vec<Option<Visit>> = vec![None; count];
+        let mut skipped = vec![false; count];
+        let mut next = 0;
+        let mut inside: Option<usize> = None;
+        for &(index, point) in &points {
+            if let Some(current) = inside {
+                if let Some(visit) = visits[current].as_mut() {
+                    let held = seconds_between(visit.arrival_time, point.time)
+                        .is_none_or(|seconds| seconds >= self.waypoints[current].hold_seconds);
+                    let advance = held && next < count && self.reaches(&self.waypoints[next], point);
+                    if !advance && self.reaches(&self.waypoints[current], point) {
+                        visit.departure_index = index;
+                        visit.departure_time = point.time;
+                        continue;
+                    }
+                }
+                inside = None;
+            }
+            if let Some(reached) = (next..count).find(|&i| self.reaches(&self.waypoints[i], point)) {
+                skipped[next..reached].iter_mut().for_each(|s| *s = true);
+                visits[reached] = Some(Visit {
+                    arrival_index: index,
+                    arrival_time: point.time,
+                    departure_index: index,
+                    departure_time: point.time,
+                });
+                inside = Some(reached);
+                next = reached + 1;
+            }
+        }
+
+        let mut previous_departure = None;
+        let waypoints: Vec<WaypointVisitDto> = self.waypoints.iter().enumerate().map(|(i, waypoint)| {
+            let closest = points
+                .iter()
+                .map(|(_, point)| haversine(waypoint.position, point.position()))
+                .fold(None, |min: Option<f64>, d| Some(min.map_or(d, |m| m.min(d))));
+            let mut dto = WaypointVisitDto {
+                index: i,
+                action: waypoint.action.clone(),
+                status: if visits[i].is_some() {
+                    WaypointStatus::Visited
+                } else if skipped[i] {
+                    WaypointStatus::Skipped
+                } else {
+                    WaypointStatus::NotReached
+                },
+                closest_meters: closest,
+                hold_seconds: waypoint.hold_seconds,
+                arrival_index: None,
+                departure_index: None,
+                arrival_time: None,
+                departure_time: None,
+                dwell_seconds: None,
+                transit_seconds: None,
+                hold_met: None,
+            };
+            if let Some(visit) = &visits[i] {
+                let dwell = seconds_between(visit.arrival_time, visit.departure_time);
+                dto.arrival_index = Some(visit.arrival_index);
+                dto.departure_index = Some(visit.departure_index);
+                dto.arrival_time = visit.arrival_time.map(DateTime::to_chrono);
+                dto.departure_time = visit.departure_time.map(DateTime::to_chrono);
+                dto.dwell_seconds = dwell;
+                dto.transit_seconds = seconds_between(previous_departure, visit.arrival_time);
+                dto.hold_met = dwell.map(|seconds| seconds >= waypoint.hold_seconds);
+                previous_departure = visit.departure_time;
+            }
+            dto
+        }).collect();
+
+        let first = visits.iter().flatten().next();
+        let last = visits.iter().flatten().last();
+        let (from, to) = match (first, last) {
+            (Some(first), Some(last)) => (first.arrival_index, last.departure_index),
+            _ => (0, u64::MAX),
+        };
+        let cross_track = self.cross_track(points.iter().filter(|(index, _)| (from..=to).contains(index)).copied());
+        let missed_waypoints: Vec<usize> = waypoints.iter().filter(|w| w.status != WaypointStatus::Visited).map(|w| w.index).collect();
+        MissionAnalysisDto {
+            mission_id: self.id,
+            track_id: track.id,
+            waypoint_count: count,
+            visited_count: count - missed_waypoints.len(),
+            completed: missed_waypoints.is_empty(),
+            missed_waypoints,
+            duration_seconds: match (first, last) {
+                (Some(first), Some(last)) => seconds_between(first.arrival_time, last.departure_time),
+                _ => None,
+            },
+            cross_track,
+            waypoints,
+        }
+    }
+
+    // 点到计划航线（相邻航点连成的折线）的最短水平距离
+    fn cross_track<'a>(&self, points: impl Iterator<Item = (u64, &'a TrackPoint)>) -> Option<CrossTrackDto> {
+        let route: Vec<[f64; 2]> = self.waypoints.iter().map(|w| w.position).collect();
+        let (mut samples, mut sum, mut sum_squares) = (0usize, 0.0, 0.0);
+        let (mut max, mut max_index) = (0.0, 0);
+        for (index, point) in points {
+            let position = point.position();
+            let distance = match route.as_slice() {
+                [only] => haversine(*only, position),
+                _ => route.windows(2).map(|leg| segment_distance(position, leg[0], leg[1])).fold(f64::INFINITY, f64::min),
+            };
+            samples += 1;
+            sum += distance;
+            sum_squares += distance * distance;
+            if distance > max || samples == 1 {
+                max = distance;
+                max_index = index;
+            }
+        }
+        if samples == 0 {
+            return None;
+        }
+        Some(CrossTrackDto {
+            samples,
+            mean_meters: sum / samples as f64,
+            rms_meters: (sum_squares / samples as f64).sqrt(),
+            max_meters: max,
+            max_index,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Visit {
+    arrival_index: u64,
+    arrival_time: Option<DateTime>,
+    departure_index: u64,
+    departure_time: Option<DateTime>,
+}
+
+fn seconds_between(from: Option<DateTime>, to: Option<DateTime>) -> Option<f64> {
+    match (from, to) {
+        (Some(from), Some(to)) => Some((to.timestamp_millis() - from.timestamp_millis()) as f64 / 1000.0),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct MissionResponseDto {
+    #[serde(rename = "_id", serialize_with = "serialize_object_id_as_hex_string")]
+    pub id: ObjectId,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub waypoints: Vec<Waypoint>,
+    #[serde(rename = "acceptanceRadius")]
+    pub acceptance_radius: f64,
+    #[serde(rename = "createdAt", serialize_with = "serialize_bson_datetime_as_rfc3339_string")]
+    pub created_at: DateTime,
+}
+
+impl From<Mission> for MissionResponseDto {
+    fn from(mission: Mission) -> Self {
+        MissionResponseDto {
+            id: mission.id,
+            name: mission.name,
+            description: mission.description,
+            waypoints: mission.waypoints,
+            acceptance_radius: mission.acceptance_radius,
+            created_at: mission.created_at,
+        }
+    }
+}
+
+// GET /mission/{id}/analysis 的参数；不传 trackId 时分析所有关联到该任务的航迹
+#[derive(Debug, Default, Deserialize)]
+pub struct AnalysisQuery {
+    #[serde(rename = "trackId")]
+    pub track_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MissionAnalysisDto {
+    #[serde(rename = "missionId", serialize_with = "serialize_object_id_as_hex_string")]
+    pub mission_id: ObjectId,
+    #[serde(rename = "trackId", serialize_with = "serialize_object_id_as_hex_string")]
+    pub track_id: ObjectId,
+    #[serde(rename = "waypointCount")]
+    pub waypoint_count: usize,
+    #[serde(rename = "visitedCount")]
+    pub visited_count: usize,
+    // 所有航点都按顺序到达
+    pub completed: bool,
+    // 跳过或未到达的航点下标
+    #[serde(rename = "missedWaypoints")]
+    pub missed_waypoints: Vec<usize>,
+    // 从到达第一个航点到离开最后一个到达的航点的时长，点没有时间时为 null
+    #[serde(rename = "durationSeconds")]
+    pub duration_seconds: Option<f64>,
+    // 没有可用的点时为 null
+    #[serde(rename = "crossTrack")]
+    pub cross_track: Option<CrossTrackDto>,
+    pub waypoints: Vec<WaypointVisitDto>,
+}
+
+// 偏航距离统计（米），maxIndex 为偏离最大的点在航迹中的下标
+#[derive(Debug, Serialize)]
+pub struct CrossTrackDto {
+    pub samples: usize,
+    #[serde(rename = "meanMeters")]
+    pub mean_meters: f64,
+    #[serde(rename = "rmsMeters")]
+    pub rms_meters: f64,
+    #[serde(rename = "maxMeters")]
+    pub max_meters: f64,
+    #[serde(rename = "maxIndex")]
+    pub max_index: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WaypointStatus {
+    Visited,
+    // 航迹到达了后面的航点，这个航点被跳过
+    Skipped,
+    // 航迹结束前没有到达
+    NotReached,
+}
+
+// 单个航点的执行情况。dwellSeconds 为在到达半径内停留的时长，transitSeconds 为从上一个到达的航点出发到本航点的时长
+#[derive(Debug, Serialize)]
+pub struct WaypointVisitDto {
+    pub index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    pub status: WaypointStatus,
+    // 航迹与航点的最近水平距离，未到达时可以看出差了多少
+    #[serde(rename = "closestMeters")]
+    pub closest_meters: Option<f64>,
+    #[serde(rename = "holdSeconds")]
+    pub hold_seconds: f64,
+    #[serde(rename = "arrivalIndex", skip_serializing_if = "Option::is_none")]
+    pub arrival_index: Option<u64>,
+    #[serde(rename = "departureIndex", skip_serializing_if = "Option::is_none")]
+    pub departure_index: Option<u64>,
+    #[serde(rename = "arrivalTime", skip_serializing_if = "Option::is_none")]
+    pub arrival_time: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(rename = "departureTime", skip_serializing_if = "Option::is_none")]
+    pub departure_time: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(rename = "dwellSeconds", skip_serializing_if = "Option::is_none")]
+    pub dwell_seconds: Option<f64>,
+    #[serde(rename = "transitSeconds", skip_serializing_if = "Option::is_none")]
+    pub transit_seconds: Option<f64>,
+    // 停留时长是否满足计划的悬停时间
+    #[serde(rename = "holdMet", skip_serializing_if = "Option::is_none")]
+    pub hold_met: Option<bool>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::ship_track::ShipTrackRequestDto;
+
+    // 沿赤道每 0.001 度（约 111 m）一个航点
+    fn mission(hold_seconds: f64) -> Mission {
+        let waypoints = (0..3).map(|i| Waypoint {
+            position: [i as f64 * 0.001, 0.0],
+            altitude: None,
+            hold_seconds,
+            action: None,
+            radius_meters: None,
+        }).collect();
+        Mission::from_request(MissionRequestDto {
+            name: "blade inspection".to_string(),
+            description: None,
+            waypoints,
+            acceptance_radius: 5.0,
+            crs: Crs::Wgs84,
+        }).unwrap()
+    }
+
+    // 每个点间隔 1 秒
+    fn track(positions: &[[f64; 2]]) -> ShipTrack {
+        let mut track = ShipTrack::from_request(ShipTrackRequestDto {
+            coordinates: positions.iter().map(|p| (*p).into()).collect(),
+            total_points: 0,
+            drone_id: None,
+            owner: None,
+            crs: Crs::Wgs84,
+        });
+        for (i, point) in track.coordinates.iter_mut().enumerate() {
+            point.time = Some(DateTime::from_millis(i as i64 * 1000));
+        }
+        track
+    }
+
+    #[test]
+    fn completes_when_all_waypoints_visited() {
+        let analysis = mission(0.0).analyze(&track(&[[-0.0005, 0.0], [0.0, 0.0], [0.0005, 0.0], [0.001, 0.0], [0.002, 0.00001]]));
+        assert!(analysis.completed);
+        assert_eq!(analysis.visited_count, 3);
+        assert_eq!(analysis.duration_seconds, Some(3.0));
+        let statuses: Vec<WaypointStatus> = analysis.waypoints.iter().map(|w| w.status).collect();
+        assert_eq!(statuses, [WaypointStatus::Visited; 3]);
+        // 起飞段不计入偏航，最大偏航在最后一个点，约 1.1 m
+        let cross_track = analysis.cross_track.unwrap();
+        assert_eq!(cross_track.samples, 4);
+        assert_eq!(cross_track.max_index, 4);
+        assert!((cross_track.max_meters - 1.11).abs() < 0.01, "{}", cross_track.max_meters);
+    }
+
+    #[test]
+    fn marks_skipped_and_unreached_waypoints() {
+        let analysis = mission(0.0).analyze(&track(&[[0.0, 0.0], [0.0015, 0.001]]));
+        assert!(!analysis.completed);
+        assert_eq!(analysis.missed_waypoints, vec![1, 2]);
+        assert_eq!(analysis.waypoints[1].status, WaypointStatus::NotReached);
+
+        let analysis = mission(0.0).analyze(&track(&[[0.0, 0.0], [0.002, 0.0]]));
+        assert_eq!(analysis.waypoints[1].status, WaypointStatus::Skipped);
+        assert_eq!(analysis.missed_waypoints, vec![1]);
+    }
+
+    #[test]
+    fn ignores_rejected_points_and_checks_hold() {
+        let mut track = track(&[[0.0, 0.0], [0.001, 0.0], [0.001, 0.0], [0.002, 0.0]]);
+        track.coordinates[1].rejected = Some(crate::model::track_point::RejectReason::Speed);
+        let analysis = mission(2.0).analyze(&track);
+        let visit = &analysis.waypoints[1];
+        assert_eq!(visit.arrival_index, Some(2));
+        assert_eq!(visit.hold_met, Some(false));
+    }
+}
@@ -1,3 +1,19 @@
 pub(crate) mod ship_track;
 pub(crate) mod report_raw;
-pub mod flight;
\ No newline at end of file
+pub mod flight;
+pub(crate) mod audit_log;
+pub(crate) mod tenant;
+pub(crate) mod trash;
+pub(crate) mod share_link;
+pub(crate) mod geojson;
+pub(crate) mod track_point;
+pub(crate) mod track_stats;
+pub(crate) mod track_segment;
+pub(crate) mod live;
+pub(crate) mod geofence;
+pub(crate) mod track_filter;
+pub(crate) mod mission;
+pub(crate) mod replay;
+pub(crate) mod heatmap;
+pub(crate) mod track_edit;
+pub(crate) mod point_codec;
@@ -0,0 +1,292 @@
+// 航迹点的紧凑二进制编码，存储在 BSON binary 字段中，读取时由 deserialize_points 透明解码。
+// 各字段按固定精度量化为整数后与同字段的前一个值做差分，再以 zigzag varint 写出；时间使用二阶差分。
+// 量化精度与 MAVLink GLOBAL_POSITION_INT 一致：经纬度 1e-7 度（约 1cm）、高度 mm、航向 0.01 度、速度 cm/s。
+// 编码必须无损：解码结果与原始点不完全相同（例如坐标系转换后的经纬度超出 1e-7 度精度）时不编码，该块保持数组格式。
+//
+// 格式：版本(u8) 点数(varint)，之后每个点：字段标志(u8) Δ经度 Δ纬度 [Δ高度] [ΔΔ时间] [Δ航向] [Δ速度] [定位质量(u8)] [拒绝原因(u8)]
+use mongodb::bson::DateTime;
+use crate::model::track_point::{RejectReason, TrackPoint};
+
+const VERSION: u8 = 1;
+
+const DEGREE_SCALE: f64 = 1e7;
+const ALTITUDE_SCALE: f64 = 1e3;
+const HEADING_SCALE: f64 = 1e2;
+const SPEED_SCALE: f64 = 1e2;
+// 量化后超出这个范围的值无法精确表示为 f64，整块不压缩
+const MAX_QUANTIZED: f64 = (1u64 << 53) as f64;
+
+const HAS_ALTITUDE: u8 = 1;
+const HAS_TIME: u8 = 1 << 1;
+const HAS_HEADING: u8 = 1 << 2;
+const HAS_SPEED: u8 = 1 << 3;
+const HAS_FIX_QUALITY: u8 = 1 << 4;
+const HAS_REJECTED: u8 = 1 << 5;
+
+// 每个字段的上一个值，差分以同一字段上一次出现的值为基准
+#[derive(Default)]
+struct Previous {
+    lon: i64,
+    lat: i64,
+    altitude: i64,
+    time: i64,
+    time_delta: i64,
+    heading: i64,
+    speed: i64,
+}
+
+// 有非有限值、超出量化范围或精度的值时返回 None，调用方应保留数组格式
+pub fn encode(points: &[TrackPoint]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(points.len() * 8 + 8);
+    out.push(VERSION);
+    write_varint(&mut out, points.len() as u64);
+    let mut previous = Previous::default();
+    for point in points {
+        let flags = [
+            (point.altitude.is_some(), HAS_ALTITUDE),
+            (point.time.is_some(), HAS_TIME),
+            (point.heading.is_some(), HAS_HEADING),
+            (point.speed.is_some(), HAS_SPEED),
+            (point.fix_quality.is_some(), HAS_FIX_QUALITY),
+            (point.rejected.is_some(), HAS_REJECTED),
+        ]
+        .iter()
+        .filter(|(present, _)| *present)
+        .fold(0, |flags, (_, bit)| flags | bit);
+        out.push(flags);
+        write_delta(&mut out, &mut previous.lon, quantize(point.lon, DEGREE_SCALE)?);
+        write_delta(&mut out, &mut previous.lat, quantize(point.lat, DEGREE_SCALE)?);
+        if let Some(altitude) = point.altitude {
+            write_delta(&mut out, &mut previous.altitude, quantize(altitude, ALTITUDE_SCALE)?);
+        }
+        if let Some(time) = point.time {
+            let time = time.timestamp_millis();
+            if time.unsigned_abs() >= 1 << 53 {
+                return None;
+            }
+            write_delta(&mut out, &mut previous.time_delta, time - previous.time);
+            previous.time = time;
+        }
+        if let Some(heading) = point.heading {
+            write_delta(&mut out, &mut previous.heading, quantize(heading, HEADING_SCALE)?);
+        }
+        if let Some(speed) = point.speed {
+            write_delta(&mut out, &mut previous.speed, quantize(speed, SPEED_SCALE)?);
+        }
+        if let Some(fix_quality) = point.fix_quality {
+            out.push(fix_quality);
+        }
+        if let Some(rejected) = point.rejected {
+            out.push(match rejected {
+                RejectReason::Speed => 1,
+                RejectReason::Duplicate => 2,
+            });
+        }
+    }
+    // 量化会舍入，只有解码后与原始点完全相同才使用编码结果
+    (decode(&out).ok()? == points).then_some(out)
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Vec<TrackPoint>, String> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let version = reader.byte()?;
+    if version != VERSION {
+        return Err(format!("Unsupported packed point version: {}", version));
+    }
+    let count = reader.varint()? as usize;
+    // 每个点至少 3 个字节，防止损坏的点数导致过量分配
+    let mut points = Vec::with_capacity(count.min(bytes.len() / 3));
+    let mut previous = Previous::default();
+    for _ in 0..count {
+        let flags = reader.byte()?;
+        let lon = reader.delta(&mut previous.lon)? as f64 / DEGREE_SCALE;
+        let lat = reader.delta(&mut previous.lat)? as f64 / DEGREE_SCALE;
+        let mut point = TrackPoint::from([lon, lat]);
+        if flags & HAS_ALTITUDE != 0 {
+            point.altitude = Some(reader.delta(&mut previous.altitude)? as f64 / ALTITUDE_SCALE);
+        }
+        if flags & HAS_TIME != 0 {
+            let delta = reader.delta(&mut previous.time_delta)?;
+            previous.time = previous.time.checked_add(delta).ok_or("Packed point time overflow")?;
+            point.time = Some(DateTime::from_millis(previous.time));
+        }
+        if flags & HAS_HEADING != 0 {
+            point.heading = Some(reader.delta(&mut previous.heading)? as f64 / HEADING_SCALE);
+        }
+        if flags & HAS_SPEED != 0 {
+            point.speed = Some(reader.delta(&mut previous.speed)? as f64 / SPEED_SCALE);
+        }
+        if flags & HAS_FIX_QUALITY != 0 {
+            point.fix_quality = Some(reader.byte()?);
+        }
+        if flags & HAS_REJECTED != 0 {
+            point.rejected = Some(match reader.byte()? {
+                1 => RejectReason::Speed,
+                2 => RejectReason::Duplicate,
+                other => return Err(format!("Unknown packed reject reason: {}", other)),
+            });
+        }
+        points.push(point);
+    }
+    Ok(points)
+}
+
+fn quantize(value: f64, scale: f64) -> Option<i64> {
+    let scaled = (value * scale).round();
+    (scaled.is_finite() && scaled.abs() < MAX_QUANTIZED).then_some(scaled as i64)
+}
+
+fn write_delta(out: &mut Vec<u8>, previous: &mut i64, value: i64) {
+    // 量化值和时间都不超过 2^53，差分不会溢出
+    let delta = value - *previous;
+    *previous = value;
+    write_varint(out, ((delta << 1) ^ (delta >> 63)) as u64);
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, String> {
+        let byte = *self.bytes.get(self.pos).ok_or("Truncated packed points")?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("Invalid varint in packed points".to_string())
+    }
+
+    fn delta(&mut self, previous: &mut i64) -> Result<i64, String> {
+        let zigzag = self.varint()?;
+        let delta = (zigzag >> 1) as i64 ^ -((zigzag & 1) as i64);
+        *previous = previous.wrapping_add(delta);
+        Ok(*previous)
+    }
+}
+
+// 新写入的点块使用的存储编码，由 TRACK_POINT_ENCODING 配置；读取总是兼容两种格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointEncoding {
+    Array,
+    Packed,
+}
+
+impl PointEncoding {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "array" => Ok(PointEncoding::Array),
+            "packed" => Ok(PointEncoding::Packed),
+            other => Err(format!("Unsupported point encoding: {}", other)),
+        }
+    }
+}
+
+// 一个点块压缩前后 coordinates 字段的字节数
+pub struct PackedChunk {
+    pub points: u64,
+    pub array_bytes: u64,
+    pub packed_bytes: u64,
+}
+
+// 单个点块的压缩结果
+pub enum PackOutcome {
+    Packed(PackedChunk),
+    // 已经是压缩编码或已不存在
+    Skipped,
+    // 含有超出编码精度或范围的值，压缩会改变存储的值，保持数组格式
+    Inexact,
+}
+
+// 压缩迁移的汇总，skipped 为已压缩而跳过的块，inexact 为无法无损编码而保持原样的块
+#[derive(Debug, Default)]
+pub struct PackStats {
+    pub tracks: u64,
+    pub segments: u64,
+    pub skipped: u64,
+    pub inexact: u64,
+    pub points: u64,
+    pub array_bytes: u64,
+    pub packed_bytes: u64,
+}
+
+impl PackStats {
+    pub fn add(&mut self, chunk: PackedChunk) {
+        self.points += chunk.points;
+        self.array_bytes += chunk.array_bytes;
+        self.packed_bytes += chunk.packed_bytes;
+    }
+
+    // 压缩后大小占原大小的比例
+    pub fn ratio(&self) -> f64 {
+        if self.array_bytes == 0 { 1.0 } else { self.packed_bytes as f64 / self.array_bytes as f64 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(lon: f64, lat: f64) -> TrackPoint {
+        TrackPoint::from([lon, lat])
+    }
+
+    #[test]
+    fn round_trips_all_fields() {
+        let mut points = vec![point(116.3971234, 39.9087654), point(116.3972001, 39.9088002), point(-73.9856644, 40.7484405)];
+        points[0].altitude = Some(52.125);
+        points[0].time = Some(DateTime::from_millis(1_700_000_000_000));
+        points[0].heading = Some(359.99);
+        points[0].speed = Some(12.34);
+        points[0].fix_quality = Some(4);
+        points[1].time = Some(DateTime::from_millis(1_700_000_001_000));
+        points[1].rejected = Some(RejectReason::Speed);
+        points[2].altitude = Some(-3.5);
+        points[2].time = Some(DateTime::from_millis(1_700_000_001_500));
+        points[2].rejected = Some(RejectReason::Duplicate);
+        let bytes = encode(&points).expect("points are within the encoding precision");
+        assert_eq!(decode(&bytes).unwrap(), points);
+    }
+
+    #[test]
+    fn round_trips_empty_chunk() {
+        let bytes = encode(&[]).unwrap();
+        assert!(decode(&bytes).unwrap().is_empty());
+    }
+
+    #[test]
+    fn refuses_values_beyond_precision() {
+        // 坐标系转换后的经纬度通常超过 1e-7 度精度，编码会改变存储的值
+        assert!(encode(&[point(116.39712345678, 39.9)]).is_none());
+        let mut altitude = point(116.4, 39.9);
+        altitude.altitude = Some(10.0001);
+        assert!(encode(&[altitude]).is_none());
+        assert!(encode(&[point(f64::NAN, 39.9)]).is_none());
+    }
+
+    #[test]
+    fn rejects_corrupt_input() {
+        assert!(decode(&[]).is_err());
+        assert!(decode(&[VERSION + 1, 0]).is_err());
+        let bytes = encode(&[point(116.4, 39.9), point(116.5, 39.8)]).unwrap();
+        assert!(decode(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,187 @@
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::serde_helpers::serialize_object_id_as_hex_string;
+use serde::{Deserialize, Serialize};
+use crate::geo::crs::{Crs, Projection};
+use crate::model::flight::Flight;
+use crate::model::track_point::TrackPoint;
+
+// 帧间隔下限，以及一次回放最多生成的帧数
+const MIN_STEP_MS: i64 = 100;
+pub const MAX_REPLAY_FRAMES: i64 = 100_000;
+
+// GET /track/{id}/replay 的查询参数
+#[derive(Debug, Deserialize)]
+pub struct ReplayQuery {
+    // 回放区间，默认为航迹第一个到最后一个带时间的点；超出航迹时间范围的部分会被截掉
+    pub from: Option<chrono::DateTime<chrono::Utc>>,
+    pub to: Option<chrono::DateTime<chrono::Utc>>,
+    // 帧间隔（秒），默认 1 秒
+    pub step: Option<f64>,
+    // json（默认）返回一个包含全部帧的对象，ndjson 每行一帧
+    pub format: Option<String>,
+    // 合并关联飞行记录的遥测数据
+    #[serde(default)]
+    pub telemetry: bool,
+    #[serde(default)]
+    pub crs: Crs,
+    #[serde(default)]
+    pub raw: bool,
+}
+
+impl ReplayQuery {
+    pub fn step_ms(&self) -> Result<i64, String> {
+        let step = self.step.unwrap_or(1.0);
+        if !step.is_finite() || step * 1000.0 < MIN_STEP_MS as f64 {
+            return Err(format!("step must be at least {} seconds", MIN_STEP_MS as f64 / 1000.0));
+        }
+        Ok((step * 1000.0).round() as i64)
+    }
+}
+
+// 某一时刻的位置。interpolated 为 false 表示该时刻正好有实测点
+#[derive(Debug, Serialize)]
+pub struct ReplayFrameDto {
+    pub time: chrono::DateTime<chrono::Utc>,
+    pub lon: f64,
+    pub lat: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub altitude: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heading: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speed: Option<f64>,
+    pub interpolated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub telemetry: Option<TelemetryDto>,
+}
+
+// 该时刻对应的飞行遥测采样，字段与 Flight 一致，飞行记录中没有数据的字段省略
+#[derive(Debug, Default, Serialize)]
+pub struct TelemetryDto {
+    #[serde(rename = "batteryCapacity", skip_serializing_if = "Option::is_none")]
+    pub battery_capacity: Option<f64>,
+    #[serde(rename = "estimatedRemainingUsageTime", skip_serializing_if = "Option::is_none")]
+    pub estimated_remaining_usage_time: Option<f64>,
+    #[serde(rename = "cabinTemperature", skip_serializing_if = "Option::is_none")]
+    pub cabin_temperature: Option<f64>,
+    #[serde(rename = "aircraftAltitude", skip_serializing_if = "Option::is_none")]
+    pub aircraft_altitude: Option<f64>,
+    #[serde(rename = "distanceToFan", skip_serializing_if = "Option::is_none")]
+    pub distance_to_fan: Option<f64>,
+    #[serde(rename = "airPressure", skip_serializing_if = "Option::is_none")]
+    pub air_pressure: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReplayDto {
+    #[serde(rename = "trackId", serialize_with = "serialize_object_id_as_hex_string")]
+    pub track_id: ObjectId,
+    #[serde(rename = "flightId", skip_serializing_if = "Option::is_none")]
+    pub flight_id: Option<String>,
+    #[serde(rename = "stepSeconds")]
+    pub step_seconds: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crs: Option<String>,
+    pub frames: Vec<ReplayFrameDto>,
+}
+
+// 按固定时间间隔在带时间的点之间线性插值。没有时间或时间不递增的点被跳过；
+// 航向按较小的夹角插值。遥测数组没有时间戳，按采样顺序均匀分布在航迹的时间范围内，取最近的采样
+pub struct Replay {
+    points: Vec<TrackPoint>,
+    telemetry: Option<Flight>,
+    projection: Projection,
+    span: (i64, i64),
+    next: i64,
+    end: i64,
+    step: i64,
+    cursor: usize,
+}
+
+impl Replay {
+    // 区间内没有带时间的点时返回 None
+    pub fn new(points: &[TrackPoint], from: Option<i64>, to: Option<i64>, step: i64, crs: Crs, telemetry: Option<Flight>) -> Option<Self> {
+        let mut timed: Vec<TrackPoint> = Vec::with_capacity(points.len());
+        for point in points {
+            let Some(time) = point.time else { continue };
+            if timed.last().and_then(|last| last.time).is_none_or(|last| time > last) {
+                timed.push(*point);
+            }
+        }
+        let first = timed.first()?.time?.timestamp_millis();
+        let last = timed.last()?.time?.timestamp_millis();
+        let start = from.map_or(first, |from| from.max(first));
+        let end = to.map_or(last, |to| to.min(last));
+        if start > end {
+            return None;
+        }
+        let projection = crs.projection(timed.first().map(TrackPoint::position));
+        Some(Replay { points: timed, telemetry, projection, span: (first, last), next: start, end, step, cursor: 0 })
+    }
+
+    pub fn frame_count(&self) -> i64 {
+        (self.end - self.next) / self.step + 1
+    }
+
+    pub fn crs_name(&self) -> Option<String> {
+        (!self.projection.is_identity()).then(|| self.projection.name())
+    }
+
+    fn telemetry_at(&self, time: i64) -> Option<TelemetryDto> {
+        let flight = self.telemetry.as_ref()?;
+        let (first, last) = self.span;
+        let ratio = if last > first { (time - first) as f64 / (last - first) as f64 } else { 0.0 };
+        let sample = |values: &[f64]| match values.len() {
+            0 => None,
+            n => values.get((ratio * (n - 1) as f64).round() as usize).copied(),
+        };
+        Some(TelemetryDto {
+            battery_capacity: sample(&flight.battery_capacity),
+            estimated_remaining_usage_time: sample(&flight.estimated_remaining_usage_time),
+            cabin_temperature: sample(&flight.cabin_temperature),
+            aircraft_altitude: sample(&flight.aircraft_altitude),
+            distance_to_fan: sample(&flight.distance_to_fan),
+            air_pressure: sample(&flight.air_pressure),
+        })
+    }
+}
+
+impl Iterator for Replay {
+    type Item = ReplayFrameDto;
+
+    fn next(&mut self) -> Option<ReplayFrameDto> {
+        if self.next > self.end {
+            return None;
+        }
+        let time = self.next;
+        self.next += self.step;
+        let millis = |point: &TrackPoint| point.time.map_or(i64::MIN, |t| t.timestamp_millis());
+        while self.cursor + 1 < self.points.len() && millis(&self.points[self.cursor + 1]) <= time {
+            self.cursor += 1;
+        }
+        let a = &self.points[self.cursor];
+        let b = self.points.get(self.cursor + 1).unwrap_or(a);
+        let (ta, tb) = (millis(a), millis(b));
+        let t = if tb > ta { (time - ta) as f64 / (tb - ta) as f64 } else { 0.0 };
+        let lerp = |x: f64, y: f64| x + (y - x) * t;
+        let optional = |x: Option<f64>, y: Option<f64>| match (x, y) {
+            (Some(x), Some(y)) => Some(lerp(x, y)),
+            (x, y) => if t < 0.5 { x } else { y },
+        };
+        let heading = match (a.heading, b.heading) {
+            (Some(x), Some(y)) => Some((x + ((y - x + 540.0) % 360.0 - 180.0) * t).rem_euclid(360.0)),
+            (x, y) => if t < 0.5 { x } else { y },
+        };
+        let [lon, lat] = self.projection.apply([lerp(a.lon, b.lon), lerp(a.lat, b.lat)]);
+        Some(ReplayFrameDto {
+            time: chrono::DateTime::from_timestamp_millis(time).unwrap_or_default(),
+            lon,
+            lat,
+            altitude: optional(a.altitude, b.altitude),
+            heading,
+            speed: optional(a.speed, b.speed),
+            interpolated: t != 0.0,
+            telemetry: self.telemetry_at(time),
+        })
+    }
+}
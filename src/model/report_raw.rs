@@ -3,11 +3,16 @@ use bson::oid::ObjectId;
 use mongodb::bson::serde_helpers::serialize_object_id_as_hex_string;
 use bson::serde_helpers::serialize_bson_datetime_as_rfc3339_string;
 use serde::{Deserialize, Serialize};
+use crate::model::tenant::{Tenant, TenantOwned};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ReportRaw {
     #[serde(rename = "_id")]
     pub id: ObjectId,
+    #[serde(rename = "orgId", default)]
+    pub org_id: String,
+    #[serde(rename = "windFarmId", default)]
+    pub wind_farm_id: String,
     #[serde(rename = "createdAt")]
     pub created_at: DateTime,
     #[serde(rename = "photoPath")]
@@ -21,11 +26,30 @@ pub struct ReportRaw {
     pub covering: f64,
     #[serde(rename = "aiReport")]
     pub ai_report: Option<String>,
+    // 本报告上传图片占用的字节数，用于租户存储配额统计
+    #[serde(rename = "storageBytes", default)]
+    pub storage_bytes: i64,
+    #[serde(rename = "deletedAt", default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime>,
+    #[serde(rename = "deletedBy", default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
+}
+impl TenantOwned for ReportRaw {
+    fn set_tenant(&mut self, tenant: &Tenant) {
+        self.org_id = tenant.org_id.clone();
+        self.wind_farm_id = tenant.wind_farm_id.clone();
+    }
+
+    fn tenant(&self) -> Tenant {
+        Tenant { org_id: self.org_id.clone(), wind_farm_id: self.wind_farm_id.clone() }
+    }
 }
 impl From<ReportRawRequestDto> for ReportRaw {
     fn from(dto: ReportRawRequestDto) -> Self {
         ReportRaw {
             id: ObjectId::new(),
+            org_id: String::new(),
+            wind_farm_id: String::new(),
             created_at: DateTime::now(),
             photo_path: String::new(),
             detail: dto.detail,
@@ -34,6 +58,9 @@ impl From<ReportRawRequestDto> for ReportRaw {
             rust: dto.rust,
             covering: dto.covering,
             ai_report: None,
+            storage_bytes: 0,
+            deleted_at: None,
+            deleted_by: None,
         }
     }
 }
@@ -78,4 +105,14 @@ impl From<ReportRaw> for ReportRawResponseDto {
             ai_report: report_raw.ai_report,
         }
     }
-}
\ No newline at end of file
+}
+// 批量删除的筛选条件，至少需要提供一项
+#[derive(Debug, Deserialize)]
+pub struct ReportBulkDeleteFilter {
+    pub ids: Option<Vec<String>>,
+    #[serde(rename = "createdFrom")]
+    pub created_from: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(rename = "createdTo")]
+    pub created_to: Option<chrono::DateTime<chrono::Utc>>,
+    pub title: Option<String>,
+}
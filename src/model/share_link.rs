@@ -0,0 +1,123 @@
+use bson::serde_helpers::serialize_bson_datetime_as_rfc3339_string;
+use bson::DateTime;
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::serde_helpers::serialize_object_id_as_hex_string;
+use serde::{Deserialize, Serialize, Serializer};
+
+// 对外分享链接，持有令牌的人无需账号即可只读访问指定报告
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ShareLink {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    #[serde(rename = "orgId")]
+    pub org_id: String,
+    #[serde(rename = "windFarmId")]
+    pub wind_farm_id: String,
+    #[serde(rename = "reportIds")]
+    pub report_ids: Vec<ObjectId>,
+    pub note: Option<String>,
+    #[serde(rename = "createdBy")]
+    pub created_by: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime,
+    #[serde(rename = "expiresAt")]
+    pub expires_at: DateTime,
+    #[serde(rename = "revokedAt")]
+    pub revoked_at: Option<DateTime>,
+}
+
+// 分享链接的访问记录
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ShareAccessLog {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    #[serde(rename = "shareId")]
+    pub share_id: ObjectId,
+    // 访问的资源，如 "reports"、"report"、"image"
+    pub resource: String,
+    #[serde(rename = "reportId")]
+    pub report_id: Option<ObjectId>,
+    #[serde(rename = "clientIp")]
+    pub client_ip: Option<String>,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    #[serde(rename = "accessedAt")]
+    pub accessed_at: DateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShareLinkRequestDto {
+    #[serde(rename = "reportIds")]
+    pub report_ids: Vec<String>,
+    #[serde(rename = "expiresInHours")]
+    pub expires_in_hours: Option<i64>,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShareLinkCreatedDto {
+    #[serde(rename = "_id", serialize_with = "serialize_object_id_as_hex_string")]
+    pub id: ObjectId,
+    pub token: String,
+    #[serde(rename = "expiresAt", serialize_with = "serialize_bson_datetime_as_rfc3339_string")]
+    pub expires_at: DateTime,
+}
+
+fn serialize_object_ids_as_hex_strings<S: Serializer>(ids: &[ObjectId], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(ids.iter().map(|id| id.to_hex()))
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShareLinkResponseDto {
+    #[serde(rename = "_id", serialize_with = "serialize_object_id_as_hex_string")]
+    pub id: ObjectId,
+    #[serde(rename = "reportIds", serialize_with = "serialize_object_ids_as_hex_strings")]
+    pub report_ids: Vec<ObjectId>,
+    pub note: Option<String>,
+    #[serde(rename = "createdBy")]
+    pub created_by: String,
+    #[serde(rename = "createdAt", serialize_with = "serialize_bson_datetime_as_rfc3339_string")]
+    pub created_at: DateTime,
+    #[serde(rename = "expiresAt", serialize_with = "serialize_bson_datetime_as_rfc3339_string")]
+    pub expires_at: DateTime,
+    pub revoked: bool,
+}
+
+impl From<ShareLink> for ShareLinkResponseDto {
+    fn from(link: ShareLink) -> Self {
+        ShareLinkResponseDto {
+            id: link.id,
+            report_ids: link.report_ids,
+            note: link.note,
+            created_by: link.created_by,
+            created_at: link.created_at,
+            expires_at: link.expires_at,
+            revoked: link.revoked_at.is_some(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShareAccessLogResponseDto {
+    pub resource: String,
+    #[serde(rename = "reportId")]
+    pub report_id: Option<String>,
+    #[serde(rename = "clientIp")]
+    pub client_ip: Option<String>,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    #[serde(rename = "accessedAt", serialize_with = "serialize_bson_datetime_as_rfc3339_string")]
+    pub accessed_at: DateTime,
+}
+
+impl From<ShareAccessLog> for ShareAccessLogResponseDto {
+    fn from(log: ShareAccessLog) -> Self {
+        ShareAccessLogResponseDto {
+            resource: log.resource,
+            report_id: log.report_id.map(|id| id.to_hex()),
+            client_ip: log.client_ip,
+            user_agent: log.user_agent,
+            accessed_at: log.accessed_at,
+        }
+    }
+}
@@ -1,32 +1,336 @@
 use bson::serde_helpers::serialize_bson_datetime_as_rfc3339_string;
 use mongodb::bson::oid::ObjectId;
 use mongodb::bson::serde_helpers::serialize_object_id_as_hex_string;
-use mongodb::bson::DateTime;
+use mongodb::bson::{doc, DateTime, Document};
 use serde::{Deserialize, Serialize};
+use crate::geo::bounding_box;
+use crate::geo::crs::Crs;
+use crate::geo::filter::{kalman_smooth, reject_outliers};
+use crate::model::track_filter::{FilterState, Smoothing, TrackFilterConfig};
+use crate::geo::simplify::{douglas_peucker, visvalingam};
+use crate::model::geojson::{Feature, Geometry};
+use crate::model::tenant::{Tenant, TenantOwned};
+use crate::model::track_point::{deserialize_points, TrackPoint, TrackPointDto};
+use crate::model::track_segment::SegmentRef;
+use crate::model::track_stats::TrackStats;
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ShipTrack {
     #[serde(rename = "_id")]
     pub id: ObjectId,
+    #[serde(rename = "orgId", default)]
+    pub org_id: String,
+    #[serde(rename = "windFarmId", default)]
+    pub wind_farm_id: String,
     #[serde(rename = "startTime")]
     pub start_time: DateTime,
    #[serde(rename = "lastUpdate")]
     pub last_update: DateTime,
     #[serde(rename = "totalPoints")]
     pub total_points: u32,
-    pub coordinates: Vec<[f64; 2]>,
+    // 旧文档中的点是 [经度, 纬度] 数组，读取时统一转换为 TrackPoint。
+    // 点数超过单文档上限后，这里只保存第 0 段，后续的点在 segments 指向的分段文档中。
+    #[serde(deserialize_with = "deserialize_points")]
+    pub coordinates: Vec<TrackPoint>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub segments: Vec<SegmentRef>,
+    // 第 0 段的线几何，由 service 维护并建立 2dsphere 索引
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub geometry: Option<Geometry>,
+    #[serde(rename = "droneId", default, skip_serializing_if = "Option::is_none")]
+    pub drone_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    // 关联的巡检任务，由 /mission/{id}/tracks/{trackId} 维护
+    #[serde(rename = "missionId", default, skip_serializing_if = "Option::is_none")]
+    pub mission_id: Option<ObjectId>,
+    // 最近一次接受的追加批次序号，以及最近若干批次的记录，用于识别重试
+    #[serde(rename = "lastSeq", default, skip_serializing_if = "Option::is_none")]
+    pub last_seq: Option<u64>,
+    #[serde(rename = "recentBatches", default, skip_serializing_if = "Vec::is_empty")]
+    pub recent_batches: Vec<AppendBatch>,
+    // 缓存的统计值，旧文档没有时在读取统计接口时现算
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<TrackStats>,
+    // 噪声过滤在追加批次之间的状态
+    #[serde(rename = "filterState", default, skip_serializing_if = "Option::is_none")]
+    pub filter_state: Option<FilterState>,
+    // 从文件导入的航迹记录内容哈希，用于识别重复导入
+    #[serde(rename = "contentHash", default, skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+    #[serde(rename = "deletedAt", default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime>,
+    #[serde(rename = "deletedBy", default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
+}
+impl ShipTrack {
+    // POST /track 与文件导入共用的构建方式：服务器生成 _id 和时间戳
+    pub fn from_request(dto: ShipTrackRequestDto) -> Self {
+        let current_time = DateTime::now();
+        ShipTrack {
+            id: ObjectId::new(),
+            org_id: String::new(),
+            wind_farm_id: String::new(),
+            start_time: current_time,
+            last_update: current_time,
+            total_points: dto.coordinates.len() as u32,
+            coordinates: dto.coordinates.into_iter().map(TrackPoint::from).collect(),
+            drone_id: dto.drone_id,
+            owner: dto.owner,
+            mission_id: None,
+            segments: Vec::new(),
+            geometry: None,
+            last_seq: None,
+            recent_batches: Vec::new(),
+            stats: None,
+            filter_state: None,
+            content_hash: None,
+            deleted_at: None,
+            deleted_by: None,
+        }
+    }
+
+    // PUT /track/{id} 的构建方式：_id 和时间由 service 按原航迹确定
+    pub fn from_update(dto: ShipTrackUpdateDto) -> Result<Self, String> {
+        let mission_id = dto.mission_id.as_deref()
+            .map(ObjectId::parse_str)
+            .transpose()
+            .map_err(|e| format!("Invalid missionId: {}", e))?;
+        let mut track = ShipTrack::from_request(ShipTrackRequestDto {
+            coordinates: dto.coordinates,
+            total_points: 0,
+            drone_id: dto.drone_id,
+            owner: dto.owner,
+            crs: dto.crs,
+        });
+        track.mission_id = mission_id;
+        Ok(track)
+    }
+
+    // 审计日志中记录的航迹摘要。整条航迹的快照可能超过 16MB 的文档上限，只记录点数、分段数和批次序号
+    pub fn audit_summary(&self) -> Document {
+        doc! {
+            "id": self.id.to_hex(),
+            "droneId": self.drone_id.clone(),
+            "owner": self.owner.clone(),
+            "missionId": self.mission_id.map(|id| id.to_hex()),
+            "startTime": self.start_time,
+            "lastUpdate": self.last_update,
+            "totalPoints": self.total_points as i64,
+            "segmentCount": self.segments.len() as i64,
+            "lastSeq": self.last_seq.map(|seq| seq as i64),
+        }
+    }
+
+    pub fn computed_stats(&self) -> TrackStats {
+        self.stats.clone().unwrap_or_else(|| TrackStats::compute(&self.coordinates))
+    }
+
+    // 按 simplify 参数抽稀坐标，只影响本次响应，不修改存储的数据。
+    // 统计值在抽稀前固定下来，保证距离等数据仍基于完整航迹。
+    pub fn simplify(&mut self, param: &str) -> Result<SimplificationDto, String> {
+        let original_points = self.coordinates.len();
+        let positions = self.positions();
+        let (algorithm, tolerance_meters, target_points, kept) = match param.strip_suffix("pts") {
+            Some(count) => {
+                let target: usize = count.parse().map_err(|_| format!("Invalid simplify point count: {}", param))?;
+                ("visvalingam", None, Some(target.max(2)), visvalingam(&positions, target))
+            }
+            None => {
+                let tolerance: f64 = param.strip_suffix('m').unwrap_or(param).parse()
+                    .ok()
+                    .filter(|t: &f64| t.is_finite() && *t >= 0.0)
+                    .ok_or_else(|| format!("Invalid simplify tolerance: {}", param))?;
+                ("douglas-peucker", Some(tolerance), None, douglas_peucker(&positions, tolerance))
+            }
+        };
+        self.stats = Some(self.computed_stats());
+        self.coordinates = kept.into_iter().map(|i| self.coordinates[i]).collect();
+        let simplified_points = self.coordinates.len();
+        Ok(SimplificationDto {
+            algorithm,
+            tolerance_meters,
+            target_points,
+            original_points,
+            simplified_points,
+            reduction_ratio: if original_points == 0 { 0.0 } else { 1.0 - simplified_points as f64 / original_points as f64 },
+        })
+    }
+
+    // 写入前按过滤配置判定离群点和重复点，并从头计算过滤状态
+    pub fn reject_noise(&mut self, config: &TrackFilterConfig) {
+        if !config.enabled {
+            self.coordinates.iter_mut().for_each(|point| point.rejected = None);
+            self.filter_state = None;
+            return;
+        }
+        let mut state = FilterState::default();
+        reject_outliers(config, &mut state, &mut self.coordinates);
+        self.filter_state = Some(state);
+    }
+
+    // 读取时去掉被拒绝的点并按配置平滑，只影响本次响应；统计值在处理前固定下来
+    pub fn denoise(&mut self, config: &TrackFilterConfig) {
+        self.stats = Some(self.computed_stats());
+        self.coordinates.retain(|point| point.rejected.is_none());
+        if config.enabled && config.smoothing == Smoothing::Kalman {
+            kalman_smooth(config, &mut self.coordinates);
+        }
+    }
+
+    // 把按 crs 声明的输入坐标转换为 WGS-84
+    pub fn points_to_wgs84(&mut self, crs: Crs) -> Result<(), String> {
+        self.coordinates.iter_mut().try_for_each(|point| point.convert_to_wgs84(crs))
+    }
+
+    // 按输出坐标系转换坐标，只影响本次响应；返回响应中标注的坐标系名称，WGS-84 时为 None。
+    // 统计值在转换前固定下来，UTM 带号由第一个点决定
+    pub fn project_to(&mut self, crs: Crs) -> Option<String> {
+        if crs.is_wgs84() {
+            return None;
+        }
+        self.stats = Some(self.computed_stats());
+        let projection = crs.projection(self.coordinates.first().map(TrackPoint::position));
+        self.coordinates.iter_mut().for_each(|point| point.project(&projection));
+        Some(projection.name())
+    }
+
+    // 点被拆分、合并或裁剪后，按点的时间重新确定起止时间；没有带时间的点时保持不变
+    pub fn refresh_time_range(&mut self) {
+        let stats = self.computed_stats();
+        if let Some(first) = stats.first_point_time {
+            self.start_time = first;
+        }
+        if let Some(last) = stats.last_point_time {
+            self.last_update = last;
+        }
+    }
+
+    // 从本航迹拆出的新航迹，沿用租户、无人机、所有者和任务关联
+    pub fn split_off(&mut self, at: usize) -> ShipTrack {
+        let coordinates = self.coordinates.split_off(at);
+        ShipTrack {
+            id: ObjectId::new(),
+            org_id: self.org_id.clone(),
+            wind_farm_id: self.wind_farm_id.clone(),
+            start_time: self.start_time,
+            last_update: self.last_update,
+            total_points: coordinates.len() as u32,
+            coordinates,
+            segments: Vec::new(),
+            geometry: None,
+            drone_id: self.drone_id.clone(),
+            owner: self.owner.clone(),
+            mission_id: self.mission_id,
+            last_seq: None,
+            recent_batches: Vec::new(),
+            stats: None,
+            filter_state: None,
+            content_hash: None,
+            deleted_at: None,
+            deleted_by: None,
+        }
+    }
+
+    // 只取经纬度，供几何计算和 GeoJSON 输出使用
+    pub fn positions(&self) -> Vec<[f64; 2]> {
+        self.coordinates.iter().map(TrackPoint::position).collect()
+    }
+}
+impl TenantOwned for ShipTrack {
+    fn set_tenant(&mut self, tenant: &Tenant) {
+        self.org_id = tenant.org_id.clone();
+        self.wind_farm_id = tenant.wind_farm_id.clone();
+    }
+
+    fn tenant(&self) -> Tenant {
+        Tenant { org_id: self.org_id.clone(), wind_farm_id: self.wind_farm_id.clone() }
+    }
 }
 // 新增：用于更新操作的请求体结构体
 #[derive(Debug, Deserialize)]
 pub struct UpdateShipTrackPayload {
-    #[serde(rename = "coordinatesToAdd")]
-    pub coordinates_to_add: Vec<[f64; 2]>,
+    #[serde(rename = "coordinatesToAdd", deserialize_with = "deserialize_points")]
+    pub coordinates_to_add: Vec<TrackPointDto>,
+    // 客户端批次序号，每条航迹从任意值开始逐批加 1；重复的序号视为重试，跳号的批次会被拒绝
+    pub seq: Option<u64>,
+    // 客户端生成的批次 ID，没有序号时也可以用来识别重试
+    #[serde(rename = "batchId")]
+    pub batch_id: Option<String>,
+    // 坐标所在的坐标系，默认 WGS-84
+    #[serde(default)]
+    pub crs: Crs,
+}
+
+// 已接受的追加批次，startIndex 为本批第一个点在航迹中的下标
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppendBatch {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+    #[serde(rename = "batchId", default, skip_serializing_if = "Option::is_none")]
+    pub batch_id: Option<String>,
+    #[serde(rename = "startIndex")]
+    pub start_index: u64,
+    pub count: u64,
+}
+
+// 追加接口返回的确认信息，点下标范围为 [startIndex, endIndex)；过早批次的重试没有下标范围
+#[derive(Debug, Serialize)]
+pub struct AppendAckDto {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+    #[serde(rename = "batchId", skip_serializing_if = "Option::is_none")]
+    pub batch_id: Option<String>,
+    #[serde(rename = "startIndex", skip_serializing_if = "Option::is_none")]
+    pub start_index: Option<u64>,
+    #[serde(rename = "endIndex", skip_serializing_if = "Option::is_none")]
+    pub end_index: Option<u64>,
+    // 为 true 时表示该批次之前已经接受过，本次没有写入任何点
+    pub duplicate: bool,
+}
+
+impl AppendAckDto {
+    pub fn new(batch: &AppendBatch, duplicate: bool) -> Self {
+        AppendAckDto {
+            seq: batch.seq,
+            batch_id: batch.batch_id.clone(),
+            start_index: Some(batch.start_index),
+            end_index: Some(batch.start_index + batch.count),
+            duplicate,
+        }
+    }
 }
 // 新增：用于创建操作的请求体结构体
 #[derive(Debug, Deserialize)] // 只需要 Deserialize，因为这是输入载荷
 pub struct ShipTrackRequestDto {
-    pub coordinates: Vec<[f64; 2]>,
-    #[serde(rename = "totalPoints")]
-    pub total_points: u32, // 客户端提供 total_points
+    // 每个点可以是 [经度, 纬度] 或带高度、时间等字段的对象
+    #[serde(deserialize_with = "deserialize_points")]
+    pub coordinates: Vec<TrackPointDto>,
+    // 已废弃：totalPoints 由服务器按实际点数维护，保留字段只为兼容旧客户端
+    #[serde(rename = "totalPoints", default)]
+    #[allow(dead_code)]
+    pub total_points: u32,
+    #[serde(rename = "droneId")]
+    pub drone_id: Option<String>,
+    pub owner: Option<String>,
+    // 坐标所在的坐标系，默认 WGS-84，保存前统一转换为 WGS-84
+    #[serde(default)]
+    pub crs: Crs,
+}
+
+// PUT /track/{id} 的请求体：只包含客户端可以修改的字段，分段、批次记录、过滤状态等存储字段由服务器维护
+#[derive(Debug, Deserialize)]
+pub struct ShipTrackUpdateDto {
+    #[serde(deserialize_with = "deserialize_points")]
+    pub coordinates: Vec<TrackPointDto>,
+    #[serde(rename = "droneId")]
+    pub drone_id: Option<String>,
+    pub owner: Option<String>,
+    // 不提供时保留原有的任务关联
+    #[serde(rename = "missionId")]
+    pub mission_id: Option<String>,
+    // 坐标所在的坐标系，默认 WGS-84，保存前统一转换为 WGS-84
+    #[serde(default)]
+    pub crs: Crs,
 }
 
 #[derive(Debug, Serialize)] // Only Serialize is needed for responses
@@ -40,10 +344,84 @@ pub struct ShipTrackResponseDto {
     #[serde(rename = "lastUpdate", serialize_with = "serialize_bson_datetime_as_rfc3339_string")]
     pub last_update: DateTime, // mongodb::bson::DateTime
 
-    pub coordinates: Vec<[f64; 2]>,
+    pub coordinates: TrackCoordinatesDto,
 
     #[serde(rename = "totalPoints")]
     pub total_points: u32,
+
+    #[serde(rename = "droneId", skip_serializing_if = "Option::is_none")]
+    pub drone_id: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+
+    #[serde(rename = "missionId", skip_serializing_if = "Option::is_none")]
+    pub mission_id: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub simplification: Option<SimplificationDto>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub append: Option<AppendAckDto>,
+
+    // 坐标不是 WGS-84 时标注所用的坐标系
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crs: Option<String>,
+}
+
+// 抽稀结果说明，reductionRatio 为被去掉的点所占比例
+#[derive(Debug, Serialize)]
+pub struct SimplificationDto {
+    pub algorithm: &'static str,
+    #[serde(rename = "toleranceMeters", skip_serializing_if = "Option::is_none")]
+    pub tolerance_meters: Option<f64>,
+    #[serde(rename = "targetPoints", skip_serializing_if = "Option::is_none")]
+    pub target_points: Option<usize>,
+    #[serde(rename = "originalPoints")]
+    pub original_points: usize,
+    #[serde(rename = "simplifiedPoints")]
+    pub simplified_points: usize,
+    #[serde(rename = "reductionRatio")]
+    pub reduction_ratio: f64,
+}
+
+// 完整模式返回点对象；紧凑模式只返回 [经度, 纬度]，兼容旧客户端
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum TrackCoordinatesDto {
+    Points(Vec<TrackPointDto>),
+    Compact(Vec<[f64; 2]>),
+}
+
+// 航迹查询接口的 ?compact=true 参数
+#[derive(Debug, Default, Deserialize)]
+pub struct TrackResponseQuery {
+    #[serde(default)]
+    pub compact: bool,
+    // 抽稀参数：容差米数（如 5 或 5m，Douglas–Peucker）或目标点数（如 500pts，Visvalingam–Whyatt）
+    pub simplify: Option<String>,
+    // 输出坐标系：wgs84（默认）、gcj02、bd09 或 utm
+    #[serde(default)]
+    pub crs: Crs,
+    // 为 true 时返回包括被过滤点在内的原始坐标，不做平滑
+    #[serde(default)]
+    pub raw: bool,
+}
+
+impl ShipTrackResponseDto {
+    pub fn new(mut track: ShipTrack, query: &TrackResponseQuery) -> Self {
+        let crs = track.project_to(query.crs);
+        let mut dto = if query.compact {
+            let positions = track.positions();
+            let mut dto = ShipTrackResponseDto::from(track);
+            dto.coordinates = TrackCoordinatesDto::Compact(positions);
+            dto
+        } else {
+            ShipTrackResponseDto::from(track)
+        };
+        dto.crs = crs;
+        dto
+    }
 }
 
 // Implement From trait for easy conversion from ShipTrack model to ShipTrackResponseDto
@@ -53,8 +431,318 @@ impl From<ShipTrack> for ShipTrackResponseDto {
             id: track_model.id,
             start_time: track_model.start_time,
             last_update: track_model.last_update,
-            coordinates: track_model.coordinates,
+            coordinates: TrackCoordinatesDto::Points(track_model.coordinates.into_iter().map(TrackPointDto::from).collect()),
             total_points: track_model.total_points,
+            drone_id: track_model.drone_id,
+            owner: track_model.owner,
+            mission_id: track_model.mission_id.map(|id| id.to_hex()),
+            simplification: None,
+            append: None,
+            crs: None,
         }
     }
 }
+
+// 列表查询使用的摘要，不包含 coordinates 数组
+#[derive(Debug, Deserialize)]
+pub struct ShipTrackSummary {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    #[serde(rename = "startTime")]
+    pub start_time: DateTime,
+    #[serde(rename = "lastUpdate")]
+    pub last_update: DateTime,
+    #[serde(rename = "totalPoints")]
+    pub total_points: u32,
+    #[serde(rename = "droneId", default)]
+    pub drone_id: Option<String>,
+    #[serde(default)]
+    pub owner: Option<String>,
+    #[serde(rename = "missionId", default)]
+    pub mission_id: Option<ObjectId>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ShipTrackSummaryDto {
+    #[serde(rename = "_id", serialize_with = "serialize_object_id_as_hex_string")]
+    pub id: ObjectId,
+    #[serde(rename = "startTime", serialize_with = "serialize_bson_datetime_as_rfc3339_string")]
+    pub start_time: DateTime,
+    #[serde(rename = "lastUpdate", serialize_with = "serialize_bson_datetime_as_rfc3339_string")]
+    pub last_update: DateTime,
+    #[serde(rename = "totalPoints")]
+    pub total_points: u32,
+    #[serde(rename = "droneId")]
+    pub drone_id: Option<String>,
+    pub owner: Option<String>,
+    #[serde(rename = "missionId", skip_serializing_if = "Option::is_none")]
+    pub mission_id: Option<String>,
+}
+
+impl From<ShipTrackSummary> for ShipTrackSummaryDto {
+    fn from(summary: ShipTrackSummary) -> Self {
+        ShipTrackSummaryDto {
+            id: summary.id,
+            start_time: summary.start_time,
+            last_update: summary.last_update,
+            total_points: summary.total_points,
+            drone_id: summary.drone_id,
+            owner: summary.owner,
+            mission_id: summary.mission_id.map(|id| id.to_hex()),
+        }
+    }
+}
+
+impl From<&ShipTrack> for ShipTrackSummaryDto {
+    fn from(track: &ShipTrack) -> Self {
+        ShipTrackSummaryDto {
+            id: track.id,
+            start_time: track.start_time,
+            last_update: track.last_update,
+            total_points: track.total_points,
+            drone_id: track.drone_id.clone(),
+            owner: track.owner.clone(),
+            mission_id: track.mission_id.map(|id| id.to_hex()),
+        }
+    }
+}
+
+// 单个导入文件的处理结果，解析失败时 error 不为空
+#[derive(Debug, Serialize)]
+pub struct TrackImportResultDto {
+    pub file: String,
+    pub tracks: Vec<ImportedTrackDto>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportedTrackDto {
+    #[serde(rename = "_id", serialize_with = "serialize_object_id_as_hex_string")]
+    pub id: ObjectId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(rename = "totalPoints")]
+    pub total_points: u32,
+    // 为 true 时表示内容相同的航迹已经导入过，id 为已有航迹
+    pub duplicate: bool,
+}
+
+// GET /track/{id}/stats 的响应
+#[derive(Debug, Serialize)]
+pub struct TrackStatsDto {
+    #[serde(rename = "pointCount")]
+    pub point_count: u64,
+    #[serde(rename = "distanceMeters")]
+    pub distance_meters: f64,
+    pub bbox: Option<[f64; 4]>,
+    pub centroid: Option<[f64; 2]>,
+    // 有点时间时取首末点时间，否则取 startTime / lastUpdate
+    #[serde(rename = "startTime", serialize_with = "serialize_bson_datetime_as_rfc3339_string")]
+    pub start_time: DateTime,
+    #[serde(rename = "endTime", serialize_with = "serialize_bson_datetime_as_rfc3339_string")]
+    pub end_time: DateTime,
+    #[serde(rename = "durationSeconds")]
+    pub duration_seconds: f64,
+    // 平均速度 = 总距离 / 时长（米/秒）
+    #[serde(rename = "avgSpeed")]
+    pub avg_speed: Option<f64>,
+    #[serde(rename = "maxSpeed")]
+    pub max_speed: Option<f64>,
+    #[serde(rename = "minAltitude")]
+    pub min_altitude: Option<f64>,
+    #[serde(rename = "maxAltitude")]
+    pub max_altitude: Option<f64>,
+    // 被噪声过滤拒绝的点数，其他统计值不包含这些点
+    #[serde(rename = "rejectedPoints")]
+    pub rejected_points: RejectedPointsDto,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RejectedPointsDto {
+    pub speed: u64,
+    pub duplicate: u64,
+}
+
+impl From<&ShipTrack> for TrackStatsDto {
+    fn from(track: &ShipTrack) -> Self {
+        let stats = track.computed_stats();
+        let (start_time, end_time) = match (stats.first_point_time, stats.last_point_time) {
+            (Some(first), Some(last)) if last > first => (first, last),
+            _ => (track.start_time, track.last_update),
+        };
+        let duration_seconds = ((end_time.timestamp_millis() - start_time.timestamp_millis()) as f64 / 1000.0).max(0.0);
+        TrackStatsDto {
+            point_count: stats.point_count,
+            distance_meters: stats.distance_meters,
+            bbox: stats.bbox,
+            centroid: stats.centroid,
+            start_time,
+            end_time,
+            duration_seconds,
+            avg_speed: (duration_seconds > 0.0).then(|| stats.distance_meters / duration_seconds),
+            max_speed: stats.max_speed,
+            min_altitude: stats.min_altitude,
+            max_altitude: stats.max_altitude,
+            rejected_points: RejectedPointsDto { speed: stats.rejected_speed, duplicate: stats.rejected_duplicate },
+        }
+    }
+}
+
+// GET /track/stats/monthly 的查询参数，按 startTime 所在月份统计
+#[derive(Debug, Deserialize)]
+pub struct MonthlyDistanceQuery {
+    pub from: Option<chrono::DateTime<chrono::Utc>>,
+    pub to: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(rename = "droneId")]
+    pub drone_id: Option<String>,
+}
+
+// 每架无人机每月的飞行距离
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MonthlyDistanceDto {
+    #[serde(rename = "droneId")]
+    pub drone_id: Option<String>,
+    // YYYY-MM（UTC）
+    pub month: String,
+    #[serde(rename = "trackCount")]
+    pub track_count: i64,
+    #[serde(rename = "distanceMeters")]
+    pub distance_meters: f64,
+}
+
+// GET /track/near 的查询参数
+#[derive(Debug, Deserialize)]
+pub struct NearQuery {
+    pub lon: f64,
+    pub lat: f64,
+    // 距离上限（米），默认 500
+    #[serde(rename = "maxDistance")]
+    pub max_distance: Option<f64>,
+}
+
+// GET /track/within 的查询参数：bbox=最小经度,最小纬度,最大经度,最大纬度
+#[derive(Debug, Deserialize)]
+pub struct BboxQuery {
+    pub bbox: String,
+}
+
+// 输出坐标系和是否返回原始坐标，与其他查询参数分开提取
+#[derive(Debug, Default, Deserialize)]
+pub struct OutputQuery {
+    #[serde(default)]
+    pub crs: Crs,
+    #[serde(default)]
+    pub raw: bool,
+}
+
+// GET /track 的查询参数
+#[derive(Debug, Deserialize)]
+pub struct TrackListQuery {
+    #[serde(rename = "startFrom")]
+    pub start_from: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(rename = "startTo")]
+    pub start_to: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(rename = "updatedFrom")]
+    pub updated_from: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(rename = "updatedTo")]
+    pub updated_to: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(rename = "droneId")]
+    pub drone_id: Option<String>,
+    pub owner: Option<String>,
+    #[serde(rename = "minPoints")]
+    pub min_points: Option<u32>,
+    #[serde(rename = "maxPoints")]
+    pub max_points: Option<u32>,
+    // 排序字段：startTime（默认）、lastUpdate、totalPoints
+    pub sort: Option<String>,
+    // asc 或 desc（默认）
+    pub order: Option<String>,
+    pub limit: Option<i64>,
+    // 上一页响应中的 nextCursor
+    pub cursor: Option<String>,
+    // 为 geojson 时返回 FeatureCollection，也可以用 Accept: application/geo+json
+    pub format: Option<String>,
+    // GeoJSON 输出的坐标系，以及是否返回原始坐标
+    #[serde(default)]
+    pub crs: Crs,
+    #[serde(default)]
+    pub raw: bool,
+}
+
+// 航迹批量删除的筛选条件，至少需要提供一项
+#[derive(Debug, Deserialize)]
+pub struct TrackBulkDeleteFilter {
+    pub ids: Option<Vec<String>>,
+    #[serde(rename = "droneId")]
+    pub drone_id: Option<String>,
+    pub owner: Option<String>,
+    #[serde(rename = "missionId")]
+    pub mission_id: Option<String>,
+    #[serde(rename = "startFrom")]
+    pub start_from: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(rename = "startTo")]
+    pub start_to: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrackPageDto<T> {
+    pub items: Vec<T>,
+    #[serde(rename = "nextCursor")]
+    pub next_cursor: Option<String>,
+}
+
+// GeoJSON Feature 的 properties
+#[derive(Debug, Serialize)]
+pub struct TrackFeatureProperties {
+    #[serde(rename = "startTime", serialize_with = "serialize_bson_datetime_as_rfc3339_string")]
+    pub start_time: DateTime,
+    #[serde(rename = "lastUpdate", serialize_with = "serialize_bson_datetime_as_rfc3339_string")]
+    pub last_update: DateTime,
+    #[serde(rename = "totalPoints")]
+    pub total_points: u32,
+    #[serde(rename = "droneId", skip_serializing_if = "Option::is_none")]
+    pub drone_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    pub stats: TrackFeatureStats,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub simplification: Option<SimplificationDto>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crs: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrackFeatureStats {
+    #[serde(rename = "pointCount")]
+    pub point_count: usize,
+    #[serde(rename = "distanceMeters")]
+    pub distance_meters: f64,
+}
+
+impl From<ShipTrack> for Feature<TrackFeatureProperties> {
+    fn from(track: ShipTrack) -> Self {
+        let positions = track.positions();
+        let computed = track.computed_stats();
+        let stats = TrackFeatureStats {
+            point_count: computed.point_count as usize,
+            distance_meters: computed.distance_meters,
+        };
+        let properties = TrackFeatureProperties {
+            start_time: track.start_time,
+            last_update: track.last_update,
+            total_points: track.total_points,
+            drone_id: track.drone_id,
+            owner: track.owner,
+            stats,
+            simplification: None,
+            crs: None,
+        };
+        Feature::new(
+            track.id.to_hex(),
+            bounding_box(&positions),
+            Geometry::from_track(positions),
+            properties,
+        )
+    }
+}
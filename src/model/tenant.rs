@@ -0,0 +1,78 @@
+use bson::{doc, Document};
+use serde::{Deserialize, Serialize};
+use crate::model::track_filter::TrackFilterConfig;
+
+// 调用方所属的租户：组织 -> 风场
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tenant {
+    #[serde(rename = "orgId")]
+    pub org_id: String,
+    #[serde(rename = "windFarmId")]
+    pub wind_farm_id: String,
+}
+
+impl Tenant {
+    // 在查询条件上附加租户过滤，所有 service 的查询都必须经过这里
+    pub fn scope(&self, mut filter: Document) -> Document {
+        filter.insert("orgId", self.org_id.clone());
+        filter.insert("windFarmId", self.wind_farm_id.clone());
+        filter
+    }
+}
+
+// 带有租户归属字段的文档，写入前由 service 用调用方的租户覆盖
+pub trait TenantOwned {
+    fn set_tenant(&mut self, tenant: &Tenant);
+
+    fn tenant(&self) -> Tenant;
+}
+
+// 租户级配置；windFarmId 为空时表示组织级的默认配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TenantConfig {
+    #[serde(rename = "orgId")]
+    pub org_id: String,
+    #[serde(rename = "windFarmId")]
+    pub wind_farm_id: Option<String>,
+    #[serde(rename = "aiSystemPrompt")]
+    pub ai_system_prompt: Option<String>,
+    #[serde(rename = "aiModel")]
+    pub ai_model: Option<String>,
+    #[serde(rename = "storageQuotaBytes")]
+    pub storage_quota_bytes: Option<i64>,
+    #[serde(rename = "trackFilter", default, skip_serializing_if = "Option::is_none")]
+    pub track_filter: Option<TrackFilterConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TenantConfigRequestDto {
+    // 为 true 时写入组织级配置，否则写入当前风场的配置
+    #[serde(rename = "orgWide", default)]
+    pub org_wide: bool,
+    #[serde(rename = "aiSystemPrompt")]
+    pub ai_system_prompt: Option<String>,
+    #[serde(rename = "aiModel")]
+    pub ai_model: Option<String>,
+    #[serde(rename = "storageQuotaBytes")]
+    pub storage_quota_bytes: Option<i64>,
+    // GPS 噪声过滤配置，整体覆盖，不与组织级配置逐项合并
+    #[serde(rename = "trackFilter")]
+    pub track_filter: Option<TrackFilterConfig>,
+}
+
+// 合并组织级和风场级配置后的最终生效配置
+#[derive(Debug, Clone, Serialize)]
+pub struct EffectiveTenantConfig {
+    #[serde(rename = "aiSystemPrompt")]
+    pub ai_system_prompt: String,
+    #[serde(rename = "aiModel")]
+    pub ai_model: String,
+    #[serde(rename = "storageQuotaBytes")]
+    pub storage_quota_bytes: Option<i64>,
+    #[serde(rename = "trackFilter")]
+    pub track_filter: TrackFilterConfig,
+}
+
+pub fn config_key(org_id: &str, wind_farm_id: Option<&str>) -> Document {
+    doc! { "orgId": org_id, "windFarmId": wind_farm_id }
+}
@@ -0,0 +1,147 @@
+use std::ops::Range;
+use serde::{Deserialize, Serialize};
+use crate::model::track_point::TrackPoint;
+use crate::model::track_stats::TrackStats;
+
+// 架次识别的默认时间间隔：相邻两个有效点相隔超过 5 分钟视为降落后重新起飞
+const DEFAULT_SORTIE_GAP_SECONDS: f64 = 300.0;
+const MIN_SORTIE_GAP_SECONDS: f64 = 1.0;
+
+// POST /track/{id}/split 的请求体，index、time、gapSeconds 三选一。
+// 原航迹保留拆分点之前的点，之后的点依次写入新航迹
+#[derive(Debug, Deserialize)]
+pub struct SplitRequestDto {
+    // 第一条新航迹的第一个点的下标
+    pub index: Option<u64>,
+    // 从第一个时间不早于 time 的点开始拆出
+    pub time: Option<chrono::DateTime<chrono::Utc>>,
+    // 按识别出的架次拆分，即在所有超过该间隔（秒）的时间断点处拆分
+    #[serde(rename = "gapSeconds")]
+    pub gap_seconds: Option<f64>,
+}
+
+impl SplitRequestDto {
+    // 拆分点下标，升序且都在 1..len 内，保证拆出的每段至少有一个点
+    pub fn boundaries(&self, points: &[TrackPoint]) -> Result<Vec<usize>, String> {
+        let at = match (self.index, self.time, self.gap_seconds) {
+            (Some(index), None, None) => index as usize,
+            (None, Some(time), None) => {
+                let time = mongodb::bson::DateTime::from_chrono(time);
+                points.iter().position(|p| p.time.is_some_and(|t| t >= time))
+                    .ok_or_else(|| "No point at or after the given time".to_string())?
+            }
+            (None, None, Some(_)) => {
+                let sorties = detect_sorties(points, gap_millis(self.gap_seconds)?);
+                return Ok(sorties.iter().skip(1).map(|s| s.start).collect());
+            }
+            _ => return Err("Exactly one of index, time or gapSeconds is required".to_string()),
+        };
+        if at == 0 || at >= points.len() {
+            return Err(format!("Split point must be between 1 and {}", points.len().saturating_sub(1)));
+        }
+        Ok(vec![at])
+    }
+}
+
+// POST /track/{id}/merge 的请求体：把 trackId 的点按时间合并进当前航迹，trackId 随后移入回收站
+#[derive(Debug, Deserialize)]
+pub struct MergeRequestDto {
+    #[serde(rename = "trackId")]
+    pub track_id: String,
+}
+
+// GET /track/{id}/sorties 的查询参数
+#[derive(Debug, Deserialize)]
+pub struct SortieQuery {
+    #[serde(rename = "gapSeconds")]
+    pub gap_seconds: Option<f64>,
+}
+
+impl SortieQuery {
+    pub fn gap_millis(&self) -> Result<i64, String> {
+        gap_millis(self.gap_seconds)
+    }
+}
+
+fn gap_millis(seconds: Option<f64>) -> Result<i64, String> {
+    let seconds = seconds.unwrap_or(DEFAULT_SORTIE_GAP_SECONDS);
+    if !seconds.is_finite() || seconds < MIN_SORTIE_GAP_SECONDS {
+        return Err(format!("gapSeconds must be at least {}", MIN_SORTIE_GAP_SECONDS));
+    }
+    Ok((seconds * 1000.0).round() as i64)
+}
+
+// 识别出的一个架次，点下标范围为 [startIndex, endIndex)
+#[derive(Debug, Serialize)]
+pub struct SortieDto {
+    #[serde(rename = "startIndex")]
+    pub start_index: usize,
+    #[serde(rename = "endIndex")]
+    pub end_index: usize,
+    #[serde(rename = "pointCount")]
+    pub point_count: usize,
+    #[serde(rename = "startTime", skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(rename = "endTime", skip_serializing_if = "Option::is_none")]
+    pub end_time: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(rename = "durationSeconds")]
+    pub duration_seconds: f64,
+    #[serde(rename = "distanceMeters")]
+    pub distance_meters: f64,
+}
+
+impl SortieDto {
+    pub fn new(points: &[TrackPoint], range: Range<usize>) -> Self {
+        let stats = TrackStats::compute(&points[range.clone()]);
+        let (start_time, end_time) = (stats.first_point_time.map(|t| t.to_chrono()), stats.last_point_time.map(|t| t.to_chrono()));
+        let duration_seconds = match (start_time, end_time) {
+            (Some(start), Some(end)) => (end - start).num_milliseconds() as f64 / 1000.0,
+            _ => 0.0,
+        };
+        SortieDto {
+            start_index: range.start,
+            end_index: range.end,
+            point_count: range.len(),
+            start_time,
+            end_time,
+            duration_seconds,
+            distance_meters: stats.distance_meters,
+        }
+    }
+}
+
+// 按相邻有效点之间的时间间隔划分架次。被过滤的点和没有时间的点跟随前一个点所在的架次
+pub fn detect_sorties(points: &[TrackPoint], gap_ms: i64) -> Vec<Range<usize>> {
+    let mut sorties = Vec::new();
+    let (mut start, mut previous) = (0, None);
+    for (i, point) in points.iter().enumerate() {
+        let Some(time) = point.time.filter(|_| point.accepted()).map(|t| t.timestamp_millis()) else {
+            continue;
+        };
+        if previous.is_some_and(|previous| time - previous > gap_ms) {
+            sorties.push(start..i);
+            start = i;
+        }
+        previous = Some(time);
+    }
+    if start < points.len() {
+        sorties.push(start..points.len());
+    }
+    sorties
+}
+
+// 两条航迹的点按时间归并，时间相同时 earlier 的点在前。
+// 没有时间的点沿用同一航迹中前一个带时间的点的时间，保持在原航迹中的相对顺序
+pub fn merge_by_time(earlier: Vec<TrackPoint>, later: Vec<TrackPoint>) -> Vec<TrackPoint> {
+    let keyed = |points: Vec<TrackPoint>| {
+        let mut last = i64::MIN;
+        points.into_iter().map(move |p| {
+            last = p.time.map_or(last, |t| t.timestamp_millis());
+            (last, p)
+        })
+    };
+    let mut merged: Vec<(i64, TrackPoint)> = keyed(earlier).chain(keyed(later)).collect();
+    // 稳定排序，相同时间的点保持拼接时的先后
+    merged.sort_by_key(|(time, _)| *time);
+    merged.into_iter().map(|(_, p)| p).collect()
+}
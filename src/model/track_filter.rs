@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+use crate::model::track_point::TrackPoint;
+
+// 租户的 GPS 噪声过滤配置。离群点和重复点在写入时判定并打上 rejected 标记（原始点仍然保存），
+// 卡尔曼平滑只在读取时作用于返回的坐标；?raw=true 时两者都不生效
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackFilterConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    // 与上一个有效点之间的速度超过该值（米/秒）的点视为离群点
+    #[serde(rename = "maxSpeed", default = "default_max_speed")]
+    pub max_speed: f64,
+    // 连续拒绝这么多个点后，认为上一个有效点本身有问题，接受当前点作为新的基准
+    #[serde(rename = "maxConsecutiveRejections", default = "default_max_consecutive_rejections")]
+    pub max_consecutive_rejections: u32,
+    // 时间相同（或都没有时间）且距离在该值（米）以内的相邻点视为重复点
+    #[serde(rename = "duplicateMeters", default = "default_duplicate_meters")]
+    pub duplicate_meters: f64,
+    #[serde(default)]
+    pub smoothing: Smoothing,
+    // 卡尔曼滤波的过程噪声（加速度标准差，米/秒²）和观测噪声（定位误差标准差，米）
+    #[serde(rename = "processNoise", default = "default_process_noise")]
+    pub process_noise: f64,
+    #[serde(rename = "measurementNoise", default = "default_measurement_noise")]
+    pub measurement_noise: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Smoothing {
+    #[default]
+    None,
+    Kalman,
+}
+
+impl Default for TrackFilterConfig {
+    fn default() -> Self {
+        TrackFilterConfig {
+            enabled: false,
+            max_speed: default_max_speed(),
+            max_consecutive_rejections: default_max_consecutive_rejections(),
+            duplicate_meters: default_duplicate_meters(),
+            smoothing: Smoothing::None,
+            process_noise: default_process_noise(),
+            measurement_noise: default_measurement_noise(),
+        }
+    }
+}
+
+fn default_max_speed() -> f64 {
+    40.0
+}
+
+fn default_max_consecutive_rejections() -> u32 {
+    5
+}
+
+fn default_duplicate_meters() -> f64 {
+    0.5
+}
+
+fn default_process_noise() -> f64 {
+    1.0
+}
+
+fn default_measurement_noise() -> f64 {
+    5.0
+}
+
+impl TrackFilterConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        let positive = |v: f64| v.is_finite() && v > 0.0;
+        if !positive(self.max_speed) || !positive(self.process_noise) || !positive(self.measurement_noise) {
+            return Err("maxSpeed, processNoise and measurementNoise must be positive".to_string());
+        }
+        if !self.duplicate_meters.is_finite() || self.duplicate_meters < 0.0 {
+            return Err("duplicateMeters must not be negative".to_string());
+        }
+        if self.max_consecutive_rejections == 0 {
+            return Err("maxConsecutiveRejections must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+// 跨批次保存在航迹文档上的过滤状态
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct FilterState {
+    #[serde(rename = "lastAccepted", default, skip_serializing_if = "Option::is_none")]
+    pub last_accepted: Option<TrackPoint>,
+    #[serde(rename = "consecutiveRejected", default)]
+    pub consecutive_rejected: u32,
+}
@@ -0,0 +1,202 @@
+use std::fmt;
+use std::marker::PhantomData;
+use mongodb::bson::DateTime;
+use serde::de::value::MapAccessDeserializer;
+use serde::de::{MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use crate::geo::crs::{Crs, Projection};
+use crate::model::point_codec;
+
+// 航迹点，存储的坐标为 WGS-84 经纬度，其余字段在设备或文件提供时才有
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TrackPoint {
+    pub lon: f64,
+    pub lat: f64,
+    // 海拔高度（米）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub altitude: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<DateTime>,
+    // 航向角（度，正北为 0，顺时针）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heading: Option<f64>,
+    // 地速（米/秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed: Option<f64>,
+    // GNSS 定位质量，与 NMEA GGA 的 fix quality 一致
+    #[serde(rename = "fixQuality", default, skip_serializing_if = "Option::is_none")]
+    pub fix_quality: Option<u8>,
+    // 写入时被噪声过滤拒绝的原因；被拒绝的点仍然保存，只在 ?raw=true 时返回
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rejected: Option<RejectReason>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RejectReason {
+    // 与上一个有效点之间的速度超过上限
+    Speed,
+    // 与上一个有效点时间相同且几乎没有位移
+    Duplicate,
+}
+
+impl TrackPoint {
+    pub fn position(&self) -> [f64; 2] {
+        [self.lon, self.lat]
+    }
+
+    pub fn accepted(&self) -> bool {
+        self.rejected.is_none()
+    }
+
+    // 请求中声明了其他坐标系时，写入前转换为 WGS-84，转换后的经纬度必须在有效范围内
+    pub fn convert_to_wgs84(&mut self, crs: Crs) -> Result<(), String> {
+        [self.lon, self.lat] = crs.to_wgs84(self.position())?;
+        if !(-180.0..=180.0).contains(&self.lon) || !(-90.0..=90.0).contains(&self.lat) {
+            return Err(format!("Coordinate out of range: lon {}, lat {}", self.lon, self.lat));
+        }
+        Ok(())
+    }
+
+    // 按输出坐标系转换，只用于响应
+    pub fn project(&mut self, projection: &Projection) {
+        [self.lon, self.lat] = projection.apply(self.position());
+    }
+}
+
+impl From<[f64; 2]> for TrackPoint {
+    fn from([lon, lat]: [f64; 2]) -> Self {
+        TrackPoint { lon, lat, altitude: None, time: None, heading: None, speed: None, fix_quality: None, rejected: None }
+    }
+}
+
+// 请求和响应中的航迹点，时间使用 RFC 3339 字符串
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackPointDto {
+    pub lon: f64,
+    pub lat: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub altitude: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heading: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed: Option<f64>,
+    #[serde(rename = "fixQuality", default, skip_serializing_if = "Option::is_none")]
+    pub fix_quality: Option<u8>,
+    // 只出现在 ?raw=true 的响应和实时推送中，请求中的该字段会被忽略
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rejected: Option<RejectReason>,
+}
+
+impl TrackPointDto {
+    pub fn project(&mut self, projection: &Projection) {
+        [self.lon, self.lat] = projection.apply([self.lon, self.lat]);
+    }
+}
+
+impl From<[f64; 2]> for TrackPointDto {
+    fn from(position: [f64; 2]) -> Self {
+        TrackPointDto::from(TrackPoint::from(position))
+    }
+}
+
+impl From<TrackPoint> for TrackPointDto {
+    fn from(point: TrackPoint) -> Self {
+        TrackPointDto {
+            lon: point.lon,
+            lat: point.lat,
+            altitude: point.altitude,
+            time: point.time.map(DateTime::to_chrono),
+            heading: point.heading,
+            speed: point.speed,
+            fix_quality: point.fix_quality,
+            rejected: point.rejected,
+        }
+    }
+}
+
+impl From<TrackPointDto> for TrackPoint {
+    fn from(point: TrackPointDto) -> Self {
+        TrackPoint {
+            lon: point.lon,
+            lat: point.lat,
+            altitude: point.altitude,
+            time: point.time.map(DateTime::from_chrono),
+            heading: point.heading,
+            speed: point.speed,
+            fix_quality: point.fix_quality,
+            rejected: None,
+        }
+    }
+}
+
+// 最后一个未被过滤的点的位置，用于连接相邻文档的几何
+pub fn last_accepted_position(points: &[TrackPoint]) -> Option<[f64; 2]> {
+    points.iter().rev().find(|p| p.accepted()).map(TrackPoint::position)
+}
+
+// 兼容旧格式：点既可以是 [经度, 纬度] 数组，也可以是带字段的对象，两种格式可以在同一航迹中混合出现。
+// 用于读取迁移前的航迹文档，以及仍然只发送二维坐标的旧客户端。
+// 已写满的点块可能以压缩编码存储为 binary，见 point_codec
+pub fn deserialize_points<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + From<[f64; 2]> + From<TrackPoint>,
+{
+    struct PointsVisitor<T>(PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de> + From<[f64; 2]> + From<TrackPoint>> Visitor<'de> for PointsVisitor<T> {
+        type Value = Vec<T>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a list of track points")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut points = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(LegacyPoint(point)) = seq.next_element::<LegacyPoint<T>>()? {
+                points.push(point);
+            }
+            Ok(points)
+        }
+
+        fn visit_bytes<E: serde::de::Error>(self, bytes: &[u8]) -> Result<Self::Value, E> {
+            let points = point_codec::decode(bytes).map_err(E::custom)?;
+            Ok(points.into_iter().map(T::from).collect())
+        }
+    }
+
+    deserializer.deserialize_seq(PointsVisitor(PhantomData))
+}
+
+struct LegacyPoint<T>(T);
+
+impl<'de, T: Deserialize<'de> + From<[f64; 2]>> Deserialize<'de> for LegacyPoint<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct PointVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de> + From<[f64; 2]>> Visitor<'de> for PointVisitor<T> {
+            type Value = LegacyPoint<T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("[lon, lat] or a track point object")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let lon: f64 = seq.next_element()?.ok_or_else(|| serde::de::Error::invalid_length(0, &self))?;
+                let lat: f64 = seq.next_element()?.ok_or_else(|| serde::de::Error::invalid_length(1, &self))?;
+                // 忽略数组中多余的元素
+                while seq.next_element::<serde::de::IgnoredAny>()?.is_some() {}
+                Ok(LegacyPoint(T::from([lon, lat])))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                T::deserialize(MapAccessDeserializer::new(map)).map(LegacyPoint)
+            }
+        }
+
+        deserializer.deserialize_any(PointVisitor(PhantomData))
+    }
+}
@@ -0,0 +1,165 @@
+use std::ops::Range;
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+use crate::model::geojson::Geometry;
+use crate::model::tenant::Tenant;
+use crate::model::track_point::{deserialize_points, TrackPoint, TrackPointDto};
+
+// 航迹超过单文档点数上限后，后续的点写入独立的分段文档。
+// 航迹文档自身的 coordinates 相当于第 0 段，分段从 seq = 1 开始。
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrackSegment {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    #[serde(rename = "trackId")]
+    pub track_id: ObjectId,
+    #[serde(rename = "orgId")]
+    pub org_id: String,
+    #[serde(rename = "windFarmId")]
+    pub wind_farm_id: String,
+    pub seq: u32,
+    // 本段第一个点在整条航迹中的下标
+    #[serde(rename = "startIndex")]
+    pub start_index: u64,
+    // 写满上限的分段可能以压缩编码存储
+    #[serde(deserialize_with = "deserialize_points")]
+    pub coordinates: Vec<TrackPoint>,
+    // 本段的线几何，以上一段的最后一个有效点开头，保证分段之间的连线也能被空间查询命中；被过滤的点不计入
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub geometry: Option<Geometry>,
+}
+
+impl TrackSegment {
+    pub fn new(track_id: ObjectId, tenant: &Tenant, seq: u32, start_index: u64, previous: Option<[f64; 2]>, coordinates: Vec<TrackPoint>) -> Self {
+        let geometry = Geometry::from_points(previous, coordinates.iter().filter(|p| p.accepted()).map(TrackPoint::position));
+        TrackSegment {
+            id: ObjectId::new(),
+            track_id,
+            org_id: tenant.org_id.clone(),
+            wind_farm_id: tenant.wind_farm_id.clone(),
+            seq,
+            start_index,
+            coordinates,
+            geometry,
+        }
+    }
+
+    pub fn reference(&self) -> SegmentRef {
+        let mut reference = SegmentRef {
+            id: self.id,
+            seq: self.seq,
+            start_index: self.start_index,
+            point_count: 0,
+            first_time: None,
+            last_time: None,
+        };
+        reference.extend(&self.coordinates);
+        reference
+    }
+}
+
+// 航迹文档上按顺序记录的分段索引，范围查询据此只读取相关分段
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SegmentRef {
+    pub id: ObjectId,
+    pub seq: u32,
+    #[serde(rename = "startIndex")]
+    pub start_index: u64,
+    #[serde(rename = "pointCount")]
+    pub point_count: u64,
+    #[serde(rename = "firstTime", default, skip_serializing_if = "Option::is_none")]
+    pub first_time: Option<DateTime>,
+    #[serde(rename = "lastTime", default, skip_serializing_if = "Option::is_none")]
+    pub last_time: Option<DateTime>,
+}
+
+impl SegmentRef {
+    pub fn extend(&mut self, points: &[TrackPoint]) {
+        for time in points.iter().filter_map(|p| p.time) {
+            self.first_time = Some(self.first_time.map_or(time, |t| t.min(time)));
+            self.last_time = Some(self.last_time.map_or(time, |t| t.max(time)));
+        }
+        self.point_count += points.len() as u64;
+    }
+
+    // 分段是否可能包含给定下标和时间范围内的点；没有时间信息的分段总是需要读取
+    pub fn overlaps(&self, range: &PointRange) -> bool {
+        let end_index = self.start_index + self.point_count;
+        if range.start.is_some_and(|start| end_index <= start) || range.end.is_some_and(|end| self.start_index >= end) {
+            return false;
+        }
+        let from = range.from.map(DateTime::from_chrono);
+        let to = range.to.map(DateTime::from_chrono);
+        match (self.first_time, self.last_time) {
+            (Some(first), Some(last)) => !(from.is_some_and(|from| last < from) || to.is_some_and(|to| first > to)),
+            _ => true,
+        }
+    }
+}
+
+// 追加时待写入分段的点。随航迹文档的条件更新一起记录在 pendingSegments 中，
+// 航迹文档更新成功后才写入分段，写完后清除；中途失败的由下一次追加或启动时补完
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingSegmentWrite {
+    #[serde(rename = "segmentId")]
+    pub segment_id: ObjectId,
+    pub seq: u32,
+    // 本次写入的第一个点在整条航迹中的下标
+    #[serde(rename = "startIndex")]
+    pub start_index: u64,
+    // 为 true 时新建分段，否则追加到已有分段
+    pub create: bool,
+    // 写入前分段已有的点数，重放时据此跳过已写入的追加
+    #[serde(rename = "pointCount")]
+    pub point_count: u64,
+    // 已有分段的几何是否已是线
+    #[serde(rename = "isLine")]
+    pub is_line: bool,
+    // 几何的起点：上一个有效点
+    pub previous: Option<[f64; 2]>,
+    pub points: Vec<TrackPoint>,
+}
+
+// GET /track/{id}/points 的查询参数：下标范围 [start, end) 和/或时间范围 [from, to]
+#[derive(Debug, Default, Deserialize)]
+pub struct PointRange {
+    pub start: Option<u64>,
+    pub end: Option<u64>,
+    pub from: Option<chrono::DateTime<chrono::Utc>>,
+    pub to: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl PointRange {
+    pub fn contains(&self, index: u64, point: &TrackPoint) -> bool {
+        if self.start.is_some_and(|start| index < start) || self.end.is_some_and(|end| index >= end) {
+            return false;
+        }
+        if self.from.is_none() && self.to.is_none() {
+            return true;
+        }
+        // 指定时间范围时，没有时间的点不返回
+        let Some(time) = point.time.map(DateTime::to_chrono) else {
+            return false;
+        };
+        !(self.from.is_some_and(|from| time < from) || self.to.is_some_and(|to| time > to))
+    }
+
+    // 裁剪用：从第一个到最后一个在范围内的点，中间没有时间的点也保留
+    pub fn span(&self, points: &[TrackPoint]) -> Option<Range<usize>> {
+        let first = points.iter().enumerate().position(|(i, p)| self.contains(i as u64, p))?;
+        let last = points.iter().enumerate().rposition(|(i, p)| self.contains(i as u64, p))?;
+        Some(first..last + 1)
+    }
+
+    pub fn is_unbounded(&self) -> bool {
+        self.start.is_none() && self.end.is_none() && self.from.is_none() && self.to.is_none()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct IndexedPointDto {
+    pub index: u64,
+    #[serde(flatten)]
+    pub point: TrackPointDto,
+}
@@ -0,0 +1,102 @@
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+use crate::geo::haversine;
+use crate::model::track_point::{RejectReason, TrackPoint};
+
+// 缓存在航迹文档上的统计值，创建和替换时全量计算，追加坐标时增量更新
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrackStats {
+    #[serde(rename = "pointCount")]
+    pub point_count: u64,
+    #[serde(rename = "distanceMeters")]
+    pub distance_meters: f64,
+    // [最小经度, 最小纬度, 最大经度, 最大纬度]
+    pub bbox: Option<[f64; 4]>,
+    // 各点经纬度的算术平均
+    pub centroid: Option<[f64; 2]>,
+    // 第一个/最后一个带时间的点
+    #[serde(rename = "firstPointTime")]
+    pub first_point_time: Option<DateTime>,
+    #[serde(rename = "lastPointTime")]
+    pub last_point_time: Option<DateTime>,
+    // 相邻两个带时间点之间的最大速度与设备上报速度中的较大者（米/秒）
+    #[serde(rename = "maxSpeed")]
+    pub max_speed: Option<f64>,
+    #[serde(rename = "minAltitude")]
+    pub min_altitude: Option<f64>,
+    #[serde(rename = "maxAltitude")]
+    pub max_altitude: Option<f64>,
+    // 被噪声过滤拒绝的点数，pointCount 包含这些点，其余统计值不包含
+    #[serde(rename = "rejectedSpeed", default)]
+    pub rejected_speed: u64,
+    #[serde(rename = "rejectedDuplicate", default)]
+    pub rejected_duplicate: u64,
+}
+
+impl TrackStats {
+    pub fn compute(points: &[TrackPoint]) -> Self {
+        let mut stats = TrackStats::default();
+        stats.extend(None, points);
+        stats
+    }
+
+    // 在已有统计的基础上追加新点，previous 为追加前航迹的最后一个有效点
+    pub fn extend(&mut self, previous: Option<&TrackPoint>, points: &[TrackPoint]) {
+        let mut last = previous.copied();
+        for point in points {
+            match point.rejected {
+                Some(RejectReason::Speed) => self.rejected_speed += 1,
+                Some(RejectReason::Duplicate) => self.rejected_duplicate += 1,
+                None => {}
+            }
+            if point.rejected.is_some() {
+                self.point_count += 1;
+                continue;
+            }
+            if let Some(last) = &last {
+                let distance = haversine(last.position(), point.position());
+                self.distance_meters += distance;
+                if let (Some(from), Some(to)) = (last.time, point.time) {
+                    let seconds = (to.timestamp_millis() - from.timestamp_millis()) as f64 / 1000.0;
+                    if seconds > 0.0 {
+                        self.max_speed = max_option(self.max_speed, Some(distance / seconds));
+                    }
+                }
+            }
+            self.max_speed = max_option(self.max_speed, point.speed);
+
+            let count = self.accepted_points() as f64;
+            self.centroid = Some(match self.centroid {
+                Some([lon, lat]) => [(lon * count + point.lon) / (count + 1.0), (lat * count + point.lat) / (count + 1.0)],
+                None => point.position(),
+            });
+            self.bbox = Some(match self.bbox {
+                Some(b) => [b[0].min(point.lon), b[1].min(point.lat), b[2].max(point.lon), b[3].max(point.lat)],
+                None => [point.lon, point.lat, point.lon, point.lat],
+            });
+            if let Some(time) = point.time {
+                self.first_point_time = Some(self.first_point_time.map_or(time, |t| t.min(time)));
+                self.last_point_time = Some(self.last_point_time.map_or(time, |t| t.max(time)));
+            }
+            if let Some(altitude) = point.altitude {
+                self.min_altitude = Some(self.min_altitude.map_or(altitude, |a| a.min(altitude)));
+                self.max_altitude = Some(self.max_altitude.map_or(altitude, |a| a.max(altitude)));
+            }
+            self.point_count += 1;
+            last = Some(*point);
+        }
+    }
+}
+
+impl TrackStats {
+    pub fn accepted_points(&self) -> u64 {
+        self.point_count - self.rejected_speed - self.rejected_duplicate
+    }
+}
+
+fn max_option(a: Option<f64>, b: Option<f64>) -> Option<f64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        (a, b) => a.or(b),
+    }
+}
@@ -0,0 +1,32 @@
+use bson::DateTime;
+use serde::{Deserialize, Serialize};
+
+// 回收站列表中的条目，在原响应结构上附加删除信息
+#[derive(Debug, Serialize)]
+pub struct TrashEntryDto<T: Serialize> {
+    #[serde(flatten)]
+    pub item: T,
+    #[serde(rename = "deletedAt")]
+    pub deleted_at: Option<String>,
+    #[serde(rename = "deletedBy")]
+    pub deleted_by: Option<String>,
+}
+
+impl<T: Serialize> TrashEntryDto<T> {
+    pub fn new(item: T, deleted_at: Option<DateTime>, deleted_by: Option<String>) -> Self {
+        TrashEntryDto {
+            item,
+            deleted_at: deleted_at.and_then(|t| t.try_to_rfc3339_string().ok()),
+            deleted_by,
+        }
+    }
+}
+
+// 批量删除请求。第一次请求不带 confirmationToken，服务端返回匹配数量和确认令牌；
+// 第二次携带相同筛选条件和令牌才会真正执行删除
+#[derive(Debug, Deserialize)]
+pub struct BulkDeleteRequestDto<F> {
+    pub filter: F,
+    #[serde(rename = "confirmationToken")]
+    pub confirmation_token: Option<String>,
+}
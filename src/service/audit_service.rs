@@ -0,0 +1,63 @@
+use bson::{doc, DateTime, Document};
+use futures::TryStreamExt;
+use mongodb::options::FindOptions;
+use mongodb::Collection;
+use serde::Serialize;
+use tracing::error;
+use crate::model::audit_log::{AuditLog, AuditLogQuery};
+use crate::model::tenant::Tenant;
+
+const DEFAULT_QUERY_LIMIT: i64 = 200;
+const MAX_QUERY_LIMIT: i64 = 1000;
+
+pub struct AuditService {
+    pub collection: Collection<AuditLog>,
+}
+
+impl AuditService {
+    pub fn new(collection: Collection<AuditLog>) -> Self {
+        Self { collection }
+    }
+
+    // 审计集合只提供追加和查询，不提供修改和删除
+    pub async fn record(&self, entry: AuditLog) -> mongodb::error::Result<()> {
+        self.collection.insert_one(entry).await?;
+        Ok(())
+    }
+
+    pub async fn query(&self, tenant: &Tenant, query: AuditLogQuery) -> mongodb::error::Result<Vec<AuditLog>> {
+        let mut filter = tenant.scope(doc! {});
+        if let Some(entity) = query.entity {
+            filter.insert("entity", entity);
+        }
+        if let Some(entity_id) = query.entity_id {
+            filter.insert("entityId", entity_id);
+        }
+        if let Some(actor) = query.actor {
+            filter.insert("actor", actor);
+        }
+        let mut time_range = doc! {};
+        if let Some(from) = query.from {
+            time_range.insert("$gte", DateTime::from_chrono(from));
+        }
+        if let Some(to) = query.to {
+            time_range.insert("$lte", DateTime::from_chrono(to));
+        }
+        if !time_range.is_empty() {
+            filter.insert("createdAt", time_range);
+        }
+
+        let options = FindOptions::builder()
+            .sort(doc! {"createdAt": -1})
+            .limit(query.limit.unwrap_or(DEFAULT_QUERY_LIMIT).clamp(1, MAX_QUERY_LIMIT))
+            .build();
+        self.collection.find(filter).with_options(options).await?.try_collect().await
+    }
+}
+
+// 将模型转换为快照文档，失败时只记录日志，不影响审计条目本身的写入
+pub fn snapshot<T: Serialize>(value: &T) -> Option<Document> {
+    bson::to_document(value)
+        .map_err(|e| error!("Failed to snapshot audit entity: {:?}", e))
+        .ok()
+}
@@ -1,40 +1,155 @@
-use bson::doc;
+use std::sync::Arc;
+use std::time::Duration;
+use bson::{doc, Document};
 
 use mongodb::bson::oid::ObjectId;
 use mongodb::Collection;
+use mongodb::options::ReturnDocument;
 use tracing::log::error;
 use crate::error::AppError;
-use crate::model::flight::Flight;
+use crate::model::audit_log::AuditContext;
+use crate::model::flight::{Flight, FlightBulkDeleteFilter};
+use crate::model::tenant::{Tenant, TenantOwned};
+use crate::model::trash::BulkDeleteRequestDto;
+use crate::service::audit_service::{snapshot, AuditService};
+use crate::service::soft_delete::{active, list_trash, purge_expired, restore_one, soft_delete_one, BulkDelete};
 
 pub struct FlightService{
     pub collection: Collection<Flight>,
+    pub audit: Arc<AuditService>,
+    bulk_delete: BulkDelete,
 }
 impl FlightService {
-    pub fn new(collection: Collection<Flight>) -> Self {
-        Self { collection }
+    pub fn new(collection: Collection<Flight>, audit: Arc<AuditService>) -> Self {
+        Self { collection, audit, bulk_delete: BulkDelete::default() }
     }
 
-    pub async fn create(&self, flight: Flight) -> Result<(), AppError> {
+    pub async fn create(&self, mut flight: Flight, ctx: &AuditContext) -> Result<(), AppError> {
+        flight.set_tenant(&ctx.tenant);
+        let after = snapshot(&flight);
+        let id = flight.id;
         self.collection.insert_one(flight).await.map_err(|e| {
             AppError::InternalServerError(format!("Failed to save report: {}", e))
         })?;
+        self.audit.record(ctx.entry("create", "flight", Some(id.to_hex()), None, after)).await?;
         Ok(())
     }
 
-    pub async fn get(&self, id: &str) -> mongodb::error::Result<Option<Flight>> {
+    pub async fn get(&self, tenant: &Tenant, id: &str) -> mongodb::error::Result<Option<Flight>> {
         let obj_id = ObjectId::parse_str(id).map_err(|e| {
             error!("{:?}", e);
             mongodb::error::Error::custom(e)
         })?;
-        self.collection.find_one(doc! {"_id": obj_id}).await
+        self.collection.find_one(active(tenant.scope(doc! {"_id": obj_id}))).await
     }
 
-    pub async fn update(&self, id: &str, flight: Flight) -> mongodb::error::Result<()> {
+    // 航迹对应的飞行记录；同一航迹有多条时取最新创建的一条
+    pub async fn find_by_track(&self, tenant: &Tenant, track_id: ObjectId) -> mongodb::error::Result<Option<Flight>> {
+        self.collection
+            .find_one(active(tenant.scope(doc! {"trackId": track_id})))
+            .sort(doc! { "_id": -1 })
+            .await
+    }
+
+    pub async fn update(&self, id: &str, mut flight: Flight, ctx: &AuditContext) -> mongodb::error::Result<()> {
         let obj_id = ObjectId::parse_str(id).map_err(|e| {
             error!("{:?}", e);
             mongodb::error::Error::custom(e)
         })?;
-        self.collection.replace_one(doc! {"_id": obj_id}, flight).await?;
+        flight.set_tenant(&ctx.tenant);
+        flight.deleted_at = None;
+        flight.deleted_by = None;
+        let before = self.get(&ctx.tenant, id).await?;
+        // 请求中没有 missionId 时保留原有的任务关联
+        flight.mission_id = flight.mission_id.or(before.as_ref().and_then(|b| b.mission_id));
+        let after = snapshot(&flight);
+        self.collection.replace_one(active(ctx.tenant.scope(doc! {"_id": obj_id})), flight).await?;
+        self.audit.record(ctx.entry("update", "flight", Some(obj_id.to_hex()), before.as_ref().and_then(snapshot), after)).await
+    }
+
+    // 设置或清除飞行记录关联的任务，返回更新后的记录；清除时只解除与 expected 任务的关联
+    pub async fn set_mission(&self, id: &str, mission_id: Option<ObjectId>, expected: Option<ObjectId>, ctx: &AuditContext) -> Result<Option<Flight>, AppError> {
+        let obj_id = ObjectId::parse_str(id).map_err(|e| {
+            AppError::BadRequest(format!("Invalid ObjectId: {}", e))
+        })?;
+        let mut filter = doc! {"_id": obj_id};
+        if let Some(expected) = expected {
+            filter.insert("missionId", expected);
+        }
+        let update = match mission_id {
+            Some(mission_id) => doc! { "$set": { "missionId": mission_id } },
+            None => doc! { "$unset": { "missionId": "" } },
+        };
+        let updated = self.collection
+            .find_one_and_update(active(ctx.tenant.scope(filter)), update)
+            .return_document(ReturnDocument::After)
+            .await?;
+        if updated.is_some() {
+            let after = doc! { "missionId": mission_id.map(|m| m.to_hex()) };
+            self.audit.record(ctx.entry("update", "flight", Some(obj_id.to_hex()), None, Some(after))).await?;
+        }
+        Ok(updated)
+    }
+
+    // 软删除，移入回收站
+    pub async fn delete(&self, id: &str, ctx: &AuditContext) -> Result<(), AppError> {
+        let obj_id = ObjectId::parse_str(id).map_err(|e| {
+            AppError::BadRequest(format!("Invalid ObjectId: {}", e))
+        })?;
+        let deleted = soft_delete_one(&self.collection, ctx.tenant.scope(doc! {"_id": obj_id}), ctx).await?;
+        if deleted.is_some() {
+            let after = doc! { "deletedBy": ctx.actor.clone() };
+            self.audit.record(ctx.entry("delete", "flight", Some(obj_id.to_hex()), None, Some(after))).await?;
+        }
         Ok(())
     }
-}
\ No newline at end of file
+
+    // 按筛选条件批量软删除，需要两步确认，与报告的批量删除相同
+    pub async fn bulk_delete(&self, request: BulkDeleteRequestDto<FlightBulkDeleteFilter>, ctx: &AuditContext) -> Result<serde_json::Value, AppError> {
+        let filter = ctx.tenant.scope(Self::bulk_delete_filter(request.filter)?);
+        self.bulk_delete.run(&self.collection, &self.audit, "flight", filter, request.confirmation_token, ctx).await
+    }
+
+    fn bulk_delete_filter(filter: FlightBulkDeleteFilter) -> Result<Document, AppError> {
+        let parse = |id: &String| ObjectId::parse_str(id).map_err(|e| AppError::BadRequest(format!("Invalid ObjectId: {}", e)));
+        let mut query = doc! {};
+        if let Some(ids) = filter.ids {
+            query.insert("_id", doc! { "$in": ids.iter().map(parse).collect::<Result<Vec<_>, _>>()? });
+        }
+        if let Some(track_id) = filter.track_id {
+            query.insert("trackId", parse(&track_id)?);
+        }
+        if let Some(mission_id) = filter.mission_id {
+            query.insert("missionId", parse(&mission_id)?);
+        }
+        if query.is_empty() {
+            return Err(AppError::BadRequest("Bulk delete requires at least one filter".to_string()));
+        }
+        Ok(query)
+    }
+
+    pub async fn restore(&self, id: &str, ctx: &AuditContext) -> Result<Option<Flight>, AppError> {
+        let obj_id = ObjectId::parse_str(id).map_err(|e| {
+            AppError::BadRequest(format!("Invalid ObjectId: {}", e))
+        })?;
+        let restored = restore_one(&self.collection, ctx.tenant.scope(doc! {"_id": obj_id})).await?;
+        if restored.is_some() {
+            self.audit.record(ctx.entry("restore", "flight", Some(obj_id.to_hex()), None, None)).await?;
+        }
+        Ok(restored)
+    }
+
+    pub async fn get_trash(&self, tenant: &Tenant) -> mongodb::error::Result<Vec<Flight>> {
+        list_trash(&self.collection, tenant.scope(doc! {})).await
+    }
+
+    // 由定时任务调用，清除超过保留期的回收站飞行记录
+    pub async fn purge_expired(&self, retention: Duration) -> mongodb::error::Result<usize> {
+        let purged = purge_expired(&self.collection, retention, |item| item.id).await?;
+        for flight in &purged {
+            let ctx = AuditContext::system("purge_expired", flight.tenant());
+            self.audit.record(ctx.entry("purge", "flight", Some(flight.id.to_hex()), snapshot(flight), None)).await?;
+        }
+        Ok(purged.len())
+    }
+}
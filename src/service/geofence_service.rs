@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use bson::{doc, Bson, DateTime};
+use futures::TryStreamExt;
+use mongodb::bson::oid::ObjectId;
+use mongodb::{Collection, IndexModel};
+use crate::error::AppError;
+use crate::model::audit_log::AuditContext;
+use crate::model::geofence::{AlertQuery, Geofence, GeofenceAlert, GeofenceAlertDto, GeofenceRequestDto};
+use crate::model::live::LiveMessage;
+use crate::model::tenant::{Tenant, TenantOwned};
+use crate::model::track_point::TrackPoint;
+use crate::service::audit_service::{snapshot, AuditService};
+use crate::service::live_service::LiveHub;
+use crate::service::soft_delete::{active, list_trash, purge_expired, restore_one, soft_delete_one};
+
+const DEFAULT_ALERT_LIMIT: i64 = 100;
+const MAX_ALERT_LIMIT: i64 = 1000;
+
+pub struct GeofenceService {
+    pub collection: Collection<Geofence>,
+    pub alerts: Collection<GeofenceAlert>,
+    pub audit: Arc<AuditService>,
+    live: Arc<LiveHub>,
+}
+
+impl GeofenceService {
+    pub fn new(collection: Collection<Geofence>, alerts: Collection<GeofenceAlert>, audit: Arc<AuditService>, live: Arc<LiveHub>) -> Self {
+        Self { collection, alerts, audit, live }
+    }
+
+    pub async fn ensure_indexes(&self) -> mongodb::error::Result<()> {
+        self.alerts
+            .create_index(IndexModel::builder().keys(doc! { "trackId": 1, "exitedAt": 1 }).build())
+            .await?;
+        self.alerts
+            .create_index(IndexModel::builder().keys(doc! { "orgId": 1, "windFarmId": 1, "enteredAt": -1 }).build())
+            .await?;
+        Ok(())
+    }
+
+    pub async fn create(&self, mut geofence: Geofence, ctx: &AuditContext) -> Result<(), AppError> {
+        geofence.set_tenant(&ctx.tenant);
+        let after = snapshot(&geofence);
+        let id = geofence.id;
+        self.collection.insert_one(geofence).await?;
+        self.audit.record(ctx.entry("create", "geofence", Some(id.to_hex()), None, after)).await?;
+        Ok(())
+    }
+
+    pub async fn get(&self, tenant: &Tenant, id: &str) -> Result<Option<Geofence>, AppError> {
+        let obj_id = parse_id(id)?;
+        Ok(self.collection.find_one(active(tenant.scope(doc! {"_id": obj_id}))).await?)
+    }
+
+    pub async fn list(&self, tenant: &Tenant) -> mongodb::error::Result<Vec<Geofence>> {
+        self.collection
+            .find(active(tenant.scope(doc! {})))
+            .sort(doc! { "createdAt": 1 })
+            .await?
+            .try_collect()
+            .await
+    }
+
+    // 替换名称、类型、形状和启用状态；进行中的告警保持不变，由后续的点决定何时结束
+    pub async fn update(&self, id: &str, dto: GeofenceRequestDto, ctx: &AuditContext) -> Result<Option<Geofence>, AppError> {
+        let Some(before) = self.get(&ctx.tenant, id).await? else {
+            return Ok(None);
+        };
+        let mut geofence = Geofence::from_request(dto);
+        geofence.id = before.id;
+        geofence.created_at = before.created_at;
+        geofence.set_tenant(&ctx.tenant);
+        let after = snapshot(&geofence);
+        self.collection.replace_one(active(ctx.tenant.scope(doc! {"_id": before.id})), &geofence).await?;
+        self.audit.record(ctx.entry("update", "geofence", Some(before.id.to_hex()), snapshot(&before), after)).await?;
+        Ok(Some(geofence))
+    }
+
+    // 软删除，移入回收站；已有的告警记录保留
+    pub async fn delete(&self, id: &str, ctx: &AuditContext) -> Result<(), AppError> {
+        let obj_id = parse_id(id)?;
+        let deleted = soft_delete_one(&self.collection, ctx.tenant.scope(doc! {"_id": obj_id}), ctx).await?;
+        if deleted.is_some() {
+            let after = doc! { "deletedBy": ctx.actor.clone() };
+            self.audit.record(ctx.entry("delete", "geofence", Some(obj_id.to_hex()), None, Some(after))).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn restore(&self, id: &str, ctx: &AuditContext) -> Result<Option<Geofence>, AppError> {
+        let obj_id = parse_id(id)?;
+        let restored = restore_one(&self.collection, ctx.tenant.scope(doc! {"_id": obj_id})).await?;
+        if restored.is_some() {
+            self.audit.record(ctx.entry("restore", "geofence", Some(obj_id.to_hex()), None, None)).await?;
+        }
+        Ok(restored)
+    }
+
+    pub async fn get_trash(&self, tenant: &Tenant) -> mongodb::error::Result<Vec<Geofence>> {
+        list_trash(&self.collection, tenant.scope(doc! {})).await
+    }
+
+    // 由定时任务调用，清除超过保留期的回收站围栏
+    pub async fn purge_expired(&self, retention: Duration) -> mongodb::error::Result<usize> {
+        let purged = purge_expired(&self.collection, retention, |item| item.id).await?;
+        for geofence in &purged {
+            let ctx = AuditContext::system("purge_expired", geofence.tenant());
+            self.audit.record(ctx.entry("purge", "geofence", Some(geofence.id.to_hex()), snapshot(geofence), None)).await?;
+        }
+        Ok(purged.len())
+    }
+
+    pub async fn alerts(&self, tenant: &Tenant, query: AlertQuery) -> Result<Vec<GeofenceAlert>, AppError> {
+        let mut filter = tenant.scope(doc! {});
+        if let Some(track_id) = &query.track_id {
+            filter.insert("trackId", parse_id(track_id)?);
+        }
+        if let Some(geofence_id) = &query.geofence_id {
+            filter.insert("geofenceId", parse_id(geofence_id)?);
+        }
+        match query.open {
+            Some(true) => { filter.insert("exitedAt", Bson::Null); }
+            Some(false) => { filter.insert("exitedAt", doc! { "$ne": Bson::Null }); }
+            None => {}
+        }
+        let mut entered = doc! {};
+        if let Some(from) = query.from {
+            entered.insert("$gte", DateTime::from_chrono(from));
+        }
+        if let Some(to) = query.to {
+            entered.insert("$lte", DateTime::from_chrono(to));
+        }
+        if !entered.is_empty() {
+            filter.insert("enteredAt", entered);
+        }
+        let limit = query.limit.unwrap_or(DEFAULT_ALERT_LIMIT).clamp(1, MAX_ALERT_LIMIT);
+        Ok(self.alerts.find(filter).sort(doc! { "enteredAt": -1 }).limit(limit).await?.try_collect().await?)
+    }
+
+    // 逐点判定新追加的点。每条航迹与每个围栏最多有一条进行中的告警：
+    // 从合规变为违规时创建告警，恢复合规时写入离开时间，变化的告警实时推送给订阅者。
+    // 调用方需保证同一航迹的判定按追加顺序串行执行
+    pub async fn evaluate(&self, tenant: &Tenant, track_id: ObjectId, drone_id: Option<&str>, start_index: u64, points: &[TrackPoint]) -> Result<(), AppError> {
+        if points.is_empty() {
+            return Ok(());
+        }
+        let geofences: Vec<Geofence> = self.collection
+            .find(active(tenant.scope(doc! { "enabled": true })))
+            .await?
+            .try_collect()
+            .await?;
+        if geofences.is_empty() {
+            return Ok(());
+        }
+        let existing: Vec<GeofenceAlert> = self.alerts
+            .find(tenant.scope(doc! { "trackId": track_id, "exitedAt": Bson::Null }))
+            .await?
+            .try_collect()
+            .await?;
+        // 进行中的告警，bool 表示是否已保存到数据库
+        let mut open: HashMap<ObjectId, (GeofenceAlert, bool)> = existing.into_iter().map(|alert| (alert.geofence_id, (alert, true))).collect();
+        let mut closed = Vec::new();
+        let received_at = DateTime::now();
+        // 被噪声过滤拒绝的点不参与判定，避免一个跳点产生误报
+        for (offset, point) in points.iter().enumerate().filter(|(_, point)| point.accepted()) {
+            let index = start_index + offset as u64;
+            let position = point.position();
+            let time = point.time.unwrap_or(received_at);
+            for geofence in &geofences {
+                match (geofence.breached_by(position), open.contains_key(&geofence.id)) {
+                    (true, false) => {
+                        let alert = GeofenceAlert {
+                            id: ObjectId::new(),
+                            org_id: tenant.org_id.clone(),
+                            wind_farm_id: tenant.wind_farm_id.clone(),
+                            geofence_id: geofence.id,
+                            geofence_name: geofence.name.clone(),
+                            kind: geofence.kind,
+                            track_id,
+                            drone_id: drone_id.map(str::to_string),
+                            entered_at: time,
+                            entry_index: index,
+                            entry_position: position,
+                            exited_at: None,
+                            exit_index: None,
+                            exit_position: None,
+                        };
+                        tracing::warn!("航迹 {} 在第 {} 个点违反围栏 {}（{:?}）", track_id.to_hex(), index, geofence.name, geofence.kind);
+                        open.insert(geofence.id, (alert, false));
+                    }
+                    (false, true) => {
+                        if let Some((mut alert, stored)) = open.remove(&geofence.id) {
+                            alert.exited_at = Some(time);
+                            alert.exit_index = Some(index);
+                            alert.exit_position = Some(position);
+                            closed.push((alert, stored));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut changed = Vec::new();
+        let mut inserts = Vec::new();
+        for (alert, stored) in closed {
+            if stored {
+                let update = doc! { "$set": {
+                    "exitedAt": alert.exited_at,
+                    "exitIndex": alert.exit_index.map(|i| i as i64),
+                    "exitPosition": alert.exit_position.map(|p| vec![p[0], p[1]]),
+                } };
+                self.alerts.update_one(doc! { "_id": alert.id }, update).await?;
+            } else {
+                inserts.push(alert.clone());
+            }
+            changed.push(alert);
+        }
+        for (alert, stored) in open.into_values() {
+            if !stored {
+                inserts.push(alert.clone());
+                changed.push(alert);
+            }
+        }
+        if !inserts.is_empty() {
+            self.alerts.insert_many(inserts).await?;
+        }
+        changed.sort_by_key(|alert| (alert.entry_index, alert.exit_index));
+        for alert in changed {
+            let dto = GeofenceAlertDto::from(alert);
+            self.live.publish(tenant, track_id, &LiveMessage::Alert(&dto));
+        }
+        Ok(())
+    }
+}
+
+fn parse_id(id: &str) -> Result<ObjectId, AppError> {
+    ObjectId::parse_str(id).map_err(|e| AppError::BadRequest(format!("Invalid ObjectId: {}", e)))
+}
@@ -0,0 +1,228 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use bson::oid::ObjectId;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use crate::error::AppError;
+use crate::geo::crs::{Crs, Projection};
+use crate::model::live::{LiveMessage, LiveQuery, TrackAppendedDto};
+use crate::model::ship_track::{ShipTrack, ShipTrackResponseDto, TrackResponseQuery};
+use crate::model::tenant::Tenant;
+use crate::model::track_point::TrackPoint;
+use crate::service::ship_track_service::ShipTrackService;
+
+// 订阅全部航迹时的默认活跃窗口，以及快照中最多包含的航迹数
+const DEFAULT_ACTIVE_MINUTES: i64 = 10;
+const MAX_LIVE_TRACKS: i64 = 100;
+
+// 广播给所有订阅者的事件，消息在发布时序列化一次，各连接共用
+pub struct LiveEvent {
+    pub tenant: Tenant,
+    pub track_id: ObjectId,
+    // 事件包含的点的下标上界（不含），用于跳过快照中已有的点；不含点的事件为 None
+    pub end_index: Option<u64>,
+    pub json: Arc<str>,
+    // 追加事件的原始数据，订阅者要求其他坐标系时据此重新生成消息
+    pub appended: Option<TrackAppendedDto>,
+}
+
+// 实时推送的进程内广播通道。缓冲区满时最慢的订阅者会掉队，由订阅端重新同步，不会阻塞写入
+pub struct LiveHub {
+    sender: broadcast::Sender<Arc<LiveEvent>>,
+}
+
+impl LiveHub {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity.max(1));
+        Self { sender }
+    }
+
+    pub fn has_subscribers(&self) -> bool {
+        self.sender.receiver_count() > 0
+    }
+
+    pub fn publish(&self, tenant: &Tenant, track_id: ObjectId, message: &LiveMessage) {
+        self.send(tenant, track_id, None, message, None);
+    }
+
+    pub fn publish_append(&self, tenant: &Tenant, appended: TrackAppendedDto) {
+        let end_index = appended.start_index + appended.points.len() as u64;
+        self.send(tenant, appended.track_id, Some(end_index), &LiveMessage::Append(&appended), Some(appended.clone()));
+    }
+
+    fn send(&self, tenant: &Tenant, track_id: ObjectId, end_index: Option<u64>, message: &LiveMessage, appended: Option<TrackAppendedDto>) {
+        if !self.has_subscribers() {
+            return;
+        }
+        match serde_json::to_string(message) {
+            Ok(json) => {
+                // 没有订阅者时发送失败，忽略即可
+                let _ = self.sender.send(Arc::new(LiveEvent { tenant: tenant.clone(), track_id, end_index, json: json.into(), appended }));
+            }
+            Err(e) => tracing::error!("序列化实时消息失败: {:?}", e),
+        }
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<Arc<LiveEvent>> {
+        self.sender.subscribe()
+    }
+}
+
+// 一个 WebSocket / SSE 连接的订阅状态。
+// 先订阅广播再读取快照，快照之后写入的批次不会丢失；快照已包含的批次按下标跳过。
+pub struct LiveSubscription {
+    service: Arc<ShipTrackService>,
+    tenant: Tenant,
+    track_id: Option<ObjectId>,
+    compact: bool,
+    crs: Crs,
+    raw: bool,
+    active_minutes: i64,
+    receiver: broadcast::Receiver<Arc<LiveEvent>>,
+    // 已发送给客户端的各航迹点数，以及各航迹的输出投影（UTM 带号随快照固定）
+    known: HashMap<ObjectId, u64>,
+    projections: HashMap<ObjectId, Projection>,
+    // 需要重新发送全量快照 / 需要补发快照的新航迹。
+    // 先记录再读取数据库，连接在读取途中被取消时下次仍会补发
+    resync: bool,
+    unseen: Vec<ObjectId>,
+    pending: VecDeque<String>,
+}
+
+impl LiveSubscription {
+    // 建立订阅并准备好首个快照；订阅单条航迹且航迹不存在时返回 404
+    pub async fn open(service: Arc<ShipTrackService>, tenant: Tenant, query: LiveQuery) -> Result<Self, AppError> {
+        let track_id = query.track_id
+            .map(|id| ObjectId::parse_str(&id).map_err(|e| AppError::BadRequest(format!("Invalid ObjectId: {}", e))))
+            .transpose()?;
+        let active_minutes = query.active_minutes.unwrap_or(DEFAULT_ACTIVE_MINUTES);
+        if active_minutes <= 0 {
+            return Err(AppError::BadRequest("activeMinutes must be positive".to_string()));
+        }
+        let mut subscription = LiveSubscription {
+            receiver: service.live.subscribe(),
+            service,
+            tenant,
+            track_id,
+            compact: query.compact,
+            crs: query.crs,
+            raw: query.raw,
+            active_minutes,
+            known: HashMap::new(),
+            projections: HashMap::new(),
+            resync: true,
+            unseen: Vec::new(),
+            pending: VecDeque::new(),
+        };
+        subscription.refresh().await?;
+        if track_id.is_some() && subscription.known.is_empty() {
+            return Err(AppError::NotFound("Track not found".to_string()));
+        }
+        Ok(subscription)
+    }
+
+    // 下一条要发送给客户端的 JSON 文本；广播通道关闭时返回 None
+    pub async fn next(&mut self) -> Result<Option<String>, AppError> {
+        loop {
+            if let Some(message) = self.pending.pop_front() {
+                return Ok(Some(message));
+            }
+            if self.resync || !self.unseen.is_empty() {
+                self.refresh().await?;
+                continue;
+            }
+            match self.receiver.recv().await {
+                Ok(event) => {
+                    if let Some(json) = self.accept(&event)? {
+                        return Ok(Some(json));
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    // 掉队期间的批次已无法补发，通知客户端后重新发送全量快照
+                    self.resync = true;
+                    return to_json(&LiveMessage::Lagged { skipped }).map(Some);
+                }
+                Err(RecvError::Closed) => return Ok(None),
+            }
+        }
+    }
+
+    // 过滤广播事件：其他租户、其他航迹和快照中已包含的批次都不发送；告警等不含点的事件直接发送
+    fn accept(&mut self, event: &LiveEvent) -> Result<Option<String>, AppError> {
+        if event.tenant != self.tenant || self.track_id.is_some_and(|id| id != event.track_id) {
+            return Ok(None);
+        }
+        let Some(end_index) = event.end_index else {
+            return Ok(Some(event.json.to_string()));
+        };
+        let Some(known) = self.known.get_mut(&event.track_id) else {
+            // 订阅后新出现的航迹补发一次快照，快照读取在事件之后，已包含本批次的点
+            if !self.unseen.contains(&event.track_id) {
+                self.unseen.push(event.track_id);
+            }
+            return Ok(None);
+        };
+        if end_index <= *known {
+            return Ok(None);
+        }
+        *known = end_index;
+        // 广播的消息是包含被拒绝点的 WGS-84 坐标，需要去掉被拒绝的点或订阅其他坐标系时重新生成
+        let Some(appended) = &event.appended else {
+            return Ok(Some(event.json.to_string()));
+        };
+        let projection = self.projections.get(&event.track_id).filter(|projection| !projection.is_identity());
+        let drop_rejected = !self.raw && appended.points.iter().any(|point| point.rejected.is_some());
+        if projection.is_none() && !drop_rejected {
+            return Ok(Some(event.json.to_string()));
+        }
+        let mut appended = appended.clone();
+        if drop_rejected {
+            appended.points.retain(|point| point.rejected.is_none());
+        }
+        if let Some(projection) = projection {
+            appended.points.iter_mut().for_each(|point| point.project(projection));
+        }
+        to_json(&LiveMessage::Append(&appended)).map(Some)
+    }
+
+    async fn refresh(&mut self) -> Result<(), AppError> {
+        let full = self.resync;
+        let mut tracks = if full {
+            match self.track_id {
+                Some(id) => self.service.get_many(&self.tenant, &[id]).await?,
+                None => {
+                    let since = chrono::Utc::now() - chrono::Duration::minutes(self.active_minutes);
+                    self.service.get_active(&self.tenant, since, MAX_LIVE_TRACKS).await?
+                }
+            }
+        } else {
+            self.service.get_many(&self.tenant, &self.unseen).await?
+        };
+        self.resync = false;
+        self.unseen.clear();
+        if full {
+            self.known.clear();
+            self.projections.clear();
+        } else if tracks.is_empty() {
+            return Ok(());
+        }
+        if !self.raw {
+            self.service.denoise(&self.tenant, &mut tracks).await?;
+        }
+        let query = TrackResponseQuery { compact: self.compact, simplify: None, crs: self.crs, raw: self.raw };
+        let tracks: Vec<ShipTrackResponseDto> = tracks
+            .into_iter()
+            .map(|track: ShipTrack| {
+                self.known.insert(track.id, track.total_points as u64);
+                self.projections.insert(track.id, self.crs.projection(track.coordinates.first().map(TrackPoint::position)));
+                ShipTrackResponseDto::new(track, &query)
+            })
+            .collect();
+        self.pending.push_back(to_json(&LiveMessage::Snapshot { tracks })?);
+        Ok(())
+    }
+}
+
+fn to_json(message: &LiveMessage) -> Result<String, AppError> {
+    serde_json::to_string(message).map_err(|e| AppError::InternalServerError(format!("序列化实时消息失败: {}", e)))
+}
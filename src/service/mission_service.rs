@@ -0,0 +1,155 @@
+use std::sync::Arc;
+use std::time::Duration;
+use bson::doc;
+use futures::TryStreamExt;
+use mongodb::bson::oid::ObjectId;
+use mongodb::Collection;
+use crate::error::AppError;
+use crate::model::audit_log::AuditContext;
+use crate::model::flight::Flight;
+use crate::model::mission::{AnalysisQuery, Mission, MissionAnalysisDto, MissionRequestDto};
+use crate::model::tenant::{Tenant, TenantOwned};
+use crate::service::audit_service::{snapshot, AuditService};
+use crate::service::flight_service::FlightService;
+use crate::service::ship_track_service::ShipTrackService;
+use crate::service::soft_delete::{active, list_trash, purge_expired, restore_one, soft_delete_one};
+
+// 不指定航迹时，一次最多分析最近的这么多条关联航迹
+const MAX_ANALYSIS_TRACKS: i64 = 20;
+
+pub struct MissionService {
+    pub collection: Collection<Mission>,
+    pub audit: Arc<AuditService>,
+    tracks: Arc<ShipTrackService>,
+    flights: Arc<FlightService>,
+}
+
+impl MissionService {
+    pub fn new(collection: Collection<Mission>, audit: Arc<AuditService>, tracks: Arc<ShipTrackService>, flights: Arc<FlightService>) -> Self {
+        Self { collection, audit, tracks, flights }
+    }
+
+    pub async fn create(&self, mut mission: Mission, ctx: &AuditContext) -> Result<(), AppError> {
+        mission.set_tenant(&ctx.tenant);
+        let after = snapshot(&mission);
+        let id = mission.id;
+        self.collection.insert_one(mission).await?;
+        self.audit.record(ctx.entry("create", "mission", Some(id.to_hex()), None, after)).await?;
+        Ok(())
+    }
+
+    pub async fn get(&self, tenant: &Tenant, id: &str) -> Result<Option<Mission>, AppError> {
+        let obj_id = parse_id(id)?;
+        Ok(self.collection.find_one(active(tenant.scope(doc! {"_id": obj_id}))).await?)
+    }
+
+    pub async fn list(&self, tenant: &Tenant) -> mongodb::error::Result<Vec<Mission>> {
+        self.collection
+            .find(active(tenant.scope(doc! {})))
+            .sort(doc! { "createdAt": -1 })
+            .await?
+            .try_collect()
+            .await
+    }
+
+    // 替换计划内容；已关联的航迹和飞行记录不变，之后的分析按新计划进行
+    pub async fn update(&self, id: &str, dto: MissionRequestDto, ctx: &AuditContext) -> Result<Option<Mission>, AppError> {
+        let Some(before) = self.get(&ctx.tenant, id).await? else {
+            return Ok(None);
+        };
+        let mut mission = Mission::from_request(dto).map_err(AppError::BadRequest)?;
+        mission.id = before.id;
+        mission.created_at = before.created_at;
+        mission.set_tenant(&ctx.tenant);
+        let after = snapshot(&mission);
+        self.collection.replace_one(active(ctx.tenant.scope(doc! {"_id": before.id})), &mission).await?;
+        self.audit.record(ctx.entry("update", "mission", Some(before.id.to_hex()), snapshot(&before), after)).await?;
+        Ok(Some(mission))
+    }
+
+    // 软删除，移入回收站；航迹和飞行记录上的关联保留，恢复后仍然有效
+    pub async fn delete(&self, id: &str, ctx: &AuditContext) -> Result<(), AppError> {
+        let obj_id = parse_id(id)?;
+        let deleted = soft_delete_one(&self.collection, ctx.tenant.scope(doc! {"_id": obj_id}), ctx).await?;
+        if deleted.is_some() {
+            let after = doc! { "deletedBy": ctx.actor.clone() };
+            self.audit.record(ctx.entry("delete", "mission", Some(obj_id.to_hex()), None, Some(after))).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn restore(&self, id: &str, ctx: &AuditContext) -> Result<Option<Mission>, AppError> {
+        let obj_id = parse_id(id)?;
+        let restored = restore_one(&self.collection, ctx.tenant.scope(doc! {"_id": obj_id})).await?;
+        if restored.is_some() {
+            self.audit.record(ctx.entry("restore", "mission", Some(obj_id.to_hex()), None, None)).await?;
+        }
+        Ok(restored)
+    }
+
+    pub async fn get_trash(&self, tenant: &Tenant) -> mongodb::error::Result<Vec<Mission>> {
+        list_trash(&self.collection, tenant.scope(doc! {})).await
+    }
+
+    // 由定时任务调用，清除超过保留期的回收站任务
+    pub async fn purge_expired(&self, retention: Duration) -> mongodb::error::Result<usize> {
+        let purged = purge_expired(&self.collection, retention, |item| item.id).await?;
+        for mission in &purged {
+            let ctx = AuditContext::system("purge_expired", mission.tenant());
+            self.audit.record(ctx.entry("purge", "mission", Some(mission.id.to_hex()), snapshot(mission), None)).await?;
+        }
+        Ok(purged.len())
+    }
+
+    pub async fn link_track(&self, id: &str, track_id: &str, ctx: &AuditContext) -> Result<(), AppError> {
+        let mission = self.require(&ctx.tenant, id).await?;
+        if !self.tracks.link_mission(parse_id(track_id)?, mission.id, ctx).await? {
+            return Err(AppError::NotFound("Track not found".to_string()));
+        }
+        Ok(())
+    }
+
+    pub async fn unlink_track(&self, id: &str, track_id: &str, ctx: &AuditContext) -> Result<(), AppError> {
+        self.tracks.unlink_mission(parse_id(track_id)?, parse_id(id)?, ctx).await?;
+        Ok(())
+    }
+
+    // 关联飞行记录时，它的航迹一并关联到任务
+    pub async fn link_flight(&self, id: &str, flight_id: &str, ctx: &AuditContext) -> Result<Flight, AppError> {
+        let mission = self.require(&ctx.tenant, id).await?;
+        let flight = self.flights.set_mission(flight_id, Some(mission.id), None, ctx).await?
+            .ok_or_else(|| AppError::NotFound("Flight not found".to_string()))?;
+        self.tracks.link_mission(flight.track_id, mission.id, ctx).await?;
+        Ok(flight)
+    }
+
+    pub async fn unlink_flight(&self, id: &str, flight_id: &str, ctx: &AuditContext) -> Result<(), AppError> {
+        let mission_id = parse_id(id)?;
+        if let Some(flight) = self.flights.set_mission(flight_id, None, Some(mission_id), ctx).await? {
+            self.tracks.unlink_mission(flight.track_id, mission_id, ctx).await?;
+        }
+        Ok(())
+    }
+
+    // 把实际航迹与任务计划对比；指定的航迹不必事先关联到任务
+    pub async fn analyze(&self, tenant: &Tenant, id: &str, query: AnalysisQuery) -> Result<Vec<MissionAnalysisDto>, AppError> {
+        let mission = self.require(tenant, id).await?;
+        let tracks = match &query.track_id {
+            Some(track_id) => {
+                let track = self.tracks.get(tenant, &parse_id(track_id)?.to_hex()).await?
+                    .ok_or_else(|| AppError::NotFound("Track not found".to_string()))?;
+                vec![track]
+            }
+            None => self.tracks.get_by_mission(tenant, mission.id, MAX_ANALYSIS_TRACKS).await?,
+        };
+        Ok(tracks.iter().map(|track| mission.analyze(track)).collect())
+    }
+
+    async fn require(&self, tenant: &Tenant, id: &str) -> Result<Mission, AppError> {
+        self.get(tenant, id).await?.ok_or_else(|| AppError::NotFound("Mission not found".to_string()))
+    }
+}
+
+fn parse_id(id: &str) -> Result<ObjectId, AppError> {
+    ObjectId::parse_str(id).map_err(|e| AppError::BadRequest(format!("Invalid ObjectId: {}", e)))
+}
@@ -1,4 +1,11 @@
 pub(crate) mod ship_track_service;
 pub mod report_raw_service;
 pub mod flight_service;
-mod ai_service;
\ No newline at end of file
+mod ai_service;
+pub mod audit_service;
+pub mod tenant_service;
+pub mod soft_delete;
+pub mod share_service;
+pub mod live_service;
+pub mod geofence_service;
+pub mod mission_service;
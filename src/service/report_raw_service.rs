@@ -1,59 +1,78 @@
-use bson::{doc, DateTime};
+use bson::{doc, Bson, DateTime};
 use futures::StreamExt;
 use mongodb::Collection;
 use mongodb::options::FindOneOptions;
-use crate::model::report_raw::{ReportRaw, ReportRawRequestDto, ReportRawResponseDto};
+use crate::model::audit_log::AuditContext;
+use crate::model::report_raw::{ReportBulkDeleteFilter, ReportRaw, ReportRawRequestDto};
+use crate::model::trash::BulkDeleteRequestDto;
+use crate::model::tenant::{Tenant, TenantOwned};
 use crate::error::AppError;
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
 use uuid::Uuid;
 use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 use tracing::info;
-use axum::extract::multipart::Field;
 use bson::oid::ObjectId;
 use crate::service::ai_service::{AIPaylod, AiService, Message};
+use crate::service::audit_service::{snapshot, AuditService};
+use crate::service::soft_delete::{active, list_trash, purge_expired, restore_one, soft_delete_one, BulkDelete};
+use crate::service::tenant_service::TenantService;
 
+pub const UPLOAD_BASE_DIR: &str = "/var/uploads/images";
 pub struct ReportRawService{
     pub collection: Collection<ReportRaw>,
+    pub audit: Arc<AuditService>,
+    pub tenants: Arc<TenantService>,
+    bulk_delete: BulkDelete,
 }
 
 impl ReportRawService {
-    pub fn new(collection: Collection<ReportRaw>) -> Self {
-        ReportRawService { collection }
+    pub fn new(collection: Collection<ReportRaw>, audit: Arc<AuditService>, tenants: Arc<TenantService>) -> Self {
+        ReportRawService { collection, audit, tenants, bulk_delete: BulkDelete::default() }
     }
 
-    pub async fn insert_one(&self, report_raw_request: ReportRawRequestDto) -> mongodb::error::Result<()> {
-        let report_raw = ReportRaw::from(report_raw_request);
+    pub async fn insert_one(&self, report_raw_request: ReportRawRequestDto, ctx: &AuditContext) -> mongodb::error::Result<()> {
+        let mut report_raw = ReportRaw::from(report_raw_request);
+        report_raw.set_tenant(&ctx.tenant);
+        let after = snapshot(&report_raw);
+        let id = report_raw.id;
         self.collection.insert_one(report_raw).await?;
-        Ok(())
+        self.audit.record(ctx.entry("create", "report", Some(id.to_hex()), None, after)).await
     }
 
-    pub async fn get_latest(&self) -> mongodb::error::Result<Option<ReportRaw>> {
+    pub async fn get_latest(&self, tenant: &Tenant) -> mongodb::error::Result<Option<ReportRaw>> {
         let find_options = FindOneOptions::builder().sort(doc! {"createdAt": -1}).build();
-        self.collection.find_one(doc! {}).with_options(find_options).await
+        self.collection.find_one(active(tenant.scope(doc! {}))).with_options(find_options).await
     }
-    pub async fn get_by_id(&self, id: &str) -> mongodb::error::Result<Option<ReportRaw>> {
+    pub async fn get_by_id(&self, tenant: &Tenant, id: &str) -> mongodb::error::Result<Option<ReportRaw>> {
         let obj_id = bson::oid::ObjectId::parse_str(id).map_err(|e| mongodb::error::Error::custom(format!("Invalid ObjectId: {}", e)))?;
-        self.collection.find_one(doc! {"_id": obj_id}).await
+        self.collection.find_one(active(tenant.scope(doc! {"_id": obj_id}))).await
     }
-    pub async fn get_all(&self) -> mongodb::error::Result<Vec<ReportRaw>> {
-        let mut cursor = self.collection.find(doc! {}).await?;
+    pub async fn get_all(&self, tenant: &Tenant) -> mongodb::error::Result<Vec<ReportRaw>> {
+        let mut cursor = self.collection.find(active(tenant.scope(doc! {}))).await?;
         let mut results = Vec::new();
         while let Some(doc) = cursor.next().await {
             match doc {
                 Ok(report_raw) => results.push(report_raw),
-                Err(e) => return Err(e.into()),
+                Err(e) => return Err(e),
             }
         }
         Ok(results)
     }
-    pub async fn delete_by_id(&self, id: &str) -> Result<(),AppError> {
+    pub async fn delete_by_id(&self, id: &str, ctx: &AuditContext) -> Result<(),AppError> {
         let obj_id = ObjectId::parse_str(id).map_err(|e| {
             AppError::BadRequest(format!("Invalid ObjectId: {}", e))
         })?;
-        self.collection.delete_one(doc! {"_id": obj_id}).await.map_err(|e| {
+        // 软删除，图片文件保留到回收站清除时再删除
+        let deleted = soft_delete_one(&self.collection, ctx.tenant.scope(doc! {"_id": obj_id}), ctx).await.map_err(|e| {
             AppError::InternalServerError(format!("Failed to delete report: {}", e))
         })?;
+        if deleted.is_some() {
+            let after = doc! { "deletedBy": ctx.actor.clone() };
+            self.audit.record(ctx.entry("delete", "report", Some(obj_id.to_hex()), None, Some(after))).await?;
+        }
         Ok(())
     }
 
@@ -61,14 +80,25 @@ impl ReportRawService {
         &self,
         report_data: ReportRawRequestDto,
         image_files: Vec<(String, String, bytes::Bytes)>, // (filename, content_type, data)
+        ctx: &AuditContext,
     ) -> Result<(serde_json::Value,ObjectId), AppError> {
+        // 检查租户存储配额
+        let upload_bytes: i64 = image_files.iter().map(|(_, _, data)| data.len() as i64).sum();
+        let config = self.tenants.effective_config(&ctx.tenant).await?;
+        if let Some(quota) = config.storage_quota_bytes {
+            let used = self.storage_used(&ctx.tenant).await?;
+            if used + upload_bytes > quota {
+                return Err(AppError::Forbidden(format!(
+                    "Storage quota exceeded: used {} of {} bytes, upload needs {} bytes",
+                    used, quota, upload_bytes
+                )));
+            }
+        }
         let mut image_paths = Vec::new();
         let mut relative_paths = Vec::new(); // 用于存储相对路径
-        let base_upload_dir = "/var/uploads/images";
-        
         // 生成日期文件夹名称 (YYYYMMDD)
         let date_folder = chrono::Utc::now().format("%Y%m%d").to_string();
-        let upload_dir = format!("{}/{}", base_upload_dir, date_folder);
+        let upload_dir = format!("{}/{}", UPLOAD_BASE_DIR, date_folder);
 
         // 确保上传目录存在
         if !Path::new(&upload_dir).exists() {
@@ -85,7 +115,7 @@ impl ReportRawService {
             }
 
             // 生成唯一文件名
-            let extension = file_name.split('.').last().unwrap_or("jpg");
+            let extension = file_name.split('.').next_back().unwrap_or("jpg");
             let unique_filename = format!("{}.{}", Uuid::new_v4(), extension);
             let file_path = format!("{}/{}", upload_dir, unique_filename);
             let relative_path = format!("/{}/{}", date_folder, unique_filename);
@@ -119,6 +149,8 @@ impl ReportRawService {
         // 保存报告到数据库
         let report_raw = ReportRaw{
             id: report_id,
+            org_id: ctx.tenant.org_id.clone(),
+            wind_farm_id: ctx.tenant.wind_farm_id.clone(),
             created_at: DateTime::now(),
             photo_path: relative_paths.join(", "), // 使用相对路径
             detail: report_data.detail,
@@ -127,10 +159,15 @@ impl ReportRawService {
             rust: report_data.rust,
             covering: report_data.covering,
             ai_report: None,
+            storage_bytes: upload_bytes,
+            deleted_at: None,
+            deleted_by: None,
         };
+        let after = snapshot(&report_raw);
         self.collection.insert_one(report_raw).await.map_err(|e| {
             AppError::InternalServerError(format!("Failed to save report: {}", e))
         })?;
+        self.audit.record(ctx.entry("create", "report", Some(report_id.to_hex()), None, after)).await?;
         
         // 返回成功响应
         Ok((serde_json::json!({
@@ -144,11 +181,20 @@ impl ReportRawService {
     // 后台AI分析任务
     pub async fn generate_ai_report_background(
         &self,
+        tenant: Tenant,
         report_id: ObjectId,
         rust: f64,
         covering: f64,
         damage: f64,
     ) {
+        // 提示词和模型按租户配置
+        let config = match self.tenants.effective_config(&tenant).await {
+            Ok(config) => config,
+            Err(e) => {
+                tracing::error!("读取租户配置失败: {:?}", e);
+                return;
+            }
+        };
         let ai_service = AiService::new(
             "https://dashscope.aliyuncs.com/compatible-mode/v1/chat/completions".into(),
             "sk-7d6d33a351fd4d2b9b14dda64062676f".into()
@@ -157,7 +203,7 @@ impl ReportRawService {
         let mut messages = vec![];
         messages.push(Message {
             role: "system".to_string(),
-            content: "你是一个报告智能报告生成体,用户会发送风机叶片检测之后的三个参数,分别为锈蚀情况,覆盖情况,损坏情况,这三个参数均在0到1之间代表百分数,你需要为其生成一份简短的报告以及维修建议,切记不要使用markdown格式".to_string()
+            content: config.ai_system_prompt
         });
         messages.push(Message {
             role: "user".to_string(),
@@ -171,14 +217,14 @@ impl ReportRawService {
 
         let ai_payload = AIPaylod {
             messages,
-            model: "qwen-plus".to_string(),
+            model: config.ai_model,
         };
 
         info!("开始后台AI分析，报告ID: {}", report_id.to_hex());
         match ai_service.analyze_report(ai_payload).await {
             Ok(result) => {
                 info!("AI分析完成，报告ID: {}", report_id.to_hex());
-                if let Err(e) = self.update_ai_report(report_id, result).await {
+                if let Err(e) = self.update_ai_report(tenant, report_id, result).await {
                     tracing::error!("更新AI报告失败: {:?}", e);
                 }
             }
@@ -187,10 +233,11 @@ impl ReportRawService {
             }
         }
     }
-    pub async fn update_ai_report(&self, report_id: ObjectId, ai_report: String) -> Result<(), AppError> {
+    pub async fn update_ai_report(&self, tenant: Tenant, report_id: ObjectId, ai_report: String) -> Result<(), AppError> {
+        let diff = doc! { "aiReport": ai_report.clone() };
         self.collection
             .update_one(
-                doc! {"_id": report_id},
+                active(tenant.scope(doc! {"_id": report_id})),
                 doc! {
                     "$set": {
                         "aiReport": ai_report,
@@ -199,14 +246,92 @@ impl ReportRawService {
             )
             .await
             .map_err(|e| AppError::InternalServerError(format!("Failed to update AI report: {}", e)))?;
+        let ctx = AuditContext::system("generate_ai_report_background", tenant);
+        self.audit.record(ctx.entry("update", "report", Some(report_id.to_hex()), None, Some(diff))).await?;
 
         info!("AI报告已更新到数据库，报告ID: {}", report_id.to_hex());
         Ok(())
     }
-    pub async fn delete_all(&self) -> Result<u64, AppError> {
-        let result = self.collection.delete_many(doc!{}).await.map_err(|e| {
-            AppError::InternalServerError(format!("Failed to delete all reports: {}", e))
+    // 按筛选条件批量软删除，需要两步确认
+    pub async fn bulk_delete(&self, request: BulkDeleteRequestDto<ReportBulkDeleteFilter>, ctx: &AuditContext) -> Result<serde_json::Value, AppError> {
+        let filter = ctx.tenant.scope(Self::bulk_delete_filter(request.filter)?);
+        self.bulk_delete.run(&self.collection, &self.audit, "report", filter, request.confirmation_token, ctx).await
+    }
+
+    fn bulk_delete_filter(filter: ReportBulkDeleteFilter) -> Result<bson::Document, AppError> {
+        let mut query = doc! {};
+        if let Some(ids) = filter.ids {
+            let ids = ids.iter()
+                .map(ObjectId::parse_str)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| AppError::BadRequest(format!("Invalid ObjectId: {}", e)))?;
+            query.insert("_id", doc! { "$in": ids });
+        }
+        let mut created = doc! {};
+        if let Some(from) = filter.created_from {
+            created.insert("$gte", DateTime::from_chrono(from));
+        }
+        if let Some(to) = filter.created_to {
+            created.insert("$lte", DateTime::from_chrono(to));
+        }
+        if !created.is_empty() {
+            query.insert("createdAt", created);
+        }
+        if let Some(title) = filter.title {
+            query.insert("title", title);
+        }
+        if query.is_empty() {
+            return Err(AppError::BadRequest("Bulk delete requires at least one filter".to_string()));
+        }
+        Ok(query)
+    }
+
+    pub async fn restore(&self, id: &str, ctx: &AuditContext) -> Result<Option<ReportRaw>, AppError> {
+        let obj_id = ObjectId::parse_str(id).map_err(|e| {
+            AppError::BadRequest(format!("Invalid ObjectId: {}", e))
         })?;
-        Ok(result.deleted_count)
+        let restored = restore_one(&self.collection, ctx.tenant.scope(doc! {"_id": obj_id})).await?;
+        if restored.is_some() {
+            self.audit.record(ctx.entry("restore", "report", Some(obj_id.to_hex()), None, None)).await?;
+        }
+        Ok(restored)
+    }
+
+    pub async fn get_trash(&self, tenant: &Tenant) -> mongodb::error::Result<Vec<ReportRaw>> {
+        list_trash(&self.collection, tenant.scope(doc! {})).await
+    }
+
+    // 由定时任务调用，清除超过保留期的回收站报告及其图片文件
+    pub async fn purge_expired(&self, retention: Duration) -> mongodb::error::Result<usize> {
+        let purged = purge_expired(&self.collection, retention, |item| item.id).await?;
+        for report in &purged {
+            for relative_path in report.photo_path.split(", ").filter(|p| !p.is_empty()) {
+                let file_path = format!("{}{}", UPLOAD_BASE_DIR, relative_path);
+                if let Err(e) = fs::remove_file(&file_path).await {
+                    tracing::warn!("删除图片文件失败 {}: {:?}", file_path, e);
+                }
+            }
+            let ctx = AuditContext::system("purge_expired", report.tenant());
+            self.audit.record(ctx.entry("purge", "report", Some(report.id.to_hex()), snapshot(report), None)).await?;
+        }
+        Ok(purged.len())
     }
-}
\ No newline at end of file
+
+    // 统计租户已上传图片占用的存储
+    pub async fn storage_used(&self, tenant: &Tenant) -> mongodb::error::Result<i64> {
+        let pipeline = vec![
+            doc! { "$match": tenant.scope(doc! {}) },
+            doc! { "$group": { "_id": null, "total": { "$sum": "$storageBytes" } } },
+        ];
+        let mut cursor = self.collection.aggregate(pipeline).await?;
+        let total = match cursor.next().await {
+            Some(result) => match result?.get("total") {
+                Some(Bson::Int64(v)) => *v,
+                Some(Bson::Int32(v)) => *v as i64,
+                _ => 0,
+            },
+            None => 0,
+        };
+        Ok(total)
+    }
+}
@@ -0,0 +1,205 @@
+use std::sync::Arc;
+use bson::{doc, DateTime};
+use chrono::Utc;
+use futures::TryStreamExt;
+use hmac::{Hmac, Mac};
+use mongodb::options::FindOptions;
+use mongodb::Collection;
+use sha2::Sha256;
+use crate::error::AppError;
+use crate::model::audit_log::AuditContext;
+use crate::model::report_raw::ReportRaw;
+use crate::model::share_link::{ShareAccessLog, ShareLink, ShareLinkCreatedDto, ShareLinkRequestDto};
+use crate::model::tenant::Tenant;
+use crate::service::audit_service::{snapshot, AuditService};
+use crate::service::report_raw_service::{ReportRawService, UPLOAD_BASE_DIR};
+use bson::oid::ObjectId;
+
+const DEFAULT_EXPIRES_IN_HOURS: i64 = 72;
+const MAX_EXPIRES_IN_HOURS: i64 = 24 * 30;
+
+// 访问分享链接的外部用户信息，用于访问记录
+pub struct ShareViewer {
+    pub client_ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+pub struct ShareService {
+    pub collection: Collection<ShareLink>,
+    pub access_log: Collection<ShareAccessLog>,
+    pub reports: Arc<ReportRawService>,
+    pub audit: Arc<AuditService>,
+    secret: Vec<u8>,
+}
+
+impl ShareService {
+    pub fn new(
+        collection: Collection<ShareLink>,
+        access_log: Collection<ShareAccessLog>,
+        reports: Arc<ReportRawService>,
+        audit: Arc<AuditService>,
+        secret: Vec<u8>,
+    ) -> Self {
+        Self { collection, access_log, reports, audit, secret }
+    }
+
+    // 令牌格式: {分享ID}.{过期时间毫秒}.{HMAC-SHA256 签名}
+    fn sign(&self, id: &ObjectId, expires_at: DateTime) -> String {
+        let payload = format!("{}.{}", id.to_hex(), expires_at.timestamp_millis());
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts any key length");
+        mac.update(payload.as_bytes());
+        let signature: String = mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+        format!("{}.{}", payload, signature)
+    }
+
+    pub async fn create(&self, dto: ShareLinkRequestDto, ctx: &AuditContext) -> Result<ShareLinkCreatedDto, AppError> {
+        if dto.report_ids.is_empty() {
+            return Err(AppError::BadRequest("At least one report is required".to_string()));
+        }
+        let hours = dto.expires_in_hours.unwrap_or(DEFAULT_EXPIRES_IN_HOURS);
+        if !(1..=MAX_EXPIRES_IN_HOURS).contains(&hours) {
+            return Err(AppError::BadRequest(format!("expiresInHours must be between 1 and {}", MAX_EXPIRES_IN_HOURS)));
+        }
+        let mut report_ids = Vec::with_capacity(dto.report_ids.len());
+        for id in &dto.report_ids {
+            // 只能分享本租户下存在的报告
+            let report = self.reports.get_by_id(&ctx.tenant, id).await?
+                .ok_or_else(|| AppError::BadRequest(format!("Report not found: {}", id)))?;
+            report_ids.push(report.id);
+        }
+
+        let link = ShareLink {
+            id: ObjectId::new(),
+            org_id: ctx.tenant.org_id.clone(),
+            wind_farm_id: ctx.tenant.wind_farm_id.clone(),
+            report_ids,
+            note: dto.note,
+            created_by: ctx.actor.clone(),
+            created_at: Utc::now().into(),
+            expires_at: (Utc::now() + chrono::Duration::hours(hours)).into(),
+            revoked_at: None,
+        };
+        let token = self.sign(&link.id, link.expires_at);
+        let created = ShareLinkCreatedDto { id: link.id, token, expires_at: link.expires_at };
+        let after = snapshot(&link);
+        self.collection.insert_one(link).await?;
+        self.audit.record(ctx.entry("create", "share_link", Some(created.id.to_hex()), None, after)).await?;
+        Ok(created)
+    }
+
+    pub async fn list(&self, tenant: &Tenant) -> mongodb::error::Result<Vec<ShareLink>> {
+        self.collection
+            .find(tenant.scope(doc! {}))
+            .sort(doc! { "createdAt": -1 })
+            .await?
+            .try_collect()
+            .await
+    }
+
+    pub async fn revoke(&self, id: &str, ctx: &AuditContext) -> Result<(), AppError> {
+        let obj_id = ObjectId::parse_str(id).map_err(|e| {
+            AppError::BadRequest(format!("Invalid ObjectId: {}", e))
+        })?;
+        let now: DateTime = Utc::now().into();
+        let result = self.collection
+            .update_one(
+                ctx.tenant.scope(doc! { "_id": obj_id, "revokedAt": null }),
+                doc! { "$set": { "revokedAt": now } },
+            )
+            .await?;
+        if result.modified_count > 0 {
+            self.audit.record(ctx.entry("revoke", "share_link", Some(obj_id.to_hex()), None, Some(doc! { "revokedAt": now }))).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn get_access_log(&self, tenant: &Tenant, id: &str) -> Result<Vec<ShareAccessLog>, AppError> {
+        let obj_id = ObjectId::parse_str(id).map_err(|e| {
+            AppError::BadRequest(format!("Invalid ObjectId: {}", e))
+        })?;
+        // 先确认分享链接属于当前租户
+        self.collection.find_one(tenant.scope(doc! { "_id": obj_id })).await?
+            .ok_or_else(|| AppError::NotFound("Share link not found".to_string()))?;
+        let options = FindOptions::builder().sort(doc! { "accessedAt": -1 }).build();
+        Ok(self.access_log.find(doc! { "shareId": obj_id }).with_options(options).await?.try_collect().await?)
+    }
+
+    // 校验签名、过期时间和吊销状态，返回对应的分享链接
+    pub async fn resolve(&self, token: &str) -> Result<ShareLink, AppError> {
+        let invalid = || AppError::Forbidden("Invalid or expired share link".to_string());
+        let mut parts = token.splitn(3, '.');
+        let (Some(id), Some(expires), Some(_)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(invalid());
+        };
+        let id = ObjectId::parse_str(id).map_err(|_| invalid())?;
+        let expires_at = DateTime::from_millis(expires.parse::<i64>().map_err(|_| invalid())?);
+        // 重新计算签名并比较，签名不参与数据库查询
+        let expected = self.sign(&id, expires_at);
+        if !constant_time_eq(expected.as_bytes(), token.as_bytes()) {
+            return Err(invalid());
+        }
+        if expires_at < DateTime::now() {
+            return Err(invalid());
+        }
+        let link = self.collection.find_one(doc! { "_id": id }).await?.ok_or_else(invalid)?;
+        if link.revoked_at.is_some() || link.expires_at < DateTime::now() {
+            return Err(invalid());
+        }
+        Ok(link)
+    }
+
+    fn tenant_of(link: &ShareLink) -> Tenant {
+        Tenant { org_id: link.org_id.clone(), wind_farm_id: link.wind_farm_id.clone() }
+    }
+
+    pub async fn shared_reports(&self, link: &ShareLink) -> mongodb::error::Result<Vec<ReportRaw>> {
+        let tenant = Self::tenant_of(link);
+        let mut reports = Vec::with_capacity(link.report_ids.len());
+        for id in &link.report_ids {
+            // 已删除的报告不再对外可见
+            if let Some(report) = self.reports.get_by_id(&tenant, &id.to_hex()).await? {
+                reports.push(report);
+            }
+        }
+        Ok(reports)
+    }
+
+    pub async fn shared_report(&self, link: &ShareLink, report_id: &str) -> Result<ReportRaw, AppError> {
+        let not_found = || AppError::NotFound("Report not found".to_string());
+        let obj_id = ObjectId::parse_str(report_id).map_err(|_| not_found())?;
+        if !link.report_ids.contains(&obj_id) {
+            return Err(not_found());
+        }
+        self.reports.get_by_id(&Self::tenant_of(link), report_id).await?.ok_or_else(not_found)
+    }
+
+    // 返回分享报告中第 index 张图片在磁盘上的路径
+    pub async fn shared_image_path(&self, link: &ShareLink, report_id: &str, index: usize) -> Result<String, AppError> {
+        let report = self.shared_report(link, report_id).await?;
+        report.photo_path
+            .split(", ")
+            .filter(|p| !p.is_empty())
+            .nth(index)
+            .map(|relative_path| format!("{}{}", UPLOAD_BASE_DIR, relative_path))
+            .ok_or_else(|| AppError::NotFound("Image not found".to_string()))
+    }
+
+    pub async fn log_access(&self, link: &ShareLink, resource: &str, report_id: Option<ObjectId>, viewer: &ShareViewer) {
+        let entry = ShareAccessLog {
+            id: ObjectId::new(),
+            share_id: link.id,
+            resource: resource.to_string(),
+            report_id,
+            client_ip: viewer.client_ip.clone(),
+            user_agent: viewer.user_agent.clone(),
+            accessed_at: Utc::now().into(),
+        };
+        if let Err(e) = self.access_log.insert_one(entry).await {
+            tracing::error!("记录分享访问日志失败: {:?}", e);
+        }
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -1,77 +1,1519 @@
-use bson::{Bson, DateTime};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use bson::{Binary, Bson, DateTime, Document};
+use bson::spec::BinarySubtype;
+use futures::TryStreamExt;
 use chrono::{Utc};
-use crate::model::ship_track::ShipTrack;
+use crate::model::audit_log::AuditContext;
+use crate::error::AppError;
+use crate::geo::crs::Crs;
+use crate::import;
+use crate::model::trash::BulkDeleteRequestDto;
+use crate::model::ship_track::{AppendAckDto, AppendBatch, ImportedTrackDto, MonthlyDistanceDto, MonthlyDistanceQuery, NearQuery, ShipTrack, ShipTrackRequestDto, ShipTrackSummary, TrackBulkDeleteFilter, TrackImportResultDto, TrackListQuery, TrackPageDto};
+use crate::model::tenant::{Tenant, TenantOwned};
+use crate::model::track_filter::{FilterState, TrackFilterConfig};
+use crate::model::track_point::{deserialize_points, last_accepted_position, TrackPoint, TrackPointDto};
+use crate::geo::filter::reject_outliers;
+use crate::model::geojson::{dedup_vertices, Geometry};
+use crate::model::live::TrackAppendedDto;
+use crate::model::geofence::Geofence;
+use crate::export::map::{turbine_positions, MapScene};
+use crate::model::heatmap::{HeatmapGrid, HeatmapQuery};
+use crate::model::point_codec::{self, PackOutcome, PackStats, PackedChunk, PointEncoding};
+use crate::model::replay::{Replay, ReplayQuery, MAX_REPLAY_FRAMES};
+use crate::model::track_edit::{detect_sorties, merge_by_time, MergeRequestDto, SortieDto, SortieQuery, SplitRequestDto};
+use crate::model::track_segment::{IndexedPointDto, PendingSegmentWrite, PointRange, SegmentRef, TrackSegment};
+use crate::model::track_stats::TrackStats;
+use crate::service::audit_service::{snapshot, AuditService};
+use crate::service::flight_service::FlightService;
+use crate::service::geofence_service::GeofenceService;
+use crate::service::live_service::LiveHub;
+use crate::service::tenant_service::TenantService;
+use crate::service::soft_delete::{active, list_trash, purge_expired, restore_one, soft_delete_one, BulkDelete};
 use mongodb::{bson::{doc, oid::ObjectId}, options::FindOneOptions, Collection};
-use mongodb::options::{FindOneAndUpdateOptions, ReturnDocument};
+use mongodb::options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument};
+use mongodb::IndexModel;
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 500;
+// GET /track/near 默认的距离上限（米）
+const DEFAULT_NEAR_DISTANCE_M: f64 = 500.0;
+// 每条航迹保留的最近追加批次数，用于识别重试
+const RECENT_BATCHES: i32 = 200;
+// 迁移旧格式点时，航迹在读取和改写之间被追加后重试的次数
+const MIGRATE_ATTEMPTS: usize = 5;
+
+// 追加坐标时只读取统计值、分段索引、过滤状态、几何类型和最后一个点
+#[derive(Deserialize)]
+struct TrackTail {
+    stats: Option<TrackStats>,
+    #[serde(default)]
+    segments: Vec<SegmentRef>,
+    #[serde(rename = "lastSeq")]
+    last_seq: Option<u64>,
+    #[serde(rename = "recentBatches", default)]
+    recent_batches: Vec<AppendBatch>,
+    #[serde(rename = "pendingSegments", default)]
+    pending_segments: Vec<PendingSegmentWrite>,
+    #[serde(deserialize_with = "deserialize_points")]
+    coordinates: Vec<TrackPoint>,
+    geometry: Option<GeometryType>,
+    #[serde(rename = "filterState")]
+    filter_state: Option<FilterState>,
+    // 第 0 段已压缩存储，不能再追加
+    #[serde(default)]
+    packed: bool,
+}
+
+// 补完分段写入时只读取租户和待写入的分段
+#[derive(Deserialize)]
+struct PendingTail {
+    #[serde(rename = "_id")]
+    id: ObjectId,
+    #[serde(flatten)]
+    tenant: Tenant,
+    #[serde(rename = "pendingSegments", default)]
+    pending_segments: Vec<PendingSegmentWrite>,
+}
+
+// 一次追加请求：点、可选的批次序号和批次 ID
+pub struct AppendRequest {
+    pub points: Vec<TrackPoint>,
+    pub seq: Option<u64>,
+    pub batch_id: Option<String>,
+}
+
+// 热力图聚合只读取航迹文档的第 0 段点和分段索引
+#[derive(Deserialize)]
+struct HeatmapSource {
+    #[serde(rename = "_id")]
+    id: ObjectId,
+    #[serde(deserialize_with = "deserialize_points")]
+    coordinates: Vec<TrackPoint>,
+    #[serde(default)]
+    segments: Vec<SegmentRef>,
+}
+
+// 航迹文档或分段中的点块
+#[derive(Deserialize)]
+struct StoredPoints {
+    #[serde(deserialize_with = "deserialize_points")]
+    coordinates: Vec<TrackPoint>,
+}
+
+#[derive(Deserialize)]
+struct SegmentTail {
+    #[serde(deserialize_with = "deserialize_points")]
+    coordinates: Vec<TrackPoint>,
+    geometry: Option<GeometryType>,
+    #[serde(default)]
+    packed: bool,
+}
+
+// 航迹摘要只读取列表需要的字段，不读取点、几何和追加用的内部状态
+fn summary_projection() -> Document {
+    doc! { "_id": 1, "startTime": 1, "lastUpdate": 1, "totalPoints": 1, "droneId": 1, "owner": 1, "missionId": 1 }
+}
+
+// 追加时读取点块是否已压缩存储，压缩的块视为已写满
+fn packed_projection() -> Document {
+    doc! { "$eq": [{ "$type": "$coordinates" }, "binData"] }
+}
+
+#[derive(Deserialize)]
+struct GeometryType {
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+impl GeometryType {
+    fn is_line(geometry: &Option<GeometryType>) -> bool {
+        geometry.as_ref().is_some_and(|g| g.kind == "LineString")
+    }
+}
+
+// 追加点时的几何更新：已是 LineString 时只追加新顶点，否则用前一个点和新点重建几何。被过滤的点不进入几何
+fn update_geometry(is_line: bool, previous: Option<[f64; 2]>, points: &[TrackPoint], set: &mut Document, push: &mut Document) -> mongodb::error::Result<()> {
+    let positions = points.iter().filter(|p| p.accepted()).map(TrackPoint::position);
+    if is_line {
+        let new_vertices: Vec<[f64; 2]> = dedup_vertices(previous, positions).into_iter().skip(previous.is_some() as usize).collect();
+        if !new_vertices.is_empty() {
+            push.insert("geometry.coordinates", doc! { "$each": bson::to_bson(&new_vertices)? });
+        }
+    } else {
+        set.insert("geometry", bson::to_bson(&Geometry::from_points(previous, positions))?);
+    }
+    Ok(())
+}
 
 pub struct ShipTrackService {
     pub collection: Collection<ShipTrack>,
+    pub segments: Collection<TrackSegment>,
+    pub audit: Arc<AuditService>,
+    // 每个文档（航迹文档自身或分段文档）最多保存的点数
+    segment_points: usize,
+    append_locks: std::sync::Mutex<HashMap<ObjectId, Arc<tokio::sync::Mutex<()>>>>,
+    // 追加的批次通过这里实时推送给 WebSocket / SSE 订阅者
+    pub live: Arc<LiveHub>,
+    // 追加的每个点都要经过围栏判定
+    geofences: Arc<GeofenceService>,
+    // 读取租户的噪声过滤配置
+    tenants: Arc<TenantService>,
+    // 回放时合并关联飞行记录的遥测数据
+    flights: Arc<FlightService>,
+    // 写满的点块使用的存储编码
+    point_encoding: PointEncoding,
+    bulk_delete: BulkDelete,
 }
 
 impl ShipTrackService{
-    pub fn new(collection: Collection<ShipTrack>) -> Self {
-        Self { collection }
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(collection: Collection<ShipTrack>, segments: Collection<TrackSegment>, audit: Arc<AuditService>, live: Arc<LiveHub>, geofences: Arc<GeofenceService>, tenants: Arc<TenantService>, flights: Arc<FlightService>, segment_points: usize, point_encoding: PointEncoding) -> Self {
+        Self { collection, segments, audit, segment_points: segment_points.max(1), append_locks: Default::default(), live, geofences, tenants, flights, point_encoding, bulk_delete: BulkDelete::default() }
     }
 
-    pub async fn create(&self, track: ShipTrack) -> mongodb::error::Result<()> {
-        self.collection.insert_one(track).await?;
+    pub async fn ensure_indexes(&self) -> mongodb::error::Result<()> {
+        self.segments
+            .create_index(IndexModel::builder().keys(doc! { "trackId": 1, "seq": 1 }).build())
+            .await?;
+        self.collection
+            .create_index(IndexModel::builder().keys(doc! { "geometry": "2dsphere" }).build())
+            .await?;
+        self.segments
+            .create_index(IndexModel::builder().keys(doc! { "geometry": "2dsphere" }).build())
+            .await?;
+        self.collection
+            .create_index(IndexModel::builder().keys(doc! { "missionId": 1, "startTime": -1 }).build())
+            .await?;
         Ok(())
     }
 
-    pub async fn get(&self, id: &str) -> mongodb::error::Result<Option<ShipTrack>> {
-        let obj_id = ObjectId::parse_str(id).unwrap();
-        self.collection.find_one(doc! {"_id": obj_id}).await
+    pub async fn create(&self, mut track: ShipTrack, ctx: &AuditContext) -> mongodb::error::Result<()> {
+        track.set_tenant(&ctx.tenant);
+        track.reject_noise(&self.tenants.track_filter(&ctx.tenant).await?);
+        track.stats = Some(TrackStats::compute(&track.coordinates));
+        track.total_points = track.coordinates.len() as u32;
+        let segments = self.split_segments(&mut track);
+        let after = track.audit_summary();
+        let id = track.id;
+        self.store(&track, &segments, None).await?;
+        self.audit.record(ctx.entry("create", "track", Some(id.to_hex()), None, Some(after))).await
     }
 
-    pub async fn update(&self, id: &str, track: ShipTrack) -> mongodb::error::Result<()> {
-        let obj_id = ObjectId::parse_str(id).unwrap();
-        self.collection.replace_one(doc! {"_id": obj_id}, track).await?;
-        Ok(())
+    // 写入整条航迹的分段和航迹文档。replace 为被替换文档的条件，None 时新建。
+    // 替换时先写入新分段，航迹文档替换成功后才删除不再引用的旧分段，中途失败不会丢失原有的点；
+    // 条件不再匹配（航迹已被删除）时删除刚写入的分段并返回 false
+    async fn store(&self, track: &ShipTrack, segments: &[TrackSegment], replace: Option<Document>) -> mongodb::error::Result<bool> {
+        if !segments.is_empty() {
+            let documents: Vec<Document> = segments.iter().map(|s| self.stored(s, &s.coordinates)).collect::<Result<_, _>>()?;
+            self.segments.clone_with_type::<Document>().insert_many(documents).await?;
+        }
+        let document = self.stored(track, &track.coordinates)?;
+        let ids: Vec<ObjectId> = segments.iter().map(|s| s.id).collect();
+        match replace {
+            Some(filter) => {
+                let result = self.collection.clone_with_type::<Document>().replace_one(filter, document).await?;
+                let stale = match result.matched_count {
+                    0 => doc! { "_id": { "$in": &ids } },
+                    _ => doc! { "trackId": track.id, "_id": { "$nin": &ids } },
+                };
+                self.segments.delete_many(track.tenant().scope(stale)).await?;
+                Ok(result.matched_count > 0)
+            }
+            None => {
+                self.collection.clone_with_type::<Document>().insert_one(document).await?;
+                Ok(true)
+            }
+        }
+    }
+
+    // 序列化为存储的文档。写满上限的点块不会再追加，按配置改为压缩编码；未写满的块保持数组以便 $push
+    fn stored<T: Serialize>(&self, value: &T, points: &[TrackPoint]) -> bson::ser::Result<Document> {
+        let mut document = bson::to_document(value)?;
+        if self.point_encoding == PointEncoding::Packed && points.len() >= self.segment_points
+            && let Some(bytes) = point_codec::encode(points)
+        {
+            document.insert("coordinates", Binary { subtype: BinarySubtype::Generic, bytes });
+        }
+        Ok(document)
+    }
+
+    // 把超出单文档上限的点拆分为分段文档，航迹文档只保留第 0 段，并生成各文档的几何
+    fn split_segments(&self, track: &mut ShipTrack) -> Vec<TrackSegment> {
+        track.segments.clear();
+        let overflow = if track.coordinates.len() > self.segment_points {
+            track.coordinates.split_off(self.segment_points)
+        } else {
+            Vec::new()
+        };
+        track.geometry = Geometry::from_points(None, track.coordinates.iter().filter(|p| p.accepted()).map(TrackPoint::position));
+        let tenant = track.tenant();
+        let mut previous = last_accepted_position(&track.coordinates);
+        let mut segments = Vec::new();
+        for (i, chunk) in overflow.chunks(self.segment_points).enumerate() {
+            let start_index = ((i + 1) * self.segment_points) as u64;
+            segments.push(TrackSegment::new(track.id, &tenant, i as u32 + 1, start_index, previous, chunk.to_vec()));
+            previous = last_accepted_position(chunk).or(previous);
+        }
+        track.segments = segments.iter().map(TrackSegment::reference).collect();
+        segments
+    }
+
+    // 按顺序拼接航迹文档引用的分段，返回包含全部点的航迹
+    async fn stitch(&self, mut track: ShipTrack) -> mongodb::error::Result<ShipTrack> {
+        if track.segments.is_empty() {
+            return Ok(track);
+        }
+        let ids: Vec<ObjectId> = track.segments.iter().map(|s| s.id).collect();
+        let mut cursor = self.segments
+            .find(track.tenant().scope(doc! { "trackId": track.id, "_id": { "$in": ids } }))
+            .sort(doc! { "seq": 1 })
+            .await?;
+        while let Some(segment) = cursor.try_next().await? {
+            track.coordinates.extend(segment.coordinates);
+        }
+        Ok(track)
+    }
+
+    async fn stitch_all(&self, tracks: Vec<ShipTrack>) -> mongodb::error::Result<Vec<ShipTrack>> {
+        let mut stitched = Vec::with_capacity(tracks.len());
+        for track in tracks {
+            stitched.push(self.stitch(track).await?);
+        }
+        Ok(stitched)
+    }
+
+    // 按下标或时间范围读取航迹点，只加载与范围重叠的分段
+    pub async fn get_points(&self, tenant: &Tenant, id: &str, range: PointRange) -> Result<Vec<IndexedPointDto>, AppError> {
+        let obj_id = ObjectId::parse_str(id).map_err(|e| AppError::BadRequest(format!("Invalid ObjectId: {}", e)))?;
+        let track = self.collection.find_one(active(tenant.scope(doc! {"_id": obj_id}))).await?
+            .ok_or_else(|| AppError::NotFound("Track not found".to_string()))?;
+        let mut points: Vec<IndexedPointDto> = track.coordinates.iter()
+            .enumerate()
+            .filter(|(i, p)| range.contains(*i as u64, p))
+            .map(|(i, p)| IndexedPointDto { index: i as u64, point: TrackPointDto::from(*p) })
+            .collect();
+        let wanted: Vec<ObjectId> = track.segments.iter().filter(|s| s.overlaps(&range)).map(|s| s.id).collect();
+        if !wanted.is_empty() {
+            let mut cursor = self.segments
+                .find(tenant.scope(doc! { "_id": { "$in": wanted } }))
+                .sort(doc! { "seq": 1 })
+                .await?;
+            while let Some(segment) = cursor.try_next().await? {
+                for (offset, point) in segment.coordinates.iter().enumerate() {
+                    let index = segment.start_index + offset as u64;
+                    if range.contains(index, point) {
+                        points.push(IndexedPointDto { index, point: TrackPointDto::from(*point) });
+                    }
+                }
+            }
+        }
+        Ok(points)
+    }
+
+    // 解析单个历史航迹文件并逐条创建航迹，与 POST /track 走同一创建路径
+    pub async fn import_file(
+        &self,
+        file_name: &str,
+        content: &str,
+        drone_id: Option<String>,
+        owner: Option<String>,
+        ctx: &AuditContext,
+    ) -> TrackImportResultDto {
+        let mut result = TrackImportResultDto { file: file_name.to_string(), tracks: Vec::new(), error: None };
+        let parsed = match import::parse(file_name, content) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                result.error = Some(e);
+                return result;
+            }
+        };
+        for parsed_track in parsed {
+            match self.import_track(parsed_track, drone_id.clone(), owner.clone(), ctx).await {
+                Ok(imported) => result.tracks.push(imported),
+                Err(e) => {
+                    result.error = Some(format!("Failed to save track: {}", e));
+                    break;
+                }
+            }
+        }
+        result
+    }
+
+    async fn import_track(
+        &self,
+        parsed: import::ParsedTrack,
+        drone_id: Option<String>,
+        owner: Option<String>,
+        ctx: &AuditContext,
+    ) -> mongodb::error::Result<ImportedTrackDto> {
+        let content_hash = parsed.content_hash();
+        let total_points = parsed.points.len() as u32;
+        if let Some(existing) = self.collection.find_one(active(ctx.tenant.scope(doc! { "contentHash": &content_hash }))).await? {
+            return Ok(ImportedTrackDto { id: existing.id, name: parsed.name, total_points: existing.total_points, duplicate: true });
+        }
+        let (start_time, end_time) = (parsed.start_time(), parsed.end_time());
+        let mut track = ShipTrack::from_request(ShipTrackRequestDto {
+            coordinates: parsed.points.iter().map(|p| TrackPointDto {
+                altitude: p.altitude,
+                time: p.time,
+                ..TrackPointDto::from([p.lon, p.lat])
+            }).collect(),
+            total_points,
+            drone_id,
+            owner,
+            // GPX / KML / NMEA 文件中的坐标都是 WGS-84
+            crs: Crs::Wgs84,
+        });
+        // 文件中带有时间时，以首末点时间作为航迹的起止时间
+        if let Some(start_time) = start_time {
+            track.start_time = start_time.into();
+        }
+        if let Some(end_time) = end_time {
+            track.last_update = end_time.into();
+        }
+        track.content_hash = Some(content_hash);
+        let id = track.id;
+        self.create(track, ctx).await?;
+        Ok(ImportedTrackDto { id, name: parsed.name, total_points, duplicate: false })
+    }
+
+    pub async fn get(&self, tenant: &Tenant, id: &str) -> Result<Option<ShipTrack>, AppError> {
+        let obj_id = ObjectId::parse_str(id).map_err(|e| AppError::BadRequest(format!("Invalid ObjectId: {}", e)))?;
+        match self.collection.find_one(active(tenant.scope(doc! {"_id": obj_id}))).await? {
+            Some(track) => Ok(Some(self.stitch(track).await?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn update(&self, id: &str, mut track: ShipTrack, ctx: &AuditContext) -> Result<(), AppError> {
+        let obj_id = ObjectId::parse_str(id).map_err(|e| AppError::BadRequest(format!("Invalid ObjectId: {}", e)))?;
+        // 不允许通过更新把航迹转移到其他租户
+        track.set_tenant(&ctx.tenant);
+        track.deleted_at = None;
+        track.deleted_by = None;
+        track.id = obj_id;
+        track.reject_noise(&self.tenants.track_filter(&ctx.tenant).await?);
+        track.stats = Some(TrackStats::compute(&track.coordinates));
+        track.total_points = track.coordinates.len() as u32;
+        // 整条替换与追加、拆分等一样持有追加锁，并先补完中断的分段写入，避免替换掉正在进行的追加
+        self.with_append_locks(vec![obj_id], async || {
+            self.complete_pending(ctx.tenant.scope(doc! { "_id": obj_id })).await?;
+            let filter = active(ctx.tenant.scope(doc! {"_id": obj_id}));
+            let before = self.collection.find_one(filter.clone()).await?;
+            let Some(previous) = before else {
+                return Err(AppError::NotFound("Track not found".to_string()));
+            };
+            // 请求中没有 missionId 时保留原有的任务关联；批次记录和导入哈希始终沿用已保存的，重试仍能识别
+            track.mission_id = track.mission_id.or(previous.mission_id);
+            track.last_seq = previous.last_seq;
+            track.recent_batches = previous.recent_batches.clone();
+            track.content_hash = previous.content_hash.clone();
+            // 起止时间按点的时间确定，点不带时间时保留原来的开始时间
+            track.start_time = previous.start_time;
+            track.refresh_time_range();
+            // 整条替换时重新分段
+            let segments = self.split_segments(&mut track);
+            let after = track.audit_summary();
+            if !self.store(&track, &segments, Some(filter)).await? {
+                return Err(AppError::NotFound("Track not found".to_string()));
+            }
+            self.audit.record(ctx.entry("update", "track", Some(obj_id.to_hex()), Some(previous.audit_summary()), Some(after))).await?;
+            Ok(())
+        }).await
     }
     // 新增方法：追加坐标并更新相关字段
+    // 带 seq / batchId 的批次是幂等的：重试返回首次写入时的确认信息，不会重复写入点
     pub async fn append_coordinates_and_update(
         &self,
         id: &str,
-        coordinates_to_add: Vec<[f64; 2]>,
-    ) -> mongodb::error::Result<Option<ShipTrack>> {
-        let obj_id = ObjectId::parse_str(id).map_err(|e| mongodb::error::Error::custom(format!("Invalid ObjectId: {}", e)))?;
+        batch: AppendRequest,
+        ctx: &AuditContext,
+    ) -> Result<Option<(ShipTrack, AppendAckDto)>, AppError> {
+        let obj_id = ObjectId::parse_str(id).map_err(|e| AppError::BadRequest(format!("Invalid ObjectId: {}", e)))?;
+        // 同一航迹的追加在本进程内串行执行，保证序号检查和写入之间没有交错
+        self.with_append_locks(vec![obj_id], async || self.append_locked(obj_id, batch, ctx).await).await
+    }
 
+    // 持有航迹的追加锁执行 f，同一航迹的追加和拆分、合并、裁剪在本进程内串行执行。
+    // 多条航迹按 ID 顺序加锁，避免两个合并请求互相等待
+    async fn with_append_locks<T>(&self, mut ids: Vec<ObjectId>, f: impl AsyncFnOnce() -> T) -> T {
+        ids.sort();
+        ids.dedup();
+        let locks: Vec<Arc<tokio::sync::Mutex<()>>> = {
+            let mut map = self.append_locks.lock().expect("append lock map poisoned");
+            ids.iter().map(|id| map.entry(*id).or_default().clone()).collect()
+        };
+        let mut guards = Vec::with_capacity(locks.len());
+        for lock in &locks {
+            guards.push(lock.lock().await);
+        }
+        let result = f().await;
+        drop(guards);
+        let mut map = self.append_locks.lock().expect("append lock map poisoned");
+        for id in &ids {
+            if map.get(id).is_some_and(|l| Arc::strong_count(l) <= 2) {
+                map.remove(id);
+            }
+        }
+        result
+    }
+
+    async fn append_locked(
+        &self,
+        obj_id: ObjectId,
+        batch: AppendRequest,
+        ctx: &AuditContext,
+    ) -> Result<Option<(ShipTrack, AppendAckDto)>, AppError> {
+        let AppendRequest { points: mut coordinates_to_add, seq, batch_id } = batch;
+        let mut filter = active(ctx.tenant.scope(doc! {"_id": obj_id}));
+        let tail = self.collection
+            .clone_with_type::<TrackTail>()
+            .find_one(filter.clone())
+            .projection(doc! { "stats": 1, "segments": 1, "lastSeq": 1, "recentBatches": 1, "pendingSegments": 1, "filterState": 1, "geometry.type": 1, "coordinates": { "$slice": -1 }, "packed": packed_projection() })
+            .await?;
+        let Some(tail) = tail else {
+            return Ok(None);
+        };
+        // 上一次追加在写入分段前中断，先补完，之后读取的分段才是完整的
+        self.write_segments(obj_id, &ctx.tenant, &tail.pending_segments).await?;
+
+        // 已接受过的批次直接返回当时的确认信息
+        let accepted = tail.recent_batches.iter().rev().find(|b| match (seq, &batch_id) {
+            (Some(seq), _) => b.seq == Some(seq),
+            (None, Some(batch_id)) => b.batch_id.as_ref() == Some(batch_id),
+            (None, None) => false,
+        });
+        if let Some(accepted) = accepted {
+            let ack = AppendAckDto::new(accepted, true);
+            return Ok(self.get_by_id(&filter).await?.map(|track| (track, ack)));
+        }
+        if let (Some(seq), Some(last_seq)) = (seq, tail.last_seq) {
+            if seq <= last_seq {
+                // 太早的批次已不在记录中，只能确认它已处理过
+                let ack = AppendAckDto { seq: Some(seq), batch_id, start_index: None, end_index: None, duplicate: true };
+                return Ok(self.get_by_id(&filter).await?.map(|track| (track, ack)));
+            }
+            if seq > last_seq + 1 {
+                return Err(AppError::Conflict(format!("Out-of-order batch: expected seq {}, got {}", last_seq + 1, seq)));
+            }
+        }
+        let mut segments = tail.segments;
+        // 最后一个点在最后一个分段里；旧文档没有缓存统计值时先全量计算一次
+        let last_segment = match segments.last() {
+            Some(last) => self.segments
+                .clone_with_type::<SegmentTail>()
+                .find_one(doc! { "_id": last.id })
+                .projection(doc! { "coordinates": { "$slice": -1 }, "geometry.type": 1, "packed": packed_projection() })
+                .await?,
+            None => None,
+        };
+        let last_point = match &last_segment {
+            Some(segment) => segment.coordinates.last().copied(),
+            None => tail.coordinates.last().copied(),
+        };
+        let (mut stats, previous) = match tail.stats {
+            Some(stats) => (stats, last_point),
+            None => match self.collection.find_one(filter.clone()).await? {
+                Some(track) => {
+                    let track = self.stitch(track).await?;
+                    (TrackStats::compute(&track.coordinates), track.coordinates.iter().rev().find(|p| p.accepted()).copied())
+                }
+                None => return Ok(None),
+            },
+        };
         let current_time: DateTime = Utc::now().into();
-        let mut update_document_parts = doc! { "$set": { "lastUpdate": current_time } };
+        let mut set = doc! { "lastUpdate": current_time };
+        let mut push = doc! {};
 
-        if !coordinates_to_add.is_empty() {
-            let bson_coordinates_to_add: Vec<Bson> = coordinates_to_add
-                .into_iter()
-                .map(|coord_pair| Bson::Array(vec![Bson::Double(coord_pair[0]), Bson::Double(coord_pair[1])]))
-                .collect();
+        // 噪声过滤从上一批结束时的状态继续判定，统计值和几何都以最后一个有效点为起点
+        let previous = tail.filter_state.and_then(|state| state.last_accepted).or(previous);
+        let config = self.tenants.track_filter(&ctx.tenant).await?;
+        if config.enabled {
+            let mut state = tail.filter_state.unwrap_or(FilterState { last_accepted: previous, consecutive_rejected: 0 });
+            reject_outliers(&config, &mut state, &mut coordinates_to_add);
+            set.insert("filterState", bson::to_bson(&state)?);
+        } else if tail.filter_state.is_some() {
+            set.insert("filterState", Bson::Null);
+        }
+        let total_before = stats.point_count;
+        stats.extend(previous.as_ref(), &coordinates_to_add);
+        set.insert("stats", bson::to_bson(&stats)?);
 
-            update_document_parts.insert("$push", doc! { "coordinates": { "$each": bson_coordinates_to_add } });
-            update_document_parts.insert("$inc", doc! { "totalPoints": 1i32 }); // totalPoints 增加 1
+        // 先填满航迹文档或最后一个分段，剩下的点按上限滚动写入新分段
+        let mut previous_position = previous.as_ref().map(TrackPoint::position);
+        let mut remaining = coordinates_to_add.as_slice();
+        // 写满的块在这次追加后改为压缩编码
+        let (mut seal_inline, mut seal_segment) = (false, None);
+        if segments.is_empty() {
+            let capacity = if tail.packed { 0 } else { self.segment_points };
+            let room = capacity.saturating_sub(total_before as usize).min(remaining.len());
+            let (inline, rest) = remaining.split_at(room);
+            if !inline.is_empty() {
+                let points: Vec<Bson> = inline.iter().map(bson::to_bson).collect::<Result<_, _>>()?;
+                push.insert("coordinates", doc! { "$each": points });
+                update_geometry(GeometryType::is_line(&tail.geometry), previous_position, inline, &mut set, &mut push)?;
+                previous_position = last_accepted_position(inline).or(previous_position);
+                seal_inline = total_before as usize + inline.len() >= self.segment_points;
+            }
+            remaining = rest;
+        }
+        // 分段的写入先记录在航迹文档上，航迹文档的条件更新成功后才执行，冲突时分段不会留下多余的点
+        let mut pending = Vec::new();
+        if let Some(last) = segments.last_mut() {
+            let capacity = if last_segment.as_ref().is_some_and(|segment| segment.packed) { 0 } else { self.segment_points };
+            let room = capacity.saturating_sub(last.point_count as usize).min(remaining.len());
+            let (filled, rest) = remaining.split_at(room);
+            if !filled.is_empty() {
+                pending.push(PendingSegmentWrite {
+                    segment_id: last.id,
+                    seq: last.seq,
+                    start_index: last.start_index + last.point_count,
+                    create: false,
+                    point_count: last.point_count,
+                    is_line: last_segment.as_ref().is_some_and(|segment| GeometryType::is_line(&segment.geometry)),
+                    previous: previous_position,
+                    points: filled.to_vec(),
+                });
+                last.extend(filled);
+                previous_position = last_accepted_position(filled).or(previous_position);
+                if last.point_count as usize >= self.segment_points {
+                    seal_segment = Some(last.id);
+                }
+            }
+            remaining = rest;
+        }
+        let mut next_index = total_before + (coordinates_to_add.len() - remaining.len()) as u64;
+        for chunk in remaining.chunks(self.segment_points) {
+            let seq = segments.last().map_or(1, |last| last.seq + 1);
+            let segment = TrackSegment::new(obj_id, &ctx.tenant, seq, next_index, previous_position, chunk.to_vec());
+            segments.push(segment.reference());
+            pending.push(PendingSegmentWrite {
+                segment_id: segment.id,
+                seq,
+                start_index: next_index,
+                create: true,
+                point_count: 0,
+                is_line: false,
+                previous: previous_position,
+                points: segment.coordinates,
+            });
+            next_index += chunk.len() as u64;
+            previous_position = last_accepted_position(chunk).or(previous_position);
+        }
+        if !pending.is_empty() {
+            set.insert("pendingSegments", bson::to_bson(&pending)?);
+        }
+
+        set.insert("segments", bson::to_bson(&segments)?);
+        let mut update_document_parts = doc! { "$set": set };
+        if !push.is_empty() {
+            update_document_parts.insert("$push", push);
+        }
+        if !coordinates_to_add.is_empty() {
+            // totalPoints 始终等于实际保存的点数
+            update_document_parts.insert("$inc", doc! { "totalPoints": coordinates_to_add.len() as i64 });
         }
         // 如果 coordinates_to_add 为空，则只更新 lastUpdate
 
+        let accepted = AppendBatch { seq, batch_id, start_index: total_before, count: coordinates_to_add.len() as u64 };
+        if accepted.seq.is_some() || accepted.batch_id.is_some() {
+            let set = update_document_parts.get_document_mut("$set").expect("$set is a document");
+            if let Some(seq) = seq {
+                set.insert("lastSeq", seq as i64);
+            }
+            let push = update_document_parts.entry("$push".to_string()).or_insert_with(|| Bson::Document(doc! {}));
+            if let Bson::Document(push) = push {
+                push.insert("recentBatches", doc! { "$each": [bson::to_bson(&accepted)?], "$slice": -RECENT_BATCHES });
+            }
+            // 多实例部署时由序号条件兜底，防止两个实例同时接受同一个序号；分段在条件更新成功后才写入
+            if seq.is_some() {
+                filter.insert("lastSeq", tail.last_seq.map_or(Bson::Null, |s| Bson::Int64(s as i64)));
+            }
+        }
+
         let options = FindOneAndUpdateOptions::builder()
             .return_document(ReturnDocument::After) // 返回更新后的文档
             .build();
 
-        self.collection
-            .find_one_and_update(doc! {"_id": obj_id}, update_document_parts)
+        let updated = self.collection
+            .find_one_and_update(filter, update_document_parts)
             .with_options(options)
-            .await
+            .await?;
+        let Some(updated) = updated else {
+            return Err(AppError::Conflict("Track was modified concurrently, retry the batch".to_string()));
+        };
+        self.write_segments(obj_id, &ctx.tenant, &pending).await?;
+        // 点已经写入，压缩失败只影响存储大小，只记录错误
+        if self.point_encoding == PointEncoding::Packed {
+            let sealed = [(seal_inline.then_some(obj_id), self.collection.clone_with_type::<Document>()), (seal_segment, self.segments.clone_with_type::<Document>())];
+            for (id, collection) in sealed {
+                if let Some(id) = id
+                    && let Err(e) = pack_chunk(&collection, id, false).await
+                {
+                    tracing::error!("航迹 {} 点块压缩失败: {:?}", obj_id.to_hex(), e);
+                }
+            }
+        }
+        // 追加只记录批次的序号和点下标范围，不记录点本身
+        let mut diff = snapshot(&accepted).unwrap_or_default();
+        diff.insert("totalPoints", updated.total_points as i64);
+        self.audit.record(ctx.entry("append", "track", Some(obj_id.to_hex()), None, Some(diff))).await?;
+        if !coordinates_to_add.is_empty() && self.live.has_subscribers() {
+            let event = TrackAppendedDto {
+                track_id: obj_id,
+                drone_id: updated.drone_id.clone(),
+                start_index: total_before,
+                total_points: updated.total_points,
+                last_update: updated.last_update,
+                points: coordinates_to_add.iter().copied().map(TrackPointDto::from).collect(),
+            };
+            self.live.publish_append(&ctx.tenant, event);
+        }
+        // 点已经写入，围栏判定失败不影响追加结果，只记录错误
+        if let Err(e) = self.geofences.evaluate(&ctx.tenant, obj_id, updated.drone_id.as_deref(), total_before, &coordinates_to_add).await {
+            tracing::error!("航迹 {} 围栏判定失败: {:?}", obj_id.to_hex(), e);
+        }
+        Ok(Some((self.stitch(updated).await?, AppendAckDto::new(&accepted, false))))
     }
-    pub async fn delete(&self, id: &str) -> mongodb::error::Result<()> {
-        let obj_id = ObjectId::parse_str(id).unwrap();
-        self.collection.delete_one(doc! {"_id": obj_id}).await?;
+
+    async fn get_by_id(&self, filter: &Document) -> mongodb::error::Result<Option<ShipTrack>> {
+        match self.collection.find_one(filter.clone()).await? {
+            Some(track) => Ok(Some(self.stitch(track).await?)),
+            None => Ok(None),
+        }
+    }
+    // 执行记录在航迹文档上的分段写入，然后清除记录。重放时已完成的写入会被跳过：
+    // 追加到已有分段以写入前的点数为条件，新分段按预先分配的 _id 只在不存在时插入
+    async fn write_segments(&self, track_id: ObjectId, tenant: &Tenant, pending: &[PendingSegmentWrite]) -> mongodb::error::Result<()> {
+        let Some(first) = pending.first() else {
+            return Ok(());
+        };
+        for write in pending {
+            if write.create {
+                let mut segment = TrackSegment::new(track_id, tenant, write.seq, write.start_index, write.previous, write.points.clone());
+                segment.id = write.segment_id;
+                let mut document = self.stored(&segment, &segment.coordinates)?;
+                document.remove("_id");
+                self.segments.clone_with_type::<Document>()
+                    .update_one(doc! { "_id": write.segment_id }, doc! { "$setOnInsert": document })
+                    .upsert(true)
+                    .await?;
+            } else {
+                let points: Vec<Bson> = write.points.iter().map(bson::to_bson).collect::<Result<_, _>>()?;
+                let (mut segment_set, mut segment_push) = (doc! {}, doc! { "coordinates": { "$each": points } });
+                update_geometry(write.is_line, write.previous, &write.points, &mut segment_set, &mut segment_push)?;
+                let mut update = doc! { "$push": segment_push };
+                if !segment_set.is_empty() {
+                    update.insert("$set", segment_set);
+                }
+                self.segments
+                    .update_one(doc! { "_id": write.segment_id, "coordinates": { "$size": write.point_count as i64 } }, update)
+                    .await?;
+            }
+        }
+        self.collection
+            .update_one(doc! { "_id": track_id, "pendingSegments.0.startIndex": first.start_index as i64 }, doc! { "$unset": { "pendingSegments": "" } })
+            .await?;
         Ok(())
     }
 
-    pub async fn get_latest(&self) -> mongodb::error::Result<Option<ShipTrack>> {
+    // 补完追加中断后遗留的分段写入，返回处理的航迹数
+    async fn complete_pending(&self, filter: Document) -> mongodb::error::Result<u64> {
+        let mut filter = filter;
+        filter.insert("pendingSegments.0", doc! { "$exists": true });
+        let mut cursor = self.collection
+            .clone_with_type::<PendingTail>()
+            .find(filter)
+            .projection(doc! { "orgId": 1, "windFarmId": 1, "pendingSegments": 1 })
+            .await?;
+        let mut completed = 0;
+        while let Some(track) = cursor.try_next().await? {
+            self.write_segments(track.id, &track.tenant, &track.pending_segments).await?;
+            completed += 1;
+        }
+        Ok(completed)
+    }
+
+    // 启动时补完所有航迹遗留的分段写入，逐条持有追加锁
+    pub async fn complete_pending_segments(&self) -> mongodb::error::Result<u64> {
+        let ids: Vec<ObjectId> = self.collection.distinct("_id", doc! { "pendingSegments.0": { "$exists": true } }).await?
+            .iter()
+            .filter_map(Bson::as_object_id)
+            .collect();
+        let mut completed = 0;
+        for id in ids {
+            completed += self.with_append_locks(vec![id], async || self.complete_pending(doc! { "_id": id }).await).await?;
+        }
+        Ok(completed)
+    }
+
+    // 把旧格式的 [经度, 纬度] 数组点改写为点对象。读取时两种格式都兼容，迁移只是为了统一存储格式。
+    // 改写整个 coordinates 数组，因此持有追加锁，并以读取时的 totalPoints 为条件，期间其他实例追加过就重新读取
+    pub async fn migrate_legacy_points(&self) -> mongodb::error::Result<u64> {
+        let legacy = doc! { "coordinates": { "$elemMatch": { "$type": "array" } } };
+        let ids: Vec<ObjectId> = self.collection.distinct("_id", legacy.clone()).await?
+            .iter()
+            .filter_map(Bson::as_object_id)
+            .collect();
+        let mut migrated = 0;
+        for id in ids {
+            let rewritten = self.with_append_locks(vec![id], async || {
+                for _ in 0..MIGRATE_ATTEMPTS {
+                    let mut filter = legacy.clone();
+                    filter.insert("_id", id);
+                    let Some(track) = self.collection.find_one(filter.clone()).await? else {
+                        return Ok(false);
+                    };
+                    let points: Vec<Bson> = track.coordinates.iter().map(bson::to_bson).collect::<Result<_, _>>()?;
+                    filter.insert("totalPoints", track.total_points as i64);
+                    let result = self.collection.update_one(filter, doc! { "$set": { "coordinates": points } }).await?;
+                    if result.matched_count > 0 {
+                        return Ok(true);
+                    }
+                }
+                tracing::warn!("航迹 {} 持续有追加，本次未迁移旧格式点", id.to_hex());
+                Ok::<_, mongodb::error::Error>(false)
+            }).await?;
+            migrated += rewritten as u64;
+        }
+        Ok(migrated)
+    }
+
+    // 为没有缓存统计值的旧航迹补算统计
+    pub async fn backfill_stats(&self) -> mongodb::error::Result<u64> {
+        let mut cursor = self.collection.find(doc! { "stats": { "$exists": false } }).await?;
+        let mut updated = 0;
+        while let Some(track) = cursor.try_next().await? {
+            let stats = TrackStats::compute(&track.coordinates);
+            // 追加时也会补算统计值，已有统计值的不再覆盖
+            self.collection
+                .update_one(doc! { "_id": track.id, "stats": { "$exists": false } }, doc! { "$set": { "stats": bson::to_bson(&stats)? } })
+                .await?;
+            updated += 1;
+        }
+        Ok(updated)
+    }
+
+    // 旧版追加每次只给 totalPoints 加 1，按统计值中的实际点数修正
+    pub async fn repair_total_points(&self) -> mongodb::error::Result<u64> {
+        let filter = doc! {
+            "stats.pointCount": { "$exists": true },
+            "$expr": { "$ne": ["$totalPoints", "$stats.pointCount"] },
+        };
+        let result = self.collection
+            .update_many(filter, vec![doc! { "$set": { "totalPoints": "$stats.pointCount" } }])
+            .await?;
+        Ok(result.modified_count)
+    }
+
+    // 为还没有几何字段的航迹文档和分段补算几何，与写入时一样不含被过滤的点。
+    // 分段的几何以上一段最后一个有效点开头。追加时也会写入几何，只更新仍然没有几何的文档
+    pub async fn backfill_geometry(&self) -> mongodb::error::Result<u64> {
+        let missing = doc! { "geometry": { "$exists": false }, "coordinates.0": { "$exists": true } };
+        let mut updated = 0;
+        let mut cursor = self.collection.find(missing.clone()).await?;
+        while let Some(track) = cursor.try_next().await? {
+            let geometry = Geometry::from_points(None, track.coordinates.iter().filter(|p| p.accepted()).map(TrackPoint::position));
+            let result = self.collection
+                .update_one(doc! { "_id": track.id, "geometry": { "$exists": false } }, doc! { "$set": { "geometry": bson::to_bson(&geometry)? } })
+                .await?;
+            updated += result.modified_count;
+        }
+        let mut cursor = self.segments.find(missing).await?;
+        while let Some(segment) = cursor.try_next().await? {
+            let preceding = match segment.seq {
+                0 | 1 => self.collection.clone_with_type::<StoredPoints>().find_one(doc! { "_id": segment.track_id }).projection(doc! { "coordinates": 1 }).await?,
+                seq => self.segments.clone_with_type::<StoredPoints>().find_one(doc! { "trackId": segment.track_id, "seq": seq - 1 }).projection(doc! { "coordinates": 1 }).await?,
+            };
+            let previous = preceding.and_then(|p| last_accepted_position(&p.coordinates));
+            let geometry = Geometry::from_points(previous, segment.coordinates.iter().filter(|p| p.accepted()).map(TrackPoint::position));
+            let result = self.segments
+                .update_one(doc! { "_id": segment.id, "geometry": { "$exists": false } }, doc! { "$set": { "geometry": bson::to_bson(&geometry)? } })
+                .await?;
+            updated += result.modified_count;
+        }
+        Ok(updated)
+    }
+
+    // 把已写满的数组点块改写为压缩编码，不受 TRACK_POINT_ENCODING 影响；dry_run 时只统计不写入
+    pub async fn pack_points(&self, dry_run: bool) -> mongodb::error::Result<PackStats> {
+        let full = doc! {
+            "coordinates": { "$type": "array" },
+            format!("coordinates.{}", self.segment_points - 1): { "$exists": true },
+        };
+        let mut stats = PackStats::default();
+        let collections = [self.collection.clone_with_type::<Document>(), self.segments.clone_with_type::<Document>()];
+        for (i, collection) in collections.iter().enumerate() {
+            let ids: Vec<ObjectId> = collection
+                .find(full.clone())
+                .projection(doc! { "_id": 1 })
+                .await?
+                .try_filter_map(|d| async move { Ok(d.get_object_id("_id").ok()) })
+                .try_collect()
+                .await?;
+            for id in ids {
+                match pack_chunk(collection, id, dry_run).await? {
+                    PackOutcome::Packed(packed) => {
+                        if i == 0 { stats.tracks += 1 } else { stats.segments += 1 }
+                        stats.add(packed);
+                    }
+                    PackOutcome::Skipped => stats.skipped += 1,
+                    PackOutcome::Inexact => stats.inexact += 1,
+                }
+            }
+        }
+        Ok(stats)
+    }
+
+    // 在航迹文档和分段上执行同一个空间条件，返回命中的航迹 ID（航迹文档命中的在前，保持其顺序）
+    async fn matching_track_ids(&self, tenant: &Tenant, condition: Document) -> mongodb::error::Result<Vec<ObjectId>> {
+        let mut ids: Vec<ObjectId> = Vec::new();
+        let mut cursor = self.collection
+            .clone_with_type::<Document>()
+            .find(active(tenant.scope(doc! { "geometry": condition.clone() })))
+            .projection(doc! { "_id": 1 })
+            .limit(MAX_PAGE_SIZE)
+            .await?;
+        while let Some(track) = cursor.try_next().await? {
+            if let Ok(id) = track.get_object_id("_id") {
+                ids.push(id);
+            }
+        }
+        let mut cursor = self.segments
+            .clone_with_type::<Document>()
+            .find(tenant.scope(doc! { "geometry": condition }))
+            .projection(doc! { "trackId": 1 })
+            .await?;
+        while let Some(segment) = cursor.try_next().await? {
+            if let Ok(id) = segment.get_object_id("trackId")
+                && !ids.contains(&id)
+            {
+                ids.push(id);
+            }
+        }
+        ids.truncate(MAX_PAGE_SIZE as usize);
+        Ok(ids)
+    }
+
+    // 按给定顺序返回航迹摘要，分段命中的航迹要再确认航迹本身未被删除
+    async fn summaries(&self, tenant: &Tenant, ids: &[ObjectId]) -> mongodb::error::Result<Vec<ShipTrackSummary>> {
+        let mut summaries: Vec<ShipTrackSummary> = self.collection
+            .clone_with_type::<ShipTrackSummary>()
+            .find(active(tenant.scope(doc! { "_id": { "$in": ids } })))
+            .projection(summary_projection())
+            .await?
+            .try_collect()
+            .await?;
+        summaries.sort_by_key(|t| ids.iter().position(|id| *id == t.id));
+        Ok(summaries)
+    }
+
+    // 经过某点 maxDistance 米范围内的航迹，按航迹文档和分段中离该点最近的距离由近到远
+    pub async fn near(&self, tenant: &Tenant, query: NearQuery) -> Result<Vec<ShipTrackSummary>, AppError> {
+        if !(-180.0..=180.0).contains(&query.lon) || !(-90.0..=90.0).contains(&query.lat) {
+            return Err(AppError::BadRequest("lon/lat out of range".to_string()));
+        }
+        let max_distance = query.max_distance.unwrap_or(DEFAULT_NEAR_DISTANCE_M);
+        // 两个集合各自按航迹取最近距离，再合并排序
+        let sources = [
+            (self.collection.clone_with_type::<Document>(), active(tenant.scope(doc! {})), "$_id"),
+            (self.segments.clone_with_type::<Document>(), tenant.scope(doc! {}), "$trackId"),
+        ];
+        let mut nearest: HashMap<ObjectId, f64> = HashMap::new();
+        for (collection, filter, track_id) in sources {
+            let pipeline = vec![
+                doc! { "$geoNear": {
+                    "near": { "type": "Point", "coordinates": [query.lon, query.lat] },
+                    "key": "geometry",
+                    "distanceField": "distance",
+                    "maxDistance": max_distance,
+                    "spherical": true,
+                    "query": filter,
+                } },
+                doc! { "$group": { "_id": track_id, "distance": { "$min": "$distance" } } },
+                doc! { "$sort": { "distance": 1 } },
+                doc! { "$limit": MAX_PAGE_SIZE },
+            ];
+            let mut cursor = collection.aggregate(pipeline).await?;
+            while let Some(hit) = cursor.try_next().await? {
+                if let (Ok(id), Ok(distance)) = (hit.get_object_id("_id"), hit.get_f64("distance")) {
+                    let entry = nearest.entry(id).or_insert(distance);
+                    *entry = entry.min(distance);
+                }
+            }
+        }
+        let mut hits: Vec<(ObjectId, f64)> = nearest.into_iter().collect();
+        hits.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let ids: Vec<ObjectId> = hits.into_iter().take(MAX_PAGE_SIZE as usize).map(|(id, _)| id).collect();
+        Ok(self.summaries(tenant, &ids).await?)
+    }
+
+    // 与给定 GeoJSON 几何相交的航迹
+    pub async fn intersects(&self, tenant: &Tenant, geometry: Document) -> Result<Vec<ShipTrackSummary>, AppError> {
+        validate_geometry(&geometry, &["Point", "MultiPoint", "LineString", "MultiLineString", "Polygon", "MultiPolygon"])?;
+        let ids = self.matching_track_ids(tenant, doc! { "$geoIntersects": { "$geometry": geometry } }).await?;
+        Ok(self.summaries(tenant, &ids).await?)
+    }
+
+    // 完全位于多边形内的航迹：航迹文档和它的所有分段都必须在多边形内
+    pub async fn within(&self, tenant: &Tenant, polygon: Document) -> Result<Vec<ShipTrackSummary>, AppError> {
+        validate_geometry(&polygon, &["Polygon", "MultiPolygon"])?;
+        let condition = doc! { "$geoWithin": { "$geometry": polygon } };
+        let mut candidates: Vec<(ObjectId, usize)> = Vec::new();
+        let mut cursor = self.collection
+            .clone_with_type::<Document>()
+            .find(active(tenant.scope(doc! { "geometry": condition.clone() })))
+            .projection(doc! { "_id": 1, "segments.seq": 1 })
+            .limit(MAX_PAGE_SIZE)
+            .await?;
+        while let Some(track) = cursor.try_next().await? {
+            if let Ok(id) = track.get_object_id("_id") {
+                candidates.push((id, track.get_array("segments").map(|s| s.len()).unwrap_or(0)));
+            }
+        }
+        let segmented: Vec<ObjectId> = candidates.iter().filter(|(_, count)| *count > 0).map(|(id, _)| *id).collect();
+        let mut inside: HashMap<ObjectId, usize> = HashMap::new();
+        if !segmented.is_empty() {
+            let mut cursor = self.segments
+                .clone_with_type::<Document>()
+                .find(tenant.scope(doc! { "trackId": { "$in": segmented }, "geometry": condition }))
+                .projection(doc! { "trackId": 1 })
+                .await?;
+            while let Some(segment) = cursor.try_next().await? {
+                if let Ok(id) = segment.get_object_id("trackId") {
+                    *inside.entry(id).or_default() += 1;
+                }
+            }
+        }
+        let ids: Vec<ObjectId> = candidates.into_iter()
+            .filter(|(id, count)| *count == inside.get(id).copied().unwrap_or(0))
+            .map(|(id, _)| id)
+            .collect();
+        Ok(self.summaries(tenant, &ids).await?)
+    }
+
+    // 按无人机和月份汇总飞行距离，使用缓存的统计值
+    pub async fn monthly_distance(&self, tenant: &Tenant, query: MonthlyDistanceQuery) -> mongodb::error::Result<Vec<MonthlyDistanceDto>> {
+        let mut filter = active(tenant.scope(doc! {}));
+        if let Some(range) = time_range(query.from, query.to) {
+            filter.insert("startTime", range);
+        }
+        if let Some(drone_id) = query.drone_id {
+            filter.insert("droneId", drone_id);
+        }
+        let pipeline = vec![
+            doc! { "$match": filter },
+            doc! { "$group": {
+                "_id": {
+                    "droneId": "$droneId",
+                    "month": { "$dateToString": { "format": "%Y-%m", "date": "$startTime" } },
+                },
+                "trackCount": { "$sum": 1 },
+                "distanceMeters": { "$sum": { "$ifNull": ["$stats.distanceMeters", 0.0] } },
+            } },
+            doc! { "$project": {
+                "_id": 0,
+                "droneId": "$_id.droneId",
+                "month": "$_id.month",
+                "trackCount": { "$toLong": "$trackCount" },
+                "distanceMeters": { "$toDouble": "$distanceMeters" },
+            } },
+            doc! { "$sort": { "month": 1, "droneId": 1 } },
+        ];
+        let mut cursor = self.collection.aggregate(pipeline).await?;
+        let mut rows = Vec::new();
+        while let Some(row) = cursor.try_next().await? {
+            rows.push(bson::from_document(row)?);
+        }
+        Ok(rows)
+    }
+
+    // 软删除，移入回收站
+    pub async fn delete(&self, id: &str, ctx: &AuditContext) -> Result<(), AppError> {
+        let obj_id = ObjectId::parse_str(id).map_err(|e| AppError::BadRequest(format!("Invalid ObjectId: {}", e)))?;
+        let deleted = soft_delete_one(&self.collection, ctx.tenant.scope(doc! {"_id": obj_id}), ctx).await?;
+        if deleted.is_some() {
+            let after = doc! { "deletedBy": ctx.actor.clone() };
+            self.audit.record(ctx.entry("delete", "track", Some(obj_id.to_hex()), None, Some(after))).await?;
+        }
+        Ok(())
+    }
+
+    // 按筛选条件批量软删除，需要两步确认，与报告的批量删除相同
+    pub async fn bulk_delete(&self, request: BulkDeleteRequestDto<TrackBulkDeleteFilter>, ctx: &AuditContext) -> Result<serde_json::Value, AppError> {
+        let filter = ctx.tenant.scope(bulk_delete_filter(request.filter)?);
+        self.bulk_delete.run(&self.collection, &self.audit, "track", filter, request.confirmation_token, ctx).await
+    }
+
+    pub async fn restore(&self, id: &str, ctx: &AuditContext) -> Result<Option<ShipTrack>, AppError> {
+        let obj_id = ObjectId::parse_str(id).map_err(|e| AppError::BadRequest(format!("Invalid ObjectId: {}", e)))?;
+        let restored = restore_one(&self.collection, ctx.tenant.scope(doc! {"_id": obj_id})).await?;
+        let Some(restored) = restored else {
+            return Ok(None);
+        };
+        self.audit.record(ctx.entry("restore", "track", Some(obj_id.to_hex()), None, None)).await?;
+        Ok(Some(self.stitch(restored).await?))
+    }
+
+    pub async fn get_trash(&self, tenant: &Tenant) -> mongodb::error::Result<Vec<ShipTrack>> {
+        let tracks = list_trash(&self.collection, tenant.scope(doc! {})).await?;
+        self.stitch_all(tracks).await
+    }
+
+    // 由定时任务调用，清除超过保留期的回收站航迹
+    pub async fn purge_expired(&self, retention: Duration) -> mongodb::error::Result<usize> {
+        let purged = purge_expired(&self.collection, retention, |item| item.id).await?;
+        let ids: Vec<ObjectId> = purged.iter().map(|t| t.id).collect();
+        if !ids.is_empty() {
+            self.segments.delete_many(doc! { "trackId": { "$in": ids } }).await?;
+        }
+        for track in &purged {
+            let ctx = AuditContext::system("purge_expired", track.tenant());
+            self.audit.record(ctx.entry("purge", "track", Some(track.id.to_hex()), Some(track.audit_summary()), None)).await?;
+        }
+        Ok(purged.len())
+    }
+
+    pub async fn get_latest(&self, tenant: &Tenant) -> mongodb::error::Result<Option<ShipTrack>> {
         let find_options = FindOneOptions::builder().sort(doc! {"lastUpdate": -1}).build();
-        self.collection.find_one(doc! {}).with_options(find_options).await
+        match self.collection.find_one(active(tenant.scope(doc! {}))).with_options(find_options).await? {
+            Some(track) => Ok(Some(self.stitch(track).await?)),
+            None => Ok(None),
+        }
+    }
+
+    // 最近有更新的航迹，按 lastUpdate 倒序，用于实时订阅的初始快照
+    pub async fn get_active(&self, tenant: &Tenant, since: chrono::DateTime<Utc>, limit: i64) -> mongodb::error::Result<Vec<ShipTrack>> {
+        let find_options = FindOptions::builder().sort(doc! { "lastUpdate": -1 }).limit(limit).build();
+        let tracks: Vec<ShipTrack> = self.collection
+            .find(active(tenant.scope(doc! { "lastUpdate": { "$gte": DateTime::from_chrono(since) } })))
+            .with_options(find_options)
+            .await?
+            .try_collect()
+            .await?;
+        self.stitch_all(tracks).await
+    }
+
+    // 按固定时间间隔插值回放航迹，被过滤的点不参与插值（raw 时除外）
+    pub async fn replay(&self, tenant: &Tenant, id: &str, query: &ReplayQuery) -> Result<(ObjectId, Option<ObjectId>, Replay), AppError> {
+        let step = query.step_ms().map_err(AppError::BadRequest)?;
+        let obj_id = ObjectId::parse_str(id).map_err(|e| AppError::BadRequest(format!("Invalid ObjectId: {}", e)))?;
+        let mut track = self.get(tenant, &obj_id.to_hex()).await?
+            .ok_or_else(|| AppError::NotFound("Track not found".to_string()))?;
+        if !query.raw {
+            self.denoise(tenant, std::slice::from_mut(&mut track)).await?;
+        }
+        let flight = match query.telemetry {
+            true => self.flights.find_by_track(tenant, obj_id).await?,
+            false => None,
+        };
+        let flight_id = flight.as_ref().map(|f| f.id);
+        let from = query.from.map(|t| t.timestamp_millis());
+        let to = query.to.map(|t| t.timestamp_millis());
+        let replay = Replay::new(&track.coordinates, from, to, step, query.crs, flight)
+            .ok_or_else(|| AppError::BadRequest("No timestamped points in the requested range".to_string()))?;
+        if replay.frame_count() > MAX_REPLAY_FRAMES {
+            return Err(AppError::BadRequest(format!("Replay would produce more than {} frames, increase step or narrow the range", MAX_REPLAY_FRAMES)));
+        }
+        Ok((obj_id, flight_id, replay))
+    }
+
+    // 静态地图要绘制的内容。围栏只取启用的，风机位置来自风机避让围栏
+    pub async fn map_scene(&self, tenant: &Tenant, id: &str, raw: bool, geofences: bool, turbines: bool) -> Result<MapScene, AppError> {
+        let obj_id = ObjectId::parse_str(id).map_err(|e| AppError::BadRequest(format!("Invalid ObjectId: {}", e)))?;
+        let mut track = self.get(tenant, &obj_id.to_hex()).await?
+            .ok_or_else(|| AppError::NotFound("Track not found".to_string()))?;
+        if !raw {
+            self.denoise(tenant, std::slice::from_mut(&mut track)).await?;
+        }
+        let enabled: Vec<Geofence> = match geofences || turbines {
+            true => self.geofences.list(tenant).await?.into_iter().filter(|g| g.enabled).collect(),
+            false => Vec::new(),
+        };
+        Ok(MapScene {
+            track: track.positions(),
+            turbines: if turbines { turbine_positions(&enabled) } else { Vec::new() },
+            geofences: if geofences { enabled } else { Vec::new() },
+        })
+    }
+
+    // 时间范围内所有航迹的点按 geohash 单元格聚合。航迹和分段都用游标逐个读取，不拼接整条航迹，
+    // 时间范围之外的分段按分段索引跳过
+    pub async fn heatmap(&self, tenant: &Tenant, query: &HeatmapQuery) -> Result<HeatmapGrid, AppError> {
+        let precision = query.precision().map_err(AppError::BadRequest)?;
+        let mut filter = active(tenant.scope(doc! {}));
+        if let Some(to) = query.to {
+            filter.insert("startTime", doc! { "$lte": DateTime::from_chrono(to) });
+        }
+        if let Some(from) = query.from {
+            filter.insert("lastUpdate", doc! { "$gte": DateTime::from_chrono(from) });
+        }
+        if let Some(drone_id) = &query.drone_id {
+            filter.insert("droneId", drone_id);
+        }
+        let range = PointRange { from: query.from, to: query.to, ..Default::default() };
+        let mut grid = HeatmapGrid::new(precision, query.from, query.to);
+        let mut tracks = self.collection
+            .clone_with_type::<HeatmapSource>()
+            .find(filter)
+            .projection(doc! { "coordinates": 1, "segments": 1 })
+            .await?;
+        while let Some(track) = tracks.try_next().await? {
+            grid.start_track();
+            grid.extend(&track.coordinates).map_err(AppError::BadRequest)?;
+            let wanted: Vec<ObjectId> = track.segments.iter().filter(|s| s.overlaps(&range)).map(|s| s.id).collect();
+            if wanted.is_empty() {
+                continue;
+            }
+            let mut segments = self.segments
+                .clone_with_type::<StoredPoints>()
+                .find(tenant.scope(doc! { "trackId": track.id, "_id": { "$in": wanted } }))
+                .projection(doc! { "coordinates": 1 })
+                .sort(doc! { "seq": 1 })
+                .await?;
+            while let Some(segment) = segments.try_next().await? {
+                grid.extend(&segment.coordinates).map_err(AppError::BadRequest)?;
+            }
+        }
+        Ok(grid)
+    }
+
+    // 在给定下标、时间或所有架次间隔处拆分航迹。原航迹保留第一段，返回原航迹和拆出的新航迹
+    pub async fn split(&self, id: &str, request: SplitRequestDto, ctx: &AuditContext) -> Result<Vec<ShipTrack>, AppError> {
+        let obj_id = ObjectId::parse_str(id).map_err(|e| AppError::BadRequest(format!("Invalid ObjectId: {}", e)))?;
+        self.with_append_locks(vec![obj_id], async || {
+            self.complete_pending(ctx.tenant.scope(doc! { "_id": obj_id })).await?;
+            let mut track = self.get(&ctx.tenant, id).await?
+                .ok_or_else(|| AppError::NotFound("Track not found".to_string()))?;
+            let boundaries = request.boundaries(&track.coordinates).map_err(AppError::BadRequest)?;
+            if boundaries.is_empty() {
+                return Ok(vec![track]);
+            }
+            let before = track.audit_summary();
+            let config = self.tenants.track_filter(&ctx.tenant).await?;
+            let mut parts: Vec<ShipTrack> = boundaries.iter().rev().map(|at| track.split_off(*at)).collect();
+            parts.reverse();
+            // 先写入新航迹再截断原航迹，中途失败时不会丢点
+            for part in &mut parts {
+                self.save_edited(part, &config, false).await?;
+                self.audit.record(ctx.entry("split", "track", Some(part.id.to_hex()), None, Some(part.audit_summary()))).await?;
+            }
+            self.save_edited(&mut track, &config, true).await?;
+            self.audit.record(ctx.entry("split", "track", Some(obj_id.to_hex()), Some(before), Some(track.audit_summary()))).await?;
+            parts.insert(0, track);
+            Ok(parts)
+        }).await
+    }
+
+    // 把另一条航迹的点按时间合并进本航迹，另一条航迹移入回收站
+    pub async fn merge(&self, id: &str, request: MergeRequestDto, ctx: &AuditContext) -> Result<ShipTrack, AppError> {
+        let parse = |id: &str| ObjectId::parse_str(id).map_err(|e| AppError::BadRequest(format!("Invalid ObjectId: {}", e)));
+        let (obj_id, other_id) = (parse(id)?, parse(&request.track_id)?);
+        if obj_id == other_id {
+            return Err(AppError::BadRequest("Cannot merge a track into itself".to_string()));
+        }
+        self.with_append_locks(vec![obj_id, other_id], async || {
+            self.complete_pending(ctx.tenant.scope(doc! { "_id": { "$in": [obj_id, other_id] } })).await?;
+            let mut track = self.get(&ctx.tenant, id).await?
+                .ok_or_else(|| AppError::NotFound("Track not found".to_string()))?;
+            let other = self.get(&ctx.tenant, &request.track_id).await?
+                .ok_or_else(|| AppError::NotFound("Track to merge not found".to_string()))?;
+            let before = track.audit_summary();
+            let config = self.tenants.track_filter(&ctx.tenant).await?;
+            // 时间相同的点，开始较早的航迹在前
+            let coordinates = std::mem::take(&mut track.coordinates);
+            track.coordinates = match other.start_time < track.start_time {
+                true => merge_by_time(other.coordinates, coordinates),
+                false => merge_by_time(coordinates, other.coordinates),
+            };
+            track.start_time = track.start_time.min(other.start_time);
+            track.last_update = track.last_update.max(other.last_update);
+            track.drone_id = track.drone_id.or(other.drone_id);
+            track.owner = track.owner.or(other.owner);
+            track.mission_id = track.mission_id.or(other.mission_id);
+            self.save_edited(&mut track, &config, true).await?;
+            self.audit.record(ctx.entry("merge", "track", Some(obj_id.to_hex()), Some(before), Some(track.audit_summary()))).await?;
+            if soft_delete_one(&self.collection, ctx.tenant.scope(doc! {"_id": other_id}), ctx).await?.is_some() {
+                let after = doc! { "mergedInto": obj_id.to_hex(), "deletedBy": ctx.actor.clone() };
+                self.audit.record(ctx.entry("delete", "track", Some(other_id.to_hex()), None, Some(after))).await?;
+            }
+            Ok(track)
+        }).await
+    }
+
+    // 去掉范围之外的首尾点，范围内第一个到最后一个点之间的点全部保留
+    pub async fn trim(&self, id: &str, range: PointRange, ctx: &AuditContext) -> Result<ShipTrack, AppError> {
+        let obj_id = ObjectId::parse_str(id).map_err(|e| AppError::BadRequest(format!("Invalid ObjectId: {}", e)))?;
+        if range.is_unbounded() {
+            return Err(AppError::BadRequest("One of start, end, from or to is required".to_string()));
+        }
+        self.with_append_locks(vec![obj_id], async || {
+            self.complete_pending(ctx.tenant.scope(doc! { "_id": obj_id })).await?;
+            let mut track = self.get(&ctx.tenant, id).await?
+                .ok_or_else(|| AppError::NotFound("Track not found".to_string()))?;
+            let span = range.span(&track.coordinates)
+                .ok_or_else(|| AppError::BadRequest("No points in the given range".to_string()))?;
+            if span.len() == track.coordinates.len() {
+                return Ok(track);
+            }
+            let before = track.audit_summary();
+            let config = self.tenants.track_filter(&ctx.tenant).await?;
+            track.coordinates.truncate(span.end);
+            track.coordinates.drain(..span.start);
+            self.save_edited(&mut track, &config, true).await?;
+            self.audit.record(ctx.entry("trim", "track", Some(obj_id.to_hex()), Some(before), Some(track.audit_summary()))).await?;
+            Ok(track)
+        }).await
+    }
+
+    // 按时间间隔识别航迹中的架次，不修改航迹
+    pub async fn sorties(&self, tenant: &Tenant, id: &str, query: &SortieQuery) -> Result<Vec<SortieDto>, AppError> {
+        let gap = query.gap_millis().map_err(AppError::BadRequest)?;
+        let obj_id = ObjectId::parse_str(id).map_err(|e| AppError::BadRequest(format!("Invalid ObjectId: {}", e)))?;
+        let track = self.get(tenant, &obj_id.to_hex()).await?
+            .ok_or_else(|| AppError::NotFound("Track not found".to_string()))?;
+        Ok(detect_sorties(&track.coordinates, gap).into_iter().map(|range| SortieDto::new(&track.coordinates, range)).collect())
+    }
+
+    // 拆分、合并、裁剪后整条重写：重新判定噪声点，重新计算统计值、点数、起止时间和分段。
+    // existing 为 false 时写入新航迹
+    async fn save_edited(&self, track: &mut ShipTrack, config: &TrackFilterConfig, existing: bool) -> Result<(), AppError> {
+        track.reject_noise(config);
+        track.stats = Some(TrackStats::compute(&track.coordinates));
+        track.total_points = track.coordinates.len() as u32;
+        track.refresh_time_range();
+        // 点的下标已经变化，批次记录中的范围不再有效；批次序号保留，重试仍能识别
+        track.recent_batches.clear();
+        let segments = self.split_segments(track);
+        let replace = existing.then(|| active(track.tenant().scope(doc! { "_id": track.id })));
+        if !self.store(track, &segments, replace).await? {
+            return Err(AppError::NotFound("Track not found".to_string()));
+        }
+        Ok(())
+    }
+
+    // 关联到某个任务的航迹，最近开始的在前
+    pub async fn get_by_mission(&self, tenant: &Tenant, mission_id: ObjectId, limit: i64) -> mongodb::error::Result<Vec<ShipTrack>> {
+        let tracks: Vec<ShipTrack> = self.collection
+            .find(active(tenant.scope(doc! { "missionId": mission_id })))
+            .sort(doc! { "startTime": -1 })
+            .limit(limit)
+            .await?
+            .try_collect()
+            .await?;
+        self.stitch_all(tracks).await
+    }
+
+    // 把航迹关联到任务，返回航迹是否存在
+    pub async fn link_mission(&self, id: ObjectId, mission_id: ObjectId, ctx: &AuditContext) -> mongodb::error::Result<bool> {
+        self.set_mission(doc! { "_id": id }, doc! { "$set": { "missionId": mission_id } }, Some(mission_id), ctx).await
+    }
+
+    // 只解除与该任务的关联，航迹已关联到其他任务时不变
+    pub async fn unlink_mission(&self, id: ObjectId, mission_id: ObjectId, ctx: &AuditContext) -> mongodb::error::Result<bool> {
+        self.set_mission(doc! { "_id": id, "missionId": mission_id }, doc! { "$unset": { "missionId": "" } }, None, ctx).await
+    }
+
+    async fn set_mission(&self, filter: Document, update: Document, mission_id: Option<ObjectId>, ctx: &AuditContext) -> mongodb::error::Result<bool> {
+        let id = filter.get_object_id("_id").ok().map(|id| id.to_hex());
+        let result = self.collection.update_one(active(ctx.tenant.scope(filter)), update).await?;
+        if result.matched_count == 0 {
+            return Ok(false);
+        }
+        let after = doc! { "missionId": mission_id.map(|m| m.to_hex()) };
+        self.audit.record(ctx.entry("update", "track", id, None, Some(after))).await?;
+        Ok(true)
+    }
+
+    // 按给定顺序批量获取航迹，不存在或无权访问的 ID 会被跳过
+    pub async fn get_many(&self, tenant: &Tenant, ids: &[ObjectId]) -> mongodb::error::Result<Vec<ShipTrack>> {
+        let mut tracks: Vec<ShipTrack> = self.collection
+            .find(active(tenant.scope(doc! { "_id": { "$in": ids } })))
+            .await?
+            .try_collect()
+            .await?;
+        tracks.sort_by_key(|t| ids.iter().position(|id| *id == t.id));
+        self.stitch_all(tracks).await
+    }
+
+    // 按租户的过滤配置处理要返回的航迹，配置只读取一次
+    pub async fn denoise(&self, tenant: &Tenant, tracks: &mut [ShipTrack]) -> mongodb::error::Result<()> {
+        let config = self.tenants.track_filter(tenant).await?;
+        tracks.iter_mut().for_each(|track| track.denoise(&config));
+        Ok(())
+    }
+
+    pub async fn track_filter(&self, tenant: &Tenant) -> mongodb::error::Result<TrackFilterConfig> {
+        self.tenants.track_filter(tenant).await
+    }
+
+    // 分页列出航迹摘要，游标为上一页最后一条的 "排序值.ID"
+    pub async fn list(&self, tenant: &Tenant, query: TrackListQuery) -> Result<TrackPageDto<ShipTrackSummary>, AppError> {
+        let sort_field = match query.sort.as_deref().unwrap_or("startTime") {
+            field @ ("startTime" | "lastUpdate" | "totalPoints") => field,
+            other => return Err(AppError::BadRequest(format!("Unsupported sort field: {}", other))),
+        };
+        let ascending = match query.order.as_deref().unwrap_or("desc") {
+            "asc" => true,
+            "desc" => false,
+            other => return Err(AppError::BadRequest(format!("Unsupported sort order: {}", other))),
+        };
+        let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+        let mut filter = active(tenant.scope(doc! {}));
+        if let Some(range) = time_range(query.start_from, query.start_to) {
+            filter.insert("startTime", range);
+        }
+        if let Some(range) = time_range(query.updated_from, query.updated_to) {
+            filter.insert("lastUpdate", range);
+        }
+        if let Some(drone_id) = query.drone_id {
+            filter.insert("droneId", drone_id);
+        }
+        if let Some(owner) = query.owner {
+            filter.insert("owner", owner);
+        }
+        let mut points = doc! {};
+        if let Some(min) = query.min_points {
+            points.insert("$gte", min as i64);
+        }
+        if let Some(max) = query.max_points {
+            points.insert("$lte", max as i64);
+        }
+        if !points.is_empty() {
+            filter.insert("totalPoints", points);
+        }
+        if let Some(cursor) = query.cursor {
+            let (value, id) = decode_cursor(sort_field, &cursor)?;
+            let op = if ascending { "$gt" } else { "$lt" };
+            let after_cursor = doc! { "$or": [
+                { sort_field: { op: value.clone() } },
+                { sort_field: value, "_id": { op: id } },
+            ] };
+            filter = doc! { "$and": [filter, after_cursor] };
+        }
+
+        let direction = if ascending { 1 } else { -1 };
+        let options = FindOptions::builder()
+            .sort(doc! { sort_field: direction, "_id": direction })
+            .projection(summary_projection())
+            .limit(limit)
+            .build();
+        let items: Vec<ShipTrackSummary> = self.collection
+            .clone_with_type::<ShipTrackSummary>()
+            .find(filter)
+            .with_options(options)
+            .await?
+            .try_collect()
+            .await?;
+        let next_cursor = if items.len() as i64 == limit {
+            items.last().map(|last| encode_cursor(sort_field, last))
+        } else {
+            None
+        };
+        Ok(TrackPageDto { items, next_cursor })
+    }
+}
+
+// bbox 转换为 GeoJSON 多边形
+pub fn bbox_polygon(bbox: &str) -> Result<Document, AppError> {
+    let values: Vec<f64> = bbox.split(',').map(|v| v.trim().parse::<f64>()).collect::<Result<_, _>>()
+        .map_err(|_| AppError::BadRequest("bbox must be minLon,minLat,maxLon,maxLat".to_string()))?;
+    let [min_lon, min_lat, max_lon, max_lat] = values[..] else {
+        return Err(AppError::BadRequest("bbox must be minLon,minLat,maxLon,maxLat".to_string()));
+    };
+    if min_lon >= max_lon || min_lat >= max_lat {
+        return Err(AppError::BadRequest("bbox minimum must be less than maximum".to_string()));
+    }
+    Ok(doc! { "type": "Polygon", "coordinates": [[
+        [min_lon, min_lat], [max_lon, min_lat], [max_lon, max_lat], [min_lon, max_lat], [min_lon, min_lat],
+    ]] })
+}
+
+fn validate_geometry(geometry: &Document, allowed: &[&str]) -> Result<(), AppError> {
+    match geometry.get_str("type") {
+        Ok(kind) if allowed.contains(&kind) && geometry.contains_key("coordinates") => Ok(()),
+        _ => Err(AppError::BadRequest(format!("Expected a GeoJSON geometry of type {}", allowed.join(" / ")))),
     }
 }
 
+// 把一个数组格式的点块改写为压缩编码，返回点数和改写前后 coordinates 的字节数。
+// 块已压缩或含有无法编码的值时返回 None
+async fn pack_chunk(collection: &Collection<Document>, id: ObjectId, dry_run: bool) -> mongodb::error::Result<PackOutcome> {
+    let filter = doc! { "_id": id, "coordinates": { "$type": "array" } };
+    let Some(chunk) = collection.clone_with_type::<StoredPoints>().find_one(filter.clone()).projection(doc! { "coordinates": 1 }).await? else {
+        return Ok(PackOutcome::Skipped);
+    };
+    let Some(bytes) = point_codec::encode(&chunk.coordinates) else {
+        return Ok(PackOutcome::Inexact);
+    };
+    let array = Bson::Array(chunk.coordinates.iter().map(bson::to_bson).collect::<Result<_, _>>()?);
+    let packed = PackedChunk {
+        points: chunk.coordinates.len() as u64,
+        array_bytes: bson::to_vec(&doc! { "coordinates": array })?.len() as u64,
+        packed_bytes: bson::to_vec(&doc! { "coordinates": Binary { subtype: BinarySubtype::Generic, bytes: bytes.clone() } })?.len() as u64,
+    };
+    if !dry_run {
+        collection.update_one(filter, doc! { "$set": { "coordinates": Binary { subtype: BinarySubtype::Generic, bytes } } }).await?;
+    }
+    Ok(PackOutcome::Packed(packed))
+}
 
+fn bulk_delete_filter(filter: TrackBulkDeleteFilter) -> Result<Document, AppError> {
+    let parse = |id: &String| ObjectId::parse_str(id).map_err(|e| AppError::BadRequest(format!("Invalid ObjectId: {}", e)));
+    let mut query = doc! {};
+    if let Some(ids) = filter.ids {
+        query.insert("_id", doc! { "$in": ids.iter().map(parse).collect::<Result<Vec<_>, _>>()? });
+    }
+    if let Some(drone_id) = filter.drone_id {
+        query.insert("droneId", drone_id);
+    }
+    if let Some(owner) = filter.owner {
+        query.insert("owner", owner);
+    }
+    if let Some(mission_id) = filter.mission_id {
+        query.insert("missionId", parse(&mission_id)?);
+    }
+    if let Some(range) = time_range(filter.start_from, filter.start_to) {
+        query.insert("startTime", range);
+    }
+    if query.is_empty() {
+        return Err(AppError::BadRequest("Bulk delete requires at least one filter".to_string()));
+    }
+    Ok(query)
+}
 
+fn time_range(from: Option<chrono::DateTime<Utc>>, to: Option<chrono::DateTime<Utc>>) -> Option<Document> {
+    let mut range = doc! {};
+    if let Some(from) = from {
+        range.insert("$gte", DateTime::from_chrono(from));
+    }
+    if let Some(to) = to {
+        range.insert("$lte", DateTime::from_chrono(to));
+    }
+    (!range.is_empty()).then_some(range)
+}
 
+fn encode_cursor(sort_field: &str, summary: &ShipTrackSummary) -> String {
+    let value = match sort_field {
+        "lastUpdate" => summary.last_update.timestamp_millis(),
+        "totalPoints" => summary.total_points as i64,
+        _ => summary.start_time.timestamp_millis(),
+    };
+    format!("{}.{}", value, summary.id.to_hex())
+}
 
+fn decode_cursor(sort_field: &str, cursor: &str) -> Result<(Bson, ObjectId), AppError> {
+    let invalid = || AppError::BadRequest("Invalid cursor".to_string());
+    let (value, id) = cursor.split_once('.').ok_or_else(invalid)?;
+    let value: i64 = value.parse().map_err(|_| invalid())?;
+    let id = ObjectId::parse_str(id).map_err(|_| invalid())?;
+    let value = match sort_field {
+        "totalPoints" => Bson::Int64(value),
+        _ => Bson::DateTime(DateTime::from_millis(value)),
+    };
+    Ok((value, id))
+}
@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use bson::{doc, Bson, DateTime, Document};
+use bson::oid::ObjectId;
+use chrono::Utc;
+use futures::TryStreamExt;
+use mongodb::options::{FindOneAndUpdateOptions, ReturnDocument};
+use mongodb::Collection;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use uuid::Uuid;
+use crate::error::AppError;
+use crate::model::audit_log::AuditContext;
+use crate::model::tenant::Tenant;
+use crate::service::audit_service::AuditService;
+use crate::service::flight_service::FlightService;
+use crate::service::geofence_service::GeofenceService;
+use crate::service::mission_service::MissionService;
+use crate::service::report_raw_service::ReportRawService;
+use crate::service::ship_track_service::ShipTrackService;
+
+// 回收站清理间隔
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+// 批量删除确认令牌的有效期
+const BULK_DELETE_TOKEN_TTL: Duration = Duration::from_secs(300);
+
+// 软删除的公共实现，航迹、报告、飞行记录共用。
+// 文档被删除时只写入 deletedAt / deletedBy，超过保留期后由定时任务真正清除。
+
+// 只匹配未删除的文档
+pub fn active(mut filter: Document) -> Document {
+    filter.insert("deletedAt", Bson::Null);
+    filter
+}
+
+// 只匹配回收站中的文档
+pub fn trashed(mut filter: Document) -> Document {
+    filter.insert("deletedAt", doc! { "$ne": Bson::Null });
+    filter
+}
+
+pub fn mark_deleted(ctx: &AuditContext) -> Document {
+    let now: DateTime = Utc::now().into();
+    doc! { "$set": { "deletedAt": now, "deletedBy": ctx.actor.clone() } }
+}
+
+pub async fn soft_delete_one<T>(
+    collection: &Collection<T>,
+    filter: Document,
+    ctx: &AuditContext,
+) -> mongodb::error::Result<Option<T>>
+where
+    T: DeserializeOwned + Serialize + Send + Sync,
+{
+    let options = FindOneAndUpdateOptions::builder()
+        .return_document(ReturnDocument::After)
+        .build();
+    collection
+        .find_one_and_update(active(filter), mark_deleted(ctx))
+        .with_options(options)
+        .await
+}
+
+struct PendingBulkDelete {
+    tenant: Tenant,
+    filter: Document,
+    expires_at: Instant,
+}
+
+// 按筛选条件批量软删除，需要两步确认：第一次请求不带令牌，只返回匹配数量和确认令牌；
+// 第二次携带相同筛选条件和令牌才真正执行删除
+#[derive(Default)]
+pub struct BulkDelete {
+    pending: Mutex<HashMap<String, PendingBulkDelete>>,
+}
+
+impl BulkDelete {
+    // filter 为调用方按租户限定后的筛选条件，entity 为审计日志中的实体类型
+    pub async fn run<T>(
+        &self,
+        collection: &Collection<T>,
+        audit: &AuditService,
+        entity: &str,
+        filter: Document,
+        token: Option<String>,
+        ctx: &AuditContext,
+    ) -> Result<serde_json::Value, AppError>
+    where
+        T: Send + Sync,
+    {
+        let filter = active(filter);
+        let Some(token) = token else {
+            let matched_count = collection.count_documents(filter.clone()).await?;
+            let token = Uuid::new_v4().to_string();
+            let mut pending = self.pending.lock().unwrap();
+            pending.retain(|_, p| p.expires_at > Instant::now());
+            pending.insert(token.clone(), PendingBulkDelete {
+                tenant: ctx.tenant.clone(),
+                filter,
+                expires_at: Instant::now() + BULK_DELETE_TOKEN_TTL,
+            });
+            return Ok(serde_json::json!({
+                "status": "confirmation_required",
+                "matched_count": matched_count,
+                "confirmation_token": token,
+                "expires_in_seconds": BULK_DELETE_TOKEN_TTL.as_secs()
+            }));
+        };
+
+        let confirmed = self.pending.lock().unwrap().remove(&token);
+        match confirmed {
+            Some(p) if p.expires_at > Instant::now() && p.tenant == ctx.tenant && p.filter == filter => {}
+            _ => return Err(AppError::BadRequest("Invalid or expired confirmation token".to_string())),
+        }
+
+        // 删除前记录被删除文档的 ID，便于事后追溯和恢复
+        let ids: Vec<Bson> = collection.distinct("_id", filter.clone()).await?;
+        let result = collection.update_many(active(doc! { "_id": { "$in": ids.clone() } }), mark_deleted(ctx)).await.map_err(|e| {
+            AppError::InternalServerError(format!("Failed to delete {}s: {}", entity, e))
+        })?;
+        let before = doc! { "filter": filter, "ids": ids };
+        let after = doc! { "deletedCount": result.modified_count as i64 };
+        audit.record(ctx.entry("bulk_delete", entity, None, Some(before), Some(after))).await?;
+        Ok(serde_json::json!({
+            "status": "success",
+            "deleted_count": result.modified_count
+        }))
+    }
+}
+
+pub async fn restore_one<T>(
+    collection: &Collection<T>,
+    filter: Document,
+) -> mongodb::error::Result<Option<T>>
+where
+    T: DeserializeOwned + Serialize + Send + Sync,
+{
+    let options = FindOneAndUpdateOptions::builder()
+        .return_document(ReturnDocument::After)
+        .build();
+    collection
+        .find_one_and_update(trashed(filter), doc! { "$unset": { "deletedAt": "", "deletedBy": "" } })
+        .with_options(options)
+        .await
+}
+
+pub async fn list_trash<T>(collection: &Collection<T>, filter: Document) -> mongodb::error::Result<Vec<T>>
+where
+    T: DeserializeOwned + Send + Sync,
+{
+    collection
+        .find(trashed(filter))
+        .sort(doc! { "deletedAt": -1 })
+        .await?
+        .try_collect()
+        .await
+}
+
+// 清除删除时间早于保留期的文档，返回被清除的文档以便调用方记录审计和清理关联文件。
+// 只删除读到的文档：读取之后才过期的留到下一轮，期间被恢复的不会删除，也不会出现在返回值中
+pub async fn purge_expired<T>(collection: &Collection<T>, retention: Duration, id: impl Fn(&T) -> ObjectId) -> mongodb::error::Result<Vec<T>>
+where
+    T: DeserializeOwned + Send + Sync,
+{
+    let cutoff: DateTime = (Utc::now() - chrono::Duration::from_std(retention).unwrap_or_default()).into();
+    let mut filter = doc! { "deletedAt": { "$ne": Bson::Null, "$lt": cutoff } };
+    let mut expired: Vec<T> = collection.find(filter.clone()).await?.try_collect().await?;
+    if expired.is_empty() {
+        return Ok(expired);
+    }
+    let ids: Vec<ObjectId> = expired.iter().map(&id).collect();
+    filter.insert("_id", doc! { "$in": &ids });
+    let result = collection.delete_many(filter).await?;
+    if result.deleted_count < ids.len() as u64 {
+        let remaining: Vec<Bson> = collection.distinct("_id", doc! { "_id": { "$in": &ids } }).await?;
+        expired.retain(|item| !remaining.contains(&Bson::ObjectId(id(item))));
+    }
+    Ok(expired)
+}
+
+// 启动定时清理任务，retention 为回收站保留期
+pub fn spawn_purge_task(
+    tracks: Arc<ShipTrackService>,
+    reports: Arc<ReportRawService>,
+    flights: Arc<FlightService>,
+    geofences: Arc<GeofenceService>,
+    missions: Arc<MissionService>,
+    retention: Duration,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match tracks.purge_expired(retention).await {
+                Ok(n) if n > 0 => tracing::info!("回收站清理航迹 {} 条", n),
+                Ok(_) => {}
+                Err(e) => tracing::error!("清理回收站航迹失败: {:?}", e),
+            }
+            match reports.purge_expired(retention).await {
+                Ok(n) if n > 0 => tracing::info!("回收站清理报告 {} 条", n),
+                Ok(_) => {}
+                Err(e) => tracing::error!("清理回收站报告失败: {:?}", e),
+            }
+            match flights.purge_expired(retention).await {
+                Ok(n) if n > 0 => tracing::info!("回收站清理飞行记录 {} 条", n),
+                Ok(_) => {}
+                Err(e) => tracing::error!("清理回收站飞行记录失败: {:?}", e),
+            }
+            match geofences.purge_expired(retention).await {
+                Ok(n) if n > 0 => tracing::info!("回收站清理围栏 {} 条", n),
+                Ok(_) => {}
+                Err(e) => tracing::error!("清理回收站围栏失败: {:?}", e),
+            }
+            match missions.purge_expired(retention).await {
+                Ok(n) if n > 0 => tracing::info!("回收站清理任务 {} 条", n),
+                Ok(_) => {}
+                Err(e) => tracing::error!("清理回收站任务失败: {:?}", e),
+            }
+        }
+    });
+}
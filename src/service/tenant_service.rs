@@ -0,0 +1,83 @@
+use bson::{doc, Document};
+use mongodb::Collection;
+use crate::model::tenant::{config_key, EffectiveTenantConfig, Tenant, TenantConfig, TenantConfigRequestDto};
+use crate::model::track_filter::TrackFilterConfig;
+
+pub const DEFAULT_AI_SYSTEM_PROMPT: &str = "你是一个报告智能报告生成体,用户会发送风机叶片检测之后的三个参数,分别为锈蚀情况,覆盖情况,损坏情况,这三个参数均在0到1之间代表百分数,你需要为其生成一份简短的报告以及维修建议,切记不要使用markdown格式";
+pub const DEFAULT_AI_MODEL: &str = "qwen-plus";
+
+pub struct TenantService {
+    pub collection: Collection<TenantConfig>,
+}
+
+impl TenantService {
+    pub fn new(collection: Collection<TenantConfig>) -> Self {
+        Self { collection }
+    }
+
+    // 风场级配置优先，其次组织级配置，最后使用内置默认值
+    pub async fn effective_config(&self, tenant: &Tenant) -> mongodb::error::Result<EffectiveTenantConfig> {
+        let farm = self.collection.find_one(config_key(&tenant.org_id, Some(&tenant.wind_farm_id))).await?;
+        let org = self.collection.find_one(config_key(&tenant.org_id, None)).await?;
+        let pick = |f: fn(&TenantConfig) -> Option<String>| {
+            farm.as_ref().and_then(f).or_else(|| org.as_ref().and_then(f))
+        };
+        Ok(EffectiveTenantConfig {
+            ai_system_prompt: pick(|c| c.ai_system_prompt.clone()).unwrap_or_else(|| DEFAULT_AI_SYSTEM_PROMPT.to_string()),
+            ai_model: pick(|c| c.ai_model.clone()).unwrap_or_else(|| DEFAULT_AI_MODEL.to_string()),
+            storage_quota_bytes: farm.as_ref().and_then(|c| c.storage_quota_bytes)
+                .or_else(|| org.as_ref().and_then(|c| c.storage_quota_bytes)),
+            track_filter: farm.as_ref().and_then(|c| c.track_filter.clone())
+                .or_else(|| org.as_ref().and_then(|c| c.track_filter.clone()))
+                .unwrap_or_default(),
+        })
+    }
+
+    pub async fn track_filter(&self, tenant: &Tenant) -> mongodb::error::Result<TrackFilterConfig> {
+        Ok(self.effective_config(tenant).await?.track_filter)
+    }
+
+    pub async fn upsert_config(&self, tenant: &Tenant, dto: TenantConfigRequestDto) -> mongodb::error::Result<()> {
+        let wind_farm_id = if dto.org_wide { None } else { Some(tenant.wind_farm_id.clone()) };
+        let config = TenantConfig {
+            org_id: tenant.org_id.clone(),
+            wind_farm_id: wind_farm_id.clone(),
+            ai_system_prompt: dto.ai_system_prompt,
+            ai_model: dto.ai_model,
+            storage_quota_bytes: dto.storage_quota_bytes,
+            track_filter: dto.track_filter,
+        };
+        self.collection
+            .replace_one(config_key(&tenant.org_id, wind_farm_id.as_deref()), config)
+            .upsert(true)
+            .await?;
+        Ok(())
+    }
+}
+
+// 按租户隔离之前写入的文档没有 orgId / windFarmId，任何租户都查不到；启动时把它们归入配置的默认租户。
+// 只补缺失的字段，已有租户的文档不变。返回被修改的文档数
+pub async fn assign_default_tenant(collections: &[Collection<Document>], tenant: &Tenant) -> mongodb::error::Result<u64> {
+    let update = vec![doc! { "$set": {
+        "orgId": { "$ifNull": ["$orgId", &tenant.org_id] },
+        "windFarmId": { "$ifNull": ["$windFarmId", &tenant.wind_farm_id] },
+    } }];
+    let mut modified = 0;
+    for collection in collections {
+        modified += collection.update_many(untenanted(), update.clone()).await?.modified_count;
+    }
+    Ok(modified)
+}
+
+// 没有配置默认租户时，统计还未归属租户的文档数以便提示
+pub async fn count_untenanted(collections: &[Collection<Document>]) -> mongodb::error::Result<u64> {
+    let mut count = 0;
+    for collection in collections {
+        count += collection.count_documents(untenanted()).await?;
+    }
+    Ok(count)
+}
+
+fn untenanted() -> Document {
+    doc! { "$or": [{ "orgId": { "$exists": false } }, { "windFarmId": { "$exists": false } }] }
+}